@@ -8,6 +8,10 @@ use std::net::IpAddr;
 const CONFIG_ENV_VAR: &str = "VERONYMOUS_ROUTER_AGENT_CONFIG";
 const DEFAULT_CONFIG_LOCATION: &str = "veronymous_router_agent_config.yml";
 
+const DEFAULT_MAX_REQUESTS_PER_SECOND: u32 = 5;
+const DEFAULT_MAX_ADDRESS_POOL_FRACTION: f64 = 0.9;
+const DEFAULT_REVOCATION_REFRESH_INTERVAL: u64 = 10;
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct RouterAgentConfig {
     pub host: IpAddr,
@@ -20,6 +24,10 @@ pub struct RouterAgentConfig {
 
     pub key_lifetime: u64,
 
+    // Seconds between the revocation accumulator refreshes, 0 to refresh with the key only
+    #[serde(default = "default_revocation_refresh_interval")]
+    pub revocation_refresh_interval: u64,
+
     pub wg_addresses: HashSet<String>,
 
     // TODO: Make required
@@ -31,7 +39,18 @@ pub struct RouterAgentConfig {
     // TODO: Make required
     pub wg_client_key: Option<String>,
 
-    // Subnet mask is 16; 0.0.0.0/16
+    // Wireguard servers sent to the clients
+    pub wg_servers: Vec<WGServerConfig>,
+
+    // DNS servers of the tunnel
+    pub wg_dns: Vec<String>,
+
+    pub wg_mtu: Option<u16>,
+
+    // Networks routed through the tunnel (CIDR)
+    pub wg_allowed_ips: Vec<String>,
+
+    // Gateway with the subnet prefix [16-30], 16 when not set; 0.0.0.0/16
     pub wg_gateway_ipv4: String,
 
     // Subnet mask is 112
@@ -53,11 +72,41 @@ pub struct RouterAgentConfig {
 
     pub token_domain: String,
 
+    // Connection requests per source ip per second, 0 for no limit
+    #[serde(default = "default_max_requests_per_second")]
+    pub max_requests_per_second: u32,
+
+    // Maximum fraction (0-1] of the address pool assigned at once
+    #[serde(default = "default_max_address_pool_fraction")]
+    pub max_address_pool_fraction: f64,
+
     pub tls_cert: Option<String>,
 
     pub tls_key: Option<String>,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct WGServerConfig {
+    // Base64 wireguard public key
+    pub public_key: String,
+
+    pub endpoint_host: String,
+
+    pub endpoint_port: u16,
+}
+
+fn default_max_requests_per_second() -> u32 {
+    DEFAULT_MAX_REQUESTS_PER_SECOND
+}
+
+fn default_max_address_pool_fraction() -> f64 {
+    DEFAULT_MAX_ADDRESS_POOL_FRACTION
+}
+
+fn default_revocation_refresh_interval() -> u64 {
+    DEFAULT_REVOCATION_REFRESH_INTERVAL
+}
+
 impl RouterAgentConfig {
     pub fn load() -> Result<Self, AgentError> {
         // Get the config location
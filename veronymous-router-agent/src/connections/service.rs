@@ -4,6 +4,7 @@ use crate::db::connections_db::ConnectionsDB;
 use crate::db::connections_state_db::redis::RedisConnectionsStateDB;
 use crate::db::connections_state_db::ConnectionsStateDB;
 use crate::error::AgentError;
+use crate::error::AgentError::{ConfigError, ResourceExhausted};
 use crate::wireguard::service::WireguardService;
 use crate::wireguard::WGKey;
 use std::net::{Ipv4Addr, Ipv6Addr};
@@ -23,6 +24,9 @@ pub struct ConnectionsService {
     epoch_length: u64,
 
     epoch_buffer: u64,
+
+    // Maximum assigned addresses
+    max_connections: u64,
 }
 
 // TODO: Schedule periodic clear connections
@@ -32,15 +36,26 @@ impl ConnectionsService {
         wg_service: WireguardService,
         connections_db: RedisConnectionsDB,
     ) -> Result<Arc<RwLock<Self>>, AgentError> {
+        if !(config.max_address_pool_fraction > 0.0 && config.max_address_pool_fraction <= 1.0) {
+            return Err(ConfigError(format!(
+                "Address pool fraction ({}) must be in (0, 1].",
+                config.max_address_pool_fraction
+            )));
+        }
+
         // Connect to redis
         let connections_state_db = RedisConnectionsStateDB::create(&config)?;
 
+        let max_connections = (connections_state_db.address_pool_size() as f64
+            * config.max_address_pool_fraction) as u64;
+
         let mut connections_service = Self {
             wg_service,
             connections_db,
             connections_state_db,
             epoch_length: config.epoch_length,
             epoch_buffer: config.epoch_buffer,
+            max_connections,
         };
 
         // Clear old connections
@@ -54,6 +69,20 @@ impl ConnectionsService {
         Ok(connections_service)
     }
 
+    // Reject the connections above the address pool limit
+    pub fn check_address_pool(&mut self, now: u64) -> Result<(), AgentError> {
+        let addresses = self.connections_state_db.count_addresses(now)?;
+
+        if addresses >= self.max_connections {
+            return Err(ResourceExhausted(format!(
+                "{} addresses are assigned, the limit is {}.",
+                addresses, self.max_connections
+            )));
+        }
+
+        Ok(())
+    }
+
     pub async fn add_connection(
         &mut self,
         public_key: &WGKey,
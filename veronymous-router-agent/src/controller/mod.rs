@@ -1 +1,2 @@
 pub mod router;
+pub mod status;
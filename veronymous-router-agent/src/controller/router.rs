@@ -1,8 +1,15 @@
+use crate::config::RouterAgentConfig;
+use crate::controller::status::{agent_error_status, status_with_details};
 use crate::error::AgentError;
+use crate::error::AgentError::ConfigError;
 use crate::grpc::router_agent_service::router_agent_service_server::RouterAgentService;
-use crate::grpc::router_agent_service::{ConnectionRequest, ConnectionResponse};
+use crate::grpc::router_agent_service::{
+    ConnectionRequest, ConnectionResponse, ErrorReason, WireguardServer,
+};
+use crate::rate_limiter::RateLimiter;
 use crate::router::service::RouterService;
 use crate::wireguard::WGKey;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tonic::{Code, Request, Response, Status};
@@ -11,11 +18,89 @@ use veronymous_token::token::VeronymousToken;
 
 pub struct RouterAgentController {
     service: Arc<Mutex<RouterService>>,
+
+    rate_limiter: Mutex<RateLimiter>,
+
+    epoch_length: u64,
+
+    // Wireguard peer config, the same for every connection
+    peer_config: ConnectionResponse,
 }
 
 impl RouterAgentController {
-    pub fn new(service: Arc<Mutex<RouterService>>) -> Self {
-        Self { service }
+    pub fn new(
+        config: &RouterAgentConfig,
+        service: Arc<Mutex<RouterService>>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            service,
+            rate_limiter: Mutex::new(RateLimiter::new(config)),
+            epoch_length: config.epoch_length,
+            peer_config: Self::peer_config(config)?,
+        })
+    }
+
+    fn peer_config(config: &RouterAgentConfig) -> Result<ConnectionResponse, AgentError> {
+        if config.wg_servers.is_empty() {
+            return Err(ConfigError("No wireguard server configured.".to_string()));
+        }
+
+        // 1) Decode the server keys
+        let mut servers = Vec::with_capacity(config.wg_servers.len());
+
+        for server in &config.wg_servers {
+            let public_key = base64::decode(&server.public_key).map_err(|e| {
+                ConfigError(format!(
+                    "Could not decode wireguard public key {}. {:?}",
+                    server.public_key, e
+                ))
+            })?;
+
+            if public_key.len() != 32 {
+                return Err(ConfigError(format!(
+                    "Wireguard public key {} is not 32 bytes.",
+                    server.public_key
+                )));
+            }
+
+            servers.push(WireguardServer {
+                public_key,
+                endpoint_host: server.endpoint_host.clone(),
+                endpoint_port: server.endpoint_port as u32,
+            });
+        }
+
+        // 2) Validate the dns servers
+        for dns in &config.wg_dns {
+            dns.parse::<IpAddr>()
+                .map_err(|e| ConfigError(format!("Invalid dns server {}. {:?}", dns, e)))?;
+        }
+
+        // 3) Validate the allowed ips
+        for allowed_ip in &config.wg_allowed_ips {
+            let valid = match allowed_ip.split_once('/') {
+                Some((ip, prefix)) => match (ip.parse::<IpAddr>(), prefix.parse::<u8>()) {
+                    (Ok(IpAddr::V4(_)), Ok(prefix)) => prefix <= 32,
+                    (Ok(IpAddr::V6(_)), Ok(prefix)) => prefix <= 128,
+                    _ => false,
+                },
+                None => false,
+            };
+
+            if !valid {
+                return Err(ConfigError(format!("Invalid allowed ips {}.", allowed_ip)));
+            }
+        }
+
+        Ok(ConnectionResponse {
+            ipv4_address: Vec::new(),
+            ipv6_address: Vec::new(),
+            expiry: 0,
+            servers,
+            dns: config.wg_dns.clone(),
+            mtu: config.wg_mtu.unwrap_or(0) as u32,
+            allowed_ips: config.wg_allowed_ips.clone(),
+        })
     }
 }
 
@@ -27,36 +112,44 @@ impl RouterAgentService for RouterAgentController {
     ) -> Result<Response<ConnectionResponse>, Status> {
         debug!("Got 'create_connection' request.");
 
+        // Throttle the source
+        let source = request.remote_addr().map(|remote_addr| remote_addr.ip());
+        if let Err(e) = self.rate_limiter.lock().await.check(source) {
+            debug!("{:?}", e);
+            return Err(agent_error_status(&e, self.epoch_length));
+        }
+
         let request = request.into_inner();
 
         // Decode the request values
-        let token = VeronymousToken::deserialize(&request.token)
-            .map_err(|_| Status::new(Code::InvalidArgument, "Invalid token."))?;
-        let wg_key: WGKey = request
-            .wg_key
-            .try_into()
-            .map_err(|_| Status::new(Code::InvalidArgument, "Invalid wireguard public key."))?;
+        let token = VeronymousToken::deserialize(&request.token).map_err(|_| {
+            status_with_details(
+                Code::InvalidArgument,
+                "Invalid token.",
+                ErrorReason::InvalidRequest,
+                None,
+            )
+        })?;
+        let wg_key: WGKey = request.wg_key.try_into().map_err(|_| {
+            status_with_details(
+                Code::InvalidArgument,
+                "Invalid wireguard public key.",
+                ErrorReason::InvalidRequest,
+                None,
+            )
+        })?;
 
         let mut service = self.service.lock().await;
 
         // Create the connection
-        let (ipv4_address, ipv6_address) = match service.create_connection(token, wg_key).await {
+        let (ipv4_address, ipv6_address, expiry) = match service
+            .create_connection(token, wg_key, request.epoch, &request.issuer_key_id)
+            .await
+        {
             Ok(connection) => connection,
             Err(err) => {
-                return match err {
-                    AgentError::DeserializationError(e) => {
-                        debug!("{:?}", e);
-                        Err(Status::invalid_argument("Received an invalid argument"))
-                    }
-                    AgentError::Unauthorized(e) => {
-                        debug!("{:?}", e);
-                        Err(Status::unauthenticated("Token verification failed."))
-                    }
-                    _ => {
-                        debug!("{:?}", err);
-                        Err(Status::aborted("Something went wrong"))
-                    }
-                }
+                debug!("{:?}", err);
+                return Err(agent_error_status(&err, self.epoch_length));
             }
         };
 
@@ -64,6 +157,8 @@ impl RouterAgentService for RouterAgentController {
         let connection_response = Response::new(ConnectionResponse {
             ipv4_address: Vec::from(ipv4_address.octets()),
             ipv6_address: Vec::from(ipv6_address.octets()),
+            expiry,
+            ..self.peer_config.clone()
         });
 
         Ok(connection_response)
@@ -0,0 +1,142 @@
+use crate::error::AgentError;
+use crate::grpc::router_agent_service::ErrorReason;
+use prost::Message;
+use prost_types::Any;
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic::codegen::Bytes;
+use tonic::{Code, Status};
+use tonic_types::pb::{ErrorInfo, RetryInfo, Status as RpcStatus};
+use veronymous_token::token::get_now_u64;
+
+/*
+* Errors are sent as google.rpc status details:
+* an ErrorInfo with the error reason and a RetryInfo when the request can be retried as is.
+* BACKEND_UNAVAILABLE errors state in the ErrorInfo metadata that the token was not spent.
+*/
+
+pub const ERROR_DOMAIN: &str = "router-agent.veronymous.io";
+
+pub const TOKEN_SPENT_METADATA_KEY: &str = "token_spent";
+
+const ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+const RATE_LIMIT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const BACKEND_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+pub fn agent_error_status(err: &AgentError, epoch_length: u64) -> Status {
+    let (code, reason, message, retry_delay) = match err {
+        AgentError::DeserializationError(_) => (
+            Code::InvalidArgument,
+            ErrorReason::InvalidRequest,
+            "Received an invalid argument",
+            None,
+        ),
+        AgentError::Unauthorized(_) => (
+            Code::Unauthenticated,
+            ErrorReason::InvalidToken,
+            "Token verification failed.",
+            None,
+        ),
+        AgentError::EpochExpired(_) => (
+            Code::FailedPrecondition,
+            ErrorReason::EpochExpired,
+            "Token was derived for an expired epoch.",
+            None,
+        ),
+        AgentError::WrongKey(_) => (
+            Code::FailedPrecondition,
+            ErrorReason::WrongKey,
+            "Token was issued with another issuer key.",
+            None,
+        ),
+        AgentError::DoubleSpend(_) => (
+            Code::AlreadyExists,
+            ErrorReason::DoubleSpend,
+            "Token was already used.",
+            None,
+        ),
+        // Addresses are released at the end of the epoch
+        AgentError::ResourceExhausted(_) => (
+            Code::ResourceExhausted,
+            ErrorReason::PoolExhausted,
+            "No connections available.",
+            Some(Duration::from_secs(
+                epoch_length - get_now_u64() % epoch_length,
+            )),
+        ),
+        AgentError::RateLimited(_) => (
+            Code::ResourceExhausted,
+            ErrorReason::RateLimited,
+            "Too many requests.",
+            Some(RATE_LIMIT_RETRY_DELAY),
+        ),
+        _ => (
+            Code::Unavailable,
+            ErrorReason::BackendUnavailable,
+            "Something went wrong",
+            Some(BACKEND_RETRY_DELAY),
+        ),
+    };
+
+    // The router service untraces the token when the connection fails after its verification
+    let mut metadata = HashMap::new();
+    if reason == ErrorReason::BackendUnavailable {
+        metadata.insert(TOKEN_SPENT_METADATA_KEY.to_string(), false.to_string());
+    }
+
+    status_with_metadata(code, message, reason, metadata, retry_delay)
+}
+
+pub fn status_with_details(
+    code: Code,
+    message: &str,
+    reason: ErrorReason,
+    retry_delay: Option<Duration>,
+) -> Status {
+    status_with_metadata(code, message, reason, HashMap::new(), retry_delay)
+}
+
+pub fn status_with_metadata(
+    code: Code,
+    message: &str,
+    reason: ErrorReason,
+    metadata: HashMap<String, String>,
+    retry_delay: Option<Duration>,
+) -> Status {
+    let error_info = ErrorInfo {
+        reason: reason.as_str_name().to_string(),
+        domain: ERROR_DOMAIN.to_string(),
+        metadata,
+    };
+
+    let mut details = vec![Any {
+        type_url: ERROR_INFO_TYPE_URL.to_string(),
+        value: error_info.encode_to_vec(),
+    }];
+
+    if let Some(retry_delay) = retry_delay {
+        let retry_info = RetryInfo {
+            retry_delay: Some(prost_types::Duration {
+                seconds: retry_delay.as_secs() as i64,
+                nanos: retry_delay.subsec_nanos() as i32,
+            }),
+        };
+
+        details.push(Any {
+            type_url: RETRY_INFO_TYPE_URL.to_string(),
+            value: retry_info.encode_to_vec(),
+        });
+    }
+
+    let status = RpcStatus {
+        code: code as i32,
+        message: message.to_string(),
+        details,
+    };
+
+    Status::with_details(code, message, Bytes::from(status.encode_to_vec()))
+}
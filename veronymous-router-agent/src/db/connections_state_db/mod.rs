@@ -1,8 +1,48 @@
 pub mod redis;
 
 use crate::error::AgentError;
+use crate::error::AgentError::ConfigError;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
+// The host id is also the ipv6 host id (112 bit network id), at most 16 bits
+const MIN_IPV4_PREFIX: u8 = 16;
+const MAX_IPV4_PREFIX: u8 = 30;
+
 pub trait ConnectionsStateDB {
     fn assign_address(&mut self, expiry: u64) -> Result<(Ipv4Addr, Ipv6Addr), AgentError>;
+
+    // Addresses assigned and not expired at 'now'
+    fn count_addresses(&mut self, now: u64) -> Result<u64, AgentError>;
+
+    fn address_pool_size(&self) -> u64;
+}
+
+/*
+* Parse the ipv4 gateway with its subnet prefix (10.8.0.1/16).
+* Prefix is 16 when not set.
+*/
+pub fn parse_gateway_ipv4(gateway: &str) -> Result<(Ipv4Addr, u8), AgentError> {
+    let (address, prefix) = match gateway.split_once('/') {
+        Some((address, prefix)) => (address, prefix.parse::<u8>().ok()),
+        None => (gateway, Some(MIN_IPV4_PREFIX)),
+    };
+
+    let address = address
+        .parse()
+        .map_err(|_| ConfigError(format!("Invalid ipv4 gateway {}.", gateway)))?;
+
+    match prefix {
+        Some(prefix) if (MIN_IPV4_PREFIX..=MAX_IPV4_PREFIX).contains(&prefix) => {
+            Ok((address, prefix))
+        }
+        _ => Err(ConfigError(format!(
+            "Ipv4 gateway prefix of {} must be in [{}, {}].",
+            gateway, MIN_IPV4_PREFIX, MAX_IPV4_PREFIX
+        ))),
+    }
+}
+
+// Host ids of the subnet without the network, broadcast and gateway addresses
+pub fn address_pool_size(prefix: u8) -> u64 {
+    (1u64 << (32 - prefix)) - 3
 }
@@ -1,19 +1,24 @@
 use crate::config::RouterAgentConfig;
-use crate::db::connections_state_db::ConnectionsStateDB;
+use crate::db::connections_state_db::{address_pool_size, parse_gateway_ipv4, ConnectionsStateDB};
 use crate::error::AgentError;
-use crate::error::AgentError::IpError;
+use crate::error::AgentError::{ConfigError, IpError};
 use rand::Rng;
 use redis::{Commands, Connection};
 use std::net::{Ipv4Addr, Ipv6Addr};
 
+// Host ids by expiry, the other keys are the 2 bytes host ids
+const ASSIGNED_ADDRESSES_KEY: &str = "assigned_addresses";
+
 /*
-* Subnet mask for ipv4 is 16 bit.
+* Subnet mask for ipv4 is 16 to 30 bit.
 * Subnet mask 112 for ipv6.
-* Ipv6 same number of network ids (16 bit) as ipv4.
-* NOTE: Assigns same 2 bytes host id for ipv6 and ipv4
+* NOTE: Assigns same host id (at most 16 bit) for ipv6 and ipv4
 */
 pub struct RedisConnectionsStateDB {
-    gateway_ipv4: [u8; 4],
+    gateway_ipv4: u32,
+
+    // Host id bits of the ipv4 subnet
+    host_mask: u32,
 
     gateway_ipv6: [u8; 16],
 
@@ -23,10 +28,13 @@ pub struct RedisConnectionsStateDB {
 impl RedisConnectionsStateDB {
     pub fn create(config: &RouterAgentConfig) -> Result<Self, AgentError> {
         // Parse the gateway addresses
-        let gateway_ipv4: Ipv4Addr = config.wg_gateway_ipv4.parse().unwrap();
-        let gateway_ipv4 = gateway_ipv4.octets();
+        let (gateway_ipv4, prefix) = parse_gateway_ipv4(&config.wg_gateway_ipv4)?;
+        let gateway_ipv4 = u32::from(gateway_ipv4);
+        let host_mask = u32::MAX >> prefix;
 
-        let gateway_ipv6: Ipv6Addr = config.wg_gateway_ipv6.parse().unwrap();
+        let gateway_ipv6: Ipv6Addr = config.wg_gateway_ipv6.parse().map_err(|_| {
+            ConfigError(format!("Invalid ipv6 gateway {}.", config.wg_gateway_ipv6))
+        })?;
         let gateway_ipv6 = gateway_ipv6.octets();
 
         let client = redis::Client::open(config.connections_state_redis_address.as_str()).map_err(
@@ -39,6 +47,7 @@ impl RedisConnectionsStateDB {
 
         Ok(Self {
             gateway_ipv4,
+            host_mask,
             gateway_ipv6,
             connection,
         })
@@ -52,15 +61,13 @@ impl ConnectionsStateDB for RedisConnectionsStateDB {
      */
     fn assign_address(&mut self, expire_at: u64) -> Result<(Ipv4Addr, Ipv6Addr), AgentError> {
         // Select random ip address
-        let mut addresses = self.random_ip_addresses();
-        let mut host_id: [u8; 2] = [addresses.0[2], addresses.0[3]];
+        let mut host_id = self.random_host_id();
         let mut find_address_attempts: u8 = 0;
 
         // Assign another if it already exists
-        // Maximum 10 attempts
+        // Maximum 20 attempts
         while self.host_id_exist(&host_id)? {
-            addresses = self.random_ip_addresses();
-            host_id = [addresses.0[2], addresses.0[3]];
+            host_id = self.random_host_id();
 
             find_address_attempts += 1;
 
@@ -72,31 +79,46 @@ impl ConnectionsStateDB for RedisConnectionsStateDB {
         // Assign the address
         self.store_host_id(&host_id, expire_at)?;
 
-        Ok((addresses.0.into(), addresses.1.into()))
+        Ok(self.ip_addresses(&host_id))
+    }
+
+    fn count_addresses(&mut self, now: u64) -> Result<u64, AgentError> {
+        // Drop the expired host ids
+        let _: () = self
+            .connection
+            .zrembyscore(ASSIGNED_ADDRESSES_KEY, "-inf", now)
+            .map_err(|err| {
+                AgentError::DBError(format!("Could not remove expired addresses. {:?}", err))
+            })?;
+
+        let count: u64 = self
+            .connection
+            .zcard(ASSIGNED_ADDRESSES_KEY)
+            .map_err(|err| AgentError::DBError(format!("Could not count addresses. {:?}", err)))?;
+
+        Ok(count)
+    }
+
+    fn address_pool_size(&self) -> u64 {
+        address_pool_size(self.host_mask.leading_zeros() as u8)
     }
 }
 
 impl RedisConnectionsStateDB {
-    fn random_ip_addresses(&self) -> ([u8; 4], [u8; 16]) {
-        let host_id = Self::random_host_id();
-
+    fn ip_addresses(&self, host_id: &[u8; 2]) -> (Ipv4Addr, Ipv6Addr) {
         // Ipv4
-        let ipv4_address = [
-            self.gateway_ipv4[0],
-            self.gateway_ipv4[1],
-            host_id[0],
-            host_id[1],
-        ];
+        let ipv4_address =
+            (self.gateway_ipv4 & !self.host_mask) | u16::from_be_bytes(*host_id) as u32;
 
         // Ipv6
         let mut ipv6_address: [u8; 16] = [0; 16];
 
         // Network id
-        ipv6_address[0..13].clone_from_slice(&self.gateway_ipv6[..13]);
+        ipv6_address[0..14].clone_from_slice(&self.gateway_ipv6[..14]);
         // Host id
-        ipv6_address[14..16].clone_from_slice(&host_id);
+        ipv6_address[14..16].clone_from_slice(host_id);
 
-        (ipv4_address, ipv6_address)
+        (ipv4_address.into(), ipv6_address.into())
     }
 
     fn host_id_exist(&mut self, host_id: &[u8; 2]) -> Result<bool, AgentError> {
@@ -120,13 +142,26 @@ impl RedisConnectionsStateDB {
             .expire_at(host_id, expire_at as usize)
             .map_err(|err| AgentError::DBError(format!("Could address expiration. {:?}", err)))?;
 
+        // Track the address for the pool count
+        let _: () = self
+            .connection
+            .zadd(ASSIGNED_ADDRESSES_KEY, host_id, expire_at)
+            .map_err(|err| AgentError::DBError(format!("Could not track ip address. {:?}", err)))?;
+
         Ok(())
     }
 
-    fn random_host_id() -> [u8; 2] {
+    // Not the network, broadcast or gateway address
+    fn random_host_id(&self) -> [u8; 2] {
         let mut rng = rand::thread_rng();
+        let gateway_host_id = self.gateway_ipv4 & self.host_mask;
+
+        loop {
+            let host_id = rng.gen_range(1, self.host_mask);
 
-        // [2-255, 0-255]
-        [rng.gen_range(2, 255), rng.gen_range(0, 255)]
+            if host_id != gateway_host_id {
+                return (host_id as u16).to_be_bytes();
+            }
+        }
     }
 }
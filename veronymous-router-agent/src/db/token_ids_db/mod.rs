@@ -12,6 +12,7 @@ pub trait TokenIDsDB {
         )
     }
 
+    // Saves the token id, true if it was already traced
     fn trace_token(
         &mut self,
         epoch: u64,
@@ -19,4 +20,7 @@ pub trait TokenIDsDB {
         now: u64,
         token_id: &SerialNumber,
     ) -> Result<bool, AgentError>;
+
+    // Removes the token id of a connection that could not be created
+    fn untrace_token(&mut self, epoch: u64, token_id: &SerialNumber) -> Result<(), AgentError>;
 }
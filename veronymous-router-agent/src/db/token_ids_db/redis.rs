@@ -36,33 +36,32 @@ impl TokenIDsDB for RedisTokenIDsDB {
 
         debug!("Tracing token_issuer id: {}", token_id_entry);
 
-        let exists: bool = self.connection.exists(&token_id_entry).map_err(|e| {
-            AgentError::DBError(format!("Could not query token_issuer id entry. {:?}", e))
-        })?;
+        // Set the key with its expiration if it does not exist, in one command
+        let next_epoch = get_next_epoch(now, epoch_length);
+        let saved: Option<String> = redis::cmd("SET")
+            .arg(&token_id_entry)
+            .arg(true)
+            .arg("NX")
+            .arg("EX")
+            .arg(next_epoch.saturating_sub(now).max(1))
+            .query(&mut self.connection)
+            .map_err(|e| AgentError::DBError(format!("Could not save token_issuer id. {:?}", e)))?;
 
-        if exists {
+        if saved.is_none() {
             debug!("Token id traced!");
             return Ok(true);
         }
 
-        debug!("Saving token_issuer id...");
+        Ok(false)
+    }
 
-        // Set the key
-        self.connection
-            .set(&token_id_entry, true)
-            .map_err(|e| AgentError::DBError(format!("Could not save token_issuer id. {:?}", e)))?;
+    fn untrace_token(&mut self, epoch: u64, token_id: &SerialNumber) -> Result<(), AgentError> {
+        let token_id_entry = Self::create_token_id_entry(epoch, token_id);
 
-        // Set expiration
-        let next_epoch = get_next_epoch(now, epoch_length);
-        self.connection
-            .expire_at(&token_id_entry, next_epoch as usize)
-            .map_err(|e| {
-                AgentError::DBError(format!(
-                    "Could not set token_issuer id expiration time. {:?}",
-                    e
-                ))
-            })?;
+        debug!("Removing token_issuer id: {}", token_id_entry);
 
-        Ok(false)
+        self.connection
+            .del::<_, ()>(&token_id_entry)
+            .map_err(|e| AgentError::DBError(format!("Could not remove token_issuer id. {:?}", e)))
     }
 }
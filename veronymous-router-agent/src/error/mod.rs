@@ -22,4 +22,19 @@ pub enum AgentError {
 
     #[error("Unauthorized. {0}")]
     Unauthorized(String),
+
+    #[error("Epoch expired. {0}")]
+    EpochExpired(String),
+
+    #[error("Wrong key. {0}")]
+    WrongKey(String),
+
+    #[error("Double spend. {0}")]
+    DoubleSpend(String),
+
+    #[error("Resource exhausted. {0}")]
+    ResourceExhausted(String),
+
+    #[error("Rate limited. {0}")]
+    RateLimited(String),
 }
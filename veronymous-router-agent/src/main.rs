@@ -22,6 +22,7 @@ mod controller;
 mod db;
 mod error;
 mod grpc;
+mod rate_limiter;
 mod router;
 mod token_issuer;
 mod wireguard;
@@ -56,7 +57,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Controller
     let router_agent_controller =
-        RouterAgentServiceServer::new(RouterAgentController::new(router_service));
+        RouterAgentServiceServer::new(RouterAgentController::new(&config, router_service).unwrap());
 
     let mut server_builder = Server::builder();
 
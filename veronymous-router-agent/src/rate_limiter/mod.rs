@@ -0,0 +1,72 @@
+use crate::config::RouterAgentConfig;
+use crate::error::AgentError;
+use crate::error::AgentError::RateLimited;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/*
+* Token bucket per source ip, the requests without a source ip share one bucket.
+* Runs before the token is deserialized and verified, a flood of invalid tokens
+* is rejected without computing pairings.
+*/
+pub struct RateLimiter {
+    requests_per_second: u32,
+
+    // Source ip -> (available requests, last refill)
+    buckets: HashMap<Option<IpAddr>, (f64, Instant)>,
+
+    last_prune: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RouterAgentConfig) -> Self {
+        Self {
+            requests_per_second: config.max_requests_per_second,
+            buckets: HashMap::new(),
+            last_prune: Instant::now(),
+        }
+    }
+
+    pub fn check(&mut self, source: Option<IpAddr>) -> Result<(), AgentError> {
+        // No limit
+        if self.requests_per_second == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let capacity = self.requests_per_second as f64;
+
+        self.prune(now);
+
+        // Refill the bucket
+        let (available, last_refill) = self.buckets.entry(source).or_insert((capacity, now));
+
+        let elapsed = now.duration_since(*last_refill).as_secs_f64();
+        *available = (*available + elapsed * capacity).min(capacity);
+        *last_refill = now;
+
+        if *available < 1.0 {
+            return Err(RateLimited(format!(
+                "Source {:?} is over {} requests per second.",
+                source, self.requests_per_second
+            )));
+        }
+
+        *available -= 1.0;
+
+        Ok(())
+    }
+
+    // Buckets refilled for a second are full, same as a new bucket
+    fn prune(&mut self, now: Instant) {
+        if now.duration_since(self.last_prune) < Duration::from_secs(1) {
+            return;
+        }
+
+        self.buckets.retain(|_, (_, last_refill)| {
+            now.duration_since(*last_refill) < Duration::from_secs(1)
+        });
+        self.last_prune = now;
+    }
+}
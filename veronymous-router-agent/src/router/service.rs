@@ -3,13 +3,14 @@ use crate::connections::service::ConnectionsService;
 use crate::db::token_ids_db::redis::RedisTokenIDsDB;
 use crate::db::token_ids_db::TokenIDsDB;
 use crate::error::AgentError;
-use crate::error::AgentError::Unauthorized;
+use crate::error::AgentError::{DoubleSpend, EpochExpired, Unauthorized, WrongKey};
 use crate::token_issuer::service::TokenService;
 use crate::wireguard::WGKey;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use veronymous_token::token::{get_current_epoch, get_now_u64, VeronymousToken};
+use veronymous_token::token::{get_current_epoch, get_now_u64, issuer_key_id, VeronymousToken};
+use veronymous_token::SerialNumber;
 
 pub struct RouterService {
     epoch_length: u64,
@@ -42,60 +43,138 @@ impl RouterService {
         }
     }
 
+    /*
+     * The token epoch and issuer key id are sent by the client, they only
+     * tell a failed token apart.
+     */
     pub async fn create_connection(
         &mut self,
         token: VeronymousToken,
         wg_key: WGKey,
-    ) -> Result<(Ipv4Addr, Ipv6Addr), AgentError> {
+        token_epoch: u64,
+        token_key_id: &[u8],
+    ) -> Result<(Ipv4Addr, Ipv6Addr, u64), AgentError> {
         // Get epoch and next epoch
         let now = get_now_u64();
         let epoch = self.get_current_epoch(now);
         let next_epoch = epoch + self.epoch_length;
 
-        // Verify the token
-        self.verify_token(&token, now, epoch).await?;
+        // Check the address pool before spending the token
+        self.connections_service
+            .write()
+            .await
+            .check_address_pool(now)?;
+
+        // Verify the token, it must be bound to the wireguard key
+        let serial_number = self
+            .verify_token(&token, &wg_key, token_epoch, token_key_id, now, epoch)
+            .await?;
 
         // Add the connection
-        let mut connections = self.connections_service.write().await;
-        let (ipv4_addr, ipv6_addr) = connections
+        let addresses = self
+            .connections_service
+            .write()
+            .await
             .add_connection(&wg_key, epoch, next_epoch)
-            .await?;
+            .await;
+
+        let (ipv4_addr, ipv6_addr) = match addresses {
+            Ok(addresses) => addresses,
+            Err(err) => return Err(self.release_token(epoch, &serial_number, err)),
+        };
 
-        Ok((ipv4_addr, ipv6_addr))
+        // The connection expires with the epoch
+        Ok((ipv4_addr, ipv6_addr, next_epoch))
     }
 
     /*
-     * Verify the access token
+     * Verify the access token and trace its serial number
      */
     async fn verify_token(
         &mut self,
         token: &VeronymousToken,
+        wg_key: &WGKey,
+        token_epoch: u64,
+        token_key_id: &[u8],
         now: u64,
         epoch: u64,
-    ) -> Result<(), AgentError> {
+    ) -> Result<SerialNumber, AgentError> {
         let token_service = self.token_service.read().await;
-        let (params, public_key, _) = token_service.get_token_params();
+        let (prepared_key, _, connection_limit, revocation_info) =
+            token_service.get_token_params();
 
         // Verify the token
         let result = token
-            .verify(&self.token_domain, epoch, &public_key, &params)
+            .verify_prepared(
+                &self.token_domain,
+                epoch,
+                connection_limit,
+                revocation_info.as_ref(),
+                Some(wg_key),
+                &prepared_key,
+            )
             .map_err(|e| Unauthorized(format!("Token verification failed. {:?}", e)))?;
 
         if !result {
-            return Err(Unauthorized(format!("Invalid auth token_issuer.")));
+            // Tell the client if it can derive a new token or needs a new root token.
+            // From the values sent with the token, a failed token costs a single verification.
+            if token_epoch != 0 && token_epoch != epoch {
+                return Err(EpochExpired(format!(
+                    "Token was derived for the epoch {}, the epoch is {}.",
+                    token_epoch, epoch
+                )));
+            }
+
+            if !token_key_id.is_empty() {
+                let key_id = issuer_key_id(&prepared_key.public_key)
+                    .map_err(|e| Unauthorized(format!("Could not get the key id. {:?}", e)))?;
+
+                if token_key_id != key_id {
+                    return Err(WrongKey(
+                        "Root token was signed with another issuer key.".to_string(),
+                    ));
+                }
+            }
+
+            return Err(Unauthorized("Invalid auth token_issuer.".to_string()));
         }
 
         // Trace the serial number
-        let serial_number = &token.serial_number().unwrap();
+        let serial_number = token
+            .serial_number()
+            .map_err(|e| Unauthorized(format!("Could not get the serial number. {:?}", e)))?;
 
         if self
             .token_ids_db
-            .trace_token(epoch, self.epoch_length, now, serial_number)?
+            .trace_token(epoch, self.epoch_length, now, &serial_number)?
         {
-            return Err(Unauthorized(format!("Attempted token_issuer id reuse.")));
+            return Err(DoubleSpend(format!("Attempted token_issuer id reuse.")));
         }
 
-        Ok(())
+        Ok(serial_number)
+    }
+
+    /*
+     * Untrace the serial number of a token whose connection failed, it can be sent again.
+     * A token that cannot be untraced is spent and must not be sent again.
+     */
+    fn release_token(
+        &mut self,
+        epoch: u64,
+        serial_number: &SerialNumber,
+        err: AgentError,
+    ) -> AgentError {
+        match self.token_ids_db.untrace_token(epoch, serial_number) {
+            Ok(()) => err,
+            Err(untrace_err) => {
+                error!("Could not untrace the token. {:?}", untrace_err);
+
+                DoubleSpend(format!(
+                    "Token was spent but the connection failed. {:?}",
+                    err
+                ))
+            }
+        }
     }
 
     fn get_current_epoch(&self, now: u64) -> u64 {
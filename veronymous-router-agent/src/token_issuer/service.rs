@@ -4,7 +4,9 @@ use crate::error::AgentError::{DeserializationError, ServiceError};
 use crate::token_issuer::grpc::token_service::veronymous_token_info_service_client::VeronymousTokenInfoServiceClient;
 use crate::token_issuer::grpc::token_service::TokenInfo as RpcTokenInfo;
 use crate::token_issuer::grpc::token_service::TokenInfoRequest;
+use ps_signatures::key_proof::PsKeyProof;
 use ps_signatures::keys::{PsParams, PsPublicKey};
+use ps_signatures::prepared::PreparedPsPublicKey;
 use ps_signatures::serde::Serializable;
 use std::fs;
 use std::str::FromStr;
@@ -13,6 +15,8 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use tokio::time::Instant;
 use tonic::transport::{Channel, Endpoint};
+use veronymous_token::revocation::RevocationInfo;
+use veronymous_token::serde::Serializable as TokenSerializable;
 use veronymous_token::token::{get_current_epoch, get_now_u64};
 
 const UPDATE_INTERVAL: u64 = 3;
@@ -20,6 +24,8 @@ const UPDATE_INTERVAL: u64 = 3;
 pub struct TokenService {
     key_lifetime: u64,
 
+    revocation_refresh_interval: u64,
+
     epoch_buffer: u64,
 
     client: VeronymousTokenInfoServiceClient<Channel>,
@@ -65,6 +71,7 @@ impl TokenService {
 
         let mut service = Self {
             key_lifetime: config.key_lifetime,
+            revocation_refresh_interval: config.revocation_refresh_interval,
             epoch_buffer: config.epoch_buffer,
             client,
             current_token_info: None,
@@ -79,28 +86,33 @@ impl TokenService {
         // Schedule token refresh
         Self::schedule_token_refresh(service.clone()).await;
 
+        // Schedule the revocation info refresh between the key updates
+        Self::schedule_revocation_refresh(service.clone()).await;
+
         Ok(service)
     }
 
-    pub fn get_token_params(&self) -> (PsParams, PsPublicKey, u64) {
+    pub fn get_token_params(&self) -> (Arc<PreparedPsPublicKey>, u64, u64, Option<RevocationInfo>) {
         if self.is_in_buffer() {
             debug!("In the buffer, using next keys...");
             // Return next key
             let token_info = self.next_token_info.as_ref().unwrap().clone();
 
             (
-                token_info.params,
-                token_info.public_key,
+                token_info.prepared_key,
                 self.current_epoch.as_ref().unwrap().clone(),
+                token_info.connection_limit,
+                token_info.revocation_info,
             )
         } else {
             let token_info = self.current_token_info.as_ref().unwrap().clone();
             debug!("Not in buffer, using current keys...");
 
             (
-                token_info.params,
-                token_info.public_key,
+                token_info.prepared_key,
                 self.current_epoch.as_ref().unwrap().clone(),
+                token_info.connection_limit,
+                token_info.revocation_info,
             )
         }
     }
@@ -183,13 +195,39 @@ impl TokenService {
         Ok(())
     }
 
+    /*
+     * Replace the revocation info of the current and next keys.
+     * Updates fetched for another key are ignored, the key update replaces them.
+     */
+    fn update_revocation_info(
+        &mut self,
+        current_token_info: TokenInfo,
+        next_token_info: TokenInfo,
+    ) {
+        for (token_info, update) in [
+            (&mut self.current_token_info, current_token_info),
+            (&mut self.next_token_info, next_token_info),
+        ] {
+            if let Some(token_info) = token_info {
+                if token_info.public_key == update.public_key {
+                    token_info.revocation_info = update.revocation_info;
+                }
+            }
+        }
+    }
+
     // Set current token_issuer info to next token_issuer info
     //self.current_token_info = self.next_token_info.clone();
     async fn fetch_token_info(&mut self) -> Result<TokenInfo, AgentError> {
+        Self::request_token_info(&mut self.client).await
+    }
+
+    async fn request_token_info(
+        client: &mut VeronymousTokenInfoServiceClient<Channel>,
+    ) -> Result<TokenInfo, AgentError> {
         let request = TokenInfoRequest {};
 
-        let rpc_token_info = self
-            .client
+        let rpc_token_info = client
             .get_token_info(tonic::Request::new(request))
             .await
             .map_err(|e| ServiceError(format!("Could not get token_issuer info: {:?}", e)))?
@@ -201,10 +239,15 @@ impl TokenService {
     }
 
     async fn fetch_next_token_info(&mut self) -> Result<TokenInfo, AgentError> {
+        Self::request_next_token_info(&mut self.client).await
+    }
+
+    async fn request_next_token_info(
+        client: &mut VeronymousTokenInfoServiceClient<Channel>,
+    ) -> Result<TokenInfo, AgentError> {
         let request = TokenInfoRequest {};
 
-        let rpc_token_info = self
-            .client
+        let rpc_token_info = client
             .get_next_token_info(tonic::Request::new(request))
             .await
             .map_err(|e| ServiceError(format!("Could not get token_issuer info: {:?}", e)))?
@@ -257,6 +300,56 @@ impl TokenService {
             }
         });
     }
+
+    /*
+     * Refetch the revocation accumulators every revocation refresh interval (0 to disable).
+     * Revoked tokens are rejected and updated witnesses accepted within the interval
+     * instead of at the next key update.
+     */
+    async fn schedule_revocation_refresh(service: Arc<RwLock<TokenService>>) {
+        let (refresh_interval, mut client) = {
+            let service_lock = service.read().await;
+
+            (
+                service_lock.revocation_refresh_interval,
+                service_lock.client.clone(),
+            )
+        };
+
+        if refresh_interval == 0 {
+            return;
+        }
+
+        info!("Scheduling revocation info refresh...");
+
+        let refresh_interval = Duration::from_secs(refresh_interval);
+
+        tokio::spawn(async move {
+            let mut interval_timer =
+                tokio::time::interval_at(Instant::now() + refresh_interval, refresh_interval);
+
+            loop {
+                interval_timer.tick().await;
+
+                // Fetch without holding the lock, the verifications keep running
+                let token_infos = async {
+                    let current_token_info = Self::request_token_info(&mut client).await?;
+                    let next_token_info = Self::request_next_token_info(&mut client).await?;
+
+                    Ok::<_, AgentError>((current_token_info, next_token_info))
+                }
+                .await;
+
+                match token_infos {
+                    Ok((current_token_info, next_token_info)) => service
+                        .write()
+                        .await
+                        .update_revocation_info(current_token_info, next_token_info),
+                    Err(e) => error!("Could not refresh the revocation info. {:?}", e),
+                }
+            }
+        });
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -265,7 +358,15 @@ pub struct TokenInfo {
 
     pub public_key: PsPublicKey,
 
+    // Verification tables of the key, built once per key lifetime
+    pub prepared_key: Arc<PreparedPsPublicKey>,
+
     pub key_lifetime: u64,
+
+    pub connection_limit: u64,
+
+    // Accumulator of the revoked root tokens
+    pub revocation_info: Option<RevocationInfo>,
 }
 
 impl TryFrom<RpcTokenInfo> for TokenInfo {
@@ -280,10 +381,43 @@ impl TryFrom<RpcTokenInfo> for TokenInfo {
             DeserializationError(format!("Could not deserialize ps public key. {:?}", e))
         })?;
 
+        // Reject malformed keys that could tag users
+        public_key
+            .validate(&params)
+            .map_err(|e| DeserializationError(format!("Invalid ps public key. {:?}", e)))?;
+
+        if !token_info.key_proof.is_empty() {
+            let key_proof = PsKeyProof::deserialize(&token_info.key_proof).map_err(|e| {
+                DeserializationError(format!("Could not deserialize key proof. {:?}", e))
+            })?;
+
+            let valid = key_proof.verify(&public_key, &params).map_err(|e| {
+                DeserializationError(format!("Could not verify key proof. {:?}", e))
+            })?;
+
+            if !valid {
+                return Err(DeserializationError("Invalid key proof.".to_string()));
+            }
+        }
+
+        let revocation_info = match token_info.revocation_info.is_empty() {
+            true => None,
+            false => Some(
+                RevocationInfo::deserialize(&token_info.revocation_info).map_err(|e| {
+                    DeserializationError(format!("Could not deserialize revocation info. {:?}", e))
+                })?,
+            ),
+        };
+
+        let prepared_key = Arc::new(PreparedPsPublicKey::new(&public_key, &params));
+
         Ok(Self {
             params,
             public_key,
+            prepared_key,
             key_lifetime: token_info.key_lifetime,
+            connection_limit: token_info.connection_limit.max(1) as u64,
+            revocation_info,
         })
     }
 }
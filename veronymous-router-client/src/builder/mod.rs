@@ -0,0 +1,206 @@
+use crate::error::RouterClientError;
+use crate::error::RouterClientError::GrpcError;
+use crate::grpc::router_agent_service::router_agent_service_client::RouterAgentServiceClient;
+use crate::VeronymousRouterClient;
+use std::str::FromStr;
+use std::time::Duration;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity};
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    // Attempts of a request, including the first one
+    pub max_attempts: u32,
+
+    // Delay before the first retry, doubled after every retry
+    pub initial_backoff: Duration,
+
+    // Longest delay the client waits before a retry
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VeronymousRouterClientBuilder {
+    endpoints: Vec<String>,
+
+    tls_ca: Option<Vec<u8>>,
+
+    // Client certificate and key (pem)
+    identity: Option<(Vec<u8>, Vec<u8>)>,
+
+    connect_timeout: Duration,
+
+    request_timeout: Duration,
+
+    retry_policy: RetryPolicy,
+}
+
+impl Default for VeronymousRouterClientBuilder {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            tls_ca: None,
+            identity: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+}
+
+impl VeronymousRouterClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Router agent endpoint, the endpoints are tried in order on failures.
+    // The same token is sent to the next endpoint, the router agents must share the token
+    // domain and the token ids database.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoints.push(endpoint.into());
+        self
+    }
+
+    pub fn endpoints<S: Into<String>>(mut self, endpoints: impl IntoIterator<Item = S>) -> Self {
+        self.endpoints
+            .extend(endpoints.into_iter().map(|endpoint| endpoint.into()));
+        self
+    }
+
+    // Custom tls certificate authority (pem)
+    pub fn tls_ca(mut self, tls_ca: &[u8]) -> Self {
+        self.tls_ca = Some(tls_ca.to_vec());
+        self
+    }
+
+    // Client certificate for mutual tls (pem)
+    pub fn identity(mut self, cert: &[u8], key: &[u8]) -> Self {
+        self.identity = Some((cert.to_vec(), key.to_vec()));
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // The endpoints are connected on the first request
+    pub fn build(self) -> Result<VeronymousRouterClient, RouterClientError> {
+        if self.endpoints.is_empty() {
+            return Err(GrpcError("No router agent endpoint.".to_string()));
+        }
+
+        if self.retry_policy.max_attempts == 0 {
+            return Err(GrpcError("Retry policy must allow 1 attempt.".to_string()));
+        }
+
+        // Tls config
+        let tls_config = match (&self.tls_ca, &self.identity) {
+            (None, None) => None,
+            (tls_ca, identity) => {
+                let mut tls_config = ClientTlsConfig::new();
+
+                if let Some(tls_ca) = tls_ca {
+                    tls_config = tls_config.ca_certificate(Certificate::from_pem(tls_ca));
+                }
+
+                if let Some((cert, key)) = identity {
+                    tls_config = tls_config.identity(Identity::from_pem(cert, key));
+                }
+
+                Some(tls_config)
+            }
+        };
+
+        let mut clients = Vec::with_capacity(self.endpoints.len());
+
+        for endpoint in &self.endpoints {
+            let mut channel_endpoint = Endpoint::from_str(endpoint).map_err(|e| {
+                GrpcError(format!("Could not parse endpoint {}. {:?}", endpoint, e))
+            })?;
+
+            channel_endpoint = channel_endpoint
+                .connect_timeout(self.connect_timeout)
+                .timeout(self.request_timeout);
+
+            if let Some(tls_config) = &tls_config {
+                channel_endpoint = channel_endpoint
+                    .tls_config(tls_config.clone())
+                    .map_err(|e| GrpcError(format!("Could not configure tls. {:?}", e)))?;
+            }
+
+            clients.push(RouterAgentServiceClient::new(
+                channel_endpoint.connect_lazy(),
+            ));
+        }
+
+        Ok(VeronymousRouterClient::from_clients(
+            clients,
+            self.retry_policy,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::RetryPolicy;
+    use crate::error::RouterClientError;
+    use crate::VeronymousRouterClient;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_builder() {
+        let client = VeronymousRouterClient::builder()
+            .endpoints(["http://127.0.0.1:7777", "http://127.0.0.1:7778"])
+            .connect_timeout(Duration::from_secs(1))
+            .request_timeout(Duration::from_secs(2))
+            .build()
+            .unwrap();
+        assert_eq!(2, client.clients.len());
+
+        // No endpoint
+        assert!(matches!(
+            VeronymousRouterClient::builder().build(),
+            Err(RouterClientError::GrpcError(_))
+        ));
+
+        // Invalid endpoint
+        assert!(VeronymousRouterClient::builder()
+            .endpoint("not an endpoint")
+            .build()
+            .is_err());
+
+        // No attempt
+        assert!(VeronymousRouterClient::builder()
+            .endpoint("http://127.0.0.1:7777")
+            .retry_policy(RetryPolicy {
+                max_attempts: 0,
+                ..RetryPolicy::default()
+            })
+            .build()
+            .is_err());
+    }
+}
@@ -1,4 +1,18 @@
+use crate::grpc::router_agent_service::ErrorReason;
+use prost::Message;
+use std::time::Duration;
 use thiserror::Error;
+use tonic::{Code, Status};
+use tonic_types::pb::{ErrorInfo, RetryInfo, Status as RpcStatus};
+
+const ERROR_DOMAIN: &str = "router-agent.veronymous.io";
+
+const ERROR_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.ErrorInfo";
+
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+// ErrorInfo metadata of the router agent errors that did not spend the token
+const TOKEN_SPENT_METADATA_KEY: &str = "token_spent";
 
 #[derive(Clone, Debug, Error)]
 pub enum RouterClientError {
@@ -8,6 +22,259 @@ pub enum RouterClientError {
     #[error("Connect error. {0}")]
     ConnectError(String),
 
+    #[error("Encoding error. {0}")]
+    EncodingError(String),
+
     #[error("Decoding error. {0}")]
     DecodingError(String),
+
+    #[error("Invalid request. {0}")]
+    InvalidRequest(String),
+
+    // The token is not valid, a new root token is needed
+    #[error("Invalid token. {0}")]
+    InvalidToken(String),
+
+    // Derive a token for the current epoch
+    #[error("Epoch expired. {0}")]
+    EpochExpired(String),
+
+    // Get a root token for the current issuer key
+    #[error("Wrong key. {0}")]
+    WrongKey(String),
+
+    // Derive a token with another connection index
+    #[error("Double spend. {0}")]
+    DoubleSpend(String),
+
+    // Retry the same token after the delay
+    #[error("Address pool exhausted. {0}")]
+    PoolExhausted(String, Option<Duration>),
+
+    #[error("Rate limited. {0}")]
+    RateLimited(String, Option<Duration>),
+
+    // Retried only when the router agent states that the token was not spent
+    #[error("Backend unavailable. {0}")]
+    BackendUnavailable(String, Option<Duration>),
+
+    // Router agent could not be reached
+    #[error("Unavailable. {0}")]
+    Unavailable(String),
+
+    // No answer in time, the request might have been processed and the token spent
+    #[error("Timed out. {0}")]
+    TimedOut(String),
+}
+
+impl RouterClientError {
+    // Delay after which the request can be retried as is
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::PoolExhausted(_, retry_after)
+            | Self::RateLimited(_, retry_after)
+            | Self::BackendUnavailable(_, retry_after) => *retry_after,
+            _ => None,
+        }
+    }
+
+    // The same request can be sent again, to this or another router agent
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::PoolExhausted(_, _)
+                | Self::RateLimited(_, _)
+                | Self::BackendUnavailable(_, Some(_))
+                | Self::Unavailable(_)
+        )
+    }
+}
+
+// Typed error from the google.rpc status details of the router agent
+impl From<Status> for RouterClientError {
+    fn from(status: Status) -> Self {
+        let details = RpcStatus::decode(status.details())
+            .map(|rpc_status| rpc_status.details)
+            .unwrap_or_default();
+
+        let mut reason = None;
+        let mut retry_after = None;
+        let mut token_spent = true;
+
+        for detail in details {
+            match detail.type_url.as_str() {
+                ERROR_INFO_TYPE_URL => {
+                    if let Ok(error_info) = ErrorInfo::decode(detail.value.as_slice()) {
+                        if error_info.domain == ERROR_DOMAIN {
+                            reason = ErrorReason::from_str_name(&error_info.reason);
+                            token_spent = error_info
+                                .metadata
+                                .get(TOKEN_SPENT_METADATA_KEY)
+                                .map(String::as_str)
+                                != Some("false");
+                        }
+                    }
+                }
+                RETRY_INFO_TYPE_URL => {
+                    retry_after = RetryInfo::decode(detail.value.as_slice())
+                        .ok()
+                        .and_then(|retry_info| retry_info.retry_delay)
+                        .filter(|delay| delay.seconds >= 0 && delay.nanos >= 0)
+                        .map(|delay| Duration::new(delay.seconds as u64, delay.nanos as u32));
+                }
+                _ => {}
+            }
+        }
+
+        let message = status.message().to_string();
+
+        match reason {
+            Some(ErrorReason::InvalidRequest) => Self::InvalidRequest(message),
+            Some(ErrorReason::InvalidToken) => Self::InvalidToken(message),
+            Some(ErrorReason::EpochExpired) => Self::EpochExpired(message),
+            Some(ErrorReason::WrongKey) => Self::WrongKey(message),
+            Some(ErrorReason::DoubleSpend) => Self::DoubleSpend(message),
+            Some(ErrorReason::PoolExhausted) => Self::PoolExhausted(message, retry_after),
+            Some(ErrorReason::RateLimited) => Self::RateLimited(message, retry_after),
+            // The token might have been spent, it is not sent again
+            Some(ErrorReason::BackendUnavailable) if token_spent => {
+                Self::BackendUnavailable(message, None)
+            }
+            Some(ErrorReason::BackendUnavailable) => Self::BackendUnavailable(message, retry_after),
+            Some(ErrorReason::UnknownError) | None => match status.code() {
+                Code::Unavailable => Self::Unavailable(message),
+                Code::DeadlineExceeded | Code::Cancelled => Self::TimedOut(message),
+                _ => Self::ConnectError(format!("Could not connect. {:?}", status)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::{RouterClientError, ERROR_DOMAIN, ERROR_INFO_TYPE_URL, RETRY_INFO_TYPE_URL};
+    use prost::Message;
+    use prost_types::Any;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tonic::codegen::Bytes;
+    use tonic::{Code, Status};
+    use tonic_types::pb::{ErrorInfo, RetryInfo, Status as RpcStatus};
+
+    fn status(code: Code, reason: &str, domain: &str, retry_delay: Option<i64>) -> Status {
+        status_with_metadata(code, reason, domain, HashMap::new(), retry_delay)
+    }
+
+    fn status_with_metadata(
+        code: Code,
+        reason: &str,
+        domain: &str,
+        metadata: HashMap<String, String>,
+        retry_delay: Option<i64>,
+    ) -> Status {
+        let mut details = vec![Any {
+            type_url: ERROR_INFO_TYPE_URL.to_string(),
+            value: ErrorInfo {
+                reason: reason.to_string(),
+                domain: domain.to_string(),
+                metadata,
+            }
+            .encode_to_vec(),
+        }];
+
+        if let Some(seconds) = retry_delay {
+            details.push(Any {
+                type_url: RETRY_INFO_TYPE_URL.to_string(),
+                value: RetryInfo {
+                    retry_delay: Some(prost_types::Duration { seconds, nanos: 0 }),
+                }
+                .encode_to_vec(),
+            });
+        }
+
+        let rpc_status = RpcStatus {
+            code: code as i32,
+            message: "message".to_string(),
+            details,
+        };
+
+        Status::with_details(code, "message", Bytes::from(rpc_status.encode_to_vec()))
+    }
+
+    #[test]
+    fn test_error_details() {
+        let err: RouterClientError = status(
+            Code::FailedPrecondition,
+            "EPOCH_EXPIRED",
+            ERROR_DOMAIN,
+            None,
+        )
+        .into();
+        assert!(matches!(err, RouterClientError::EpochExpired(_)));
+        assert_eq!(err.retry_after(), None);
+
+        let err: RouterClientError =
+            status(Code::AlreadyExists, "DOUBLE_SPEND", ERROR_DOMAIN, None).into();
+        assert!(matches!(err, RouterClientError::DoubleSpend(_)));
+        assert!(!err.is_retryable());
+
+        let err: RouterClientError = status(
+            Code::ResourceExhausted,
+            "POOL_EXHAUSTED",
+            ERROR_DOMAIN,
+            Some(42),
+        )
+        .into();
+        assert!(matches!(err, RouterClientError::PoolExhausted(_, _)));
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(42)));
+
+        // Token not spent
+        let err: RouterClientError = status_with_metadata(
+            Code::Unavailable,
+            "BACKEND_UNAVAILABLE",
+            ERROR_DOMAIN,
+            HashMap::from([("token_spent".to_string(), "false".to_string())]),
+            Some(5),
+        )
+        .into();
+        assert!(matches!(err, RouterClientError::BackendUnavailable(_, _)));
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(5)));
+
+        // The token might have been spent
+        let err: RouterClientError = status(
+            Code::Unavailable,
+            "BACKEND_UNAVAILABLE",
+            ERROR_DOMAIN,
+            Some(5),
+        )
+        .into();
+        assert!(matches!(err, RouterClientError::BackendUnavailable(_, _)));
+        assert!(!err.is_retryable());
+        assert_eq!(err.retry_after(), None);
+
+        // Other domain
+        let err: RouterClientError =
+            status(Code::FailedPrecondition, "EPOCH_EXPIRED", "other", None).into();
+        assert!(matches!(err, RouterClientError::ConnectError(_)));
+
+        // No details
+        let err: RouterClientError = Status::internal("message").into();
+        assert!(matches!(err, RouterClientError::ConnectError(_)));
+        assert!(!err.is_retryable());
+
+        let err: RouterClientError = Status::unavailable("message").into();
+        assert!(matches!(err, RouterClientError::Unavailable(_)));
+        assert!(err.is_retryable());
+
+        // Might have been processed, not sent again
+        let err: RouterClientError = Status::deadline_exceeded("message").into();
+        assert!(matches!(err, RouterClientError::TimedOut(_)));
+        assert!(!err.is_retryable());
+
+        let err: RouterClientError = Status::cancelled("message").into();
+        assert!(matches!(err, RouterClientError::TimedOut(_)));
+        assert!(!err.is_retryable());
+    }
 }
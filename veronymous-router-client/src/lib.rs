@@ -1,13 +1,15 @@
+use crate::builder::{RetryPolicy, VeronymousRouterClientBuilder};
 use crate::error::RouterClientError;
-use crate::error::RouterClientError::{ConnectError, GrpcError};
+use crate::error::RouterClientError::EncodingError;
 use crate::grpc::router_agent_service::router_agent_service_client::RouterAgentServiceClient;
 use crate::grpc::router_agent_service::ConnectionRequest;
 use crate::model::Connection;
-use std::str::FromStr;
-use tonic::transport::{Channel, Endpoint};
+use ps_signatures::keys::PsPublicKey;
+use tonic::transport::Channel;
 use veronymous_token::serde::Serializable;
-use veronymous_token::token::VeronymousToken;
+use veronymous_token::token::{issuer_key_id, VeronymousToken};
 
+pub mod builder;
 pub mod error;
 mod grpc;
 pub mod model;
@@ -17,51 +19,105 @@ const KEY_SIZE: usize = 32;
 pub type PublicKey = [u8; KEY_SIZE];
 
 pub struct VeronymousRouterClient {
-    client: RouterAgentServiceClient<Channel>,
+    // One client per router agent endpoint
+    clients: Vec<RouterAgentServiceClient<Channel>>,
+
+    retry_policy: RetryPolicy,
+
+    // Endpoint used for the next request
+    current: usize,
 }
 
 impl VeronymousRouterClient {
-    // TODO: Tls
-    pub async fn new(endpoint: &String, tls_ca: Option<&[u8]>) -> Result<Self, RouterClientError> {
-        let mut endpoint = Endpoint::from_str(endpoint.as_str())
-            .map_err(|e| GrpcError(format!("Could not parse endpoint. {:?}", e)))?;
-
-        // Custom tls encryption cert
-        if let Some(ca) = tls_ca {
-            let tls_ca = tonic::transport::Certificate::from_pem(ca);
+    pub fn builder() -> VeronymousRouterClientBuilder {
+        VeronymousRouterClientBuilder::new()
+    }
 
-            let tls_config = tonic::transport::ClientTlsConfig::new().ca_certificate(tls_ca);
+    // Client of a single endpoint with the default timeouts and retries
+    pub async fn new(endpoint: &str, tls_ca: Option<&[u8]>) -> Result<Self, RouterClientError> {
+        let mut builder = Self::builder().endpoint(endpoint);
 
-            endpoint = endpoint
-                .tls_config(tls_config)
-                .map_err(|e| GrpcError(format!("Could not add CA certificate. {:?}", e)))?;
+        if let Some(tls_ca) = tls_ca {
+            builder = builder.tls_ca(tls_ca);
         }
 
-        let client = RouterAgentServiceClient::connect(endpoint)
-            .await
-            .map_err(|e| GrpcError(format!("Could not connect to router agent. {:?}", e)))?;
+        builder.build()
+    }
 
-        Ok(Self { client })
+    pub(crate) fn from_clients(
+        clients: Vec<RouterAgentServiceClient<Channel>>,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            clients,
+            retry_policy,
+            current: 0,
+        }
     }
 
+    /*
+     * Connect with the wireguard key the token is bound to.
+     * The epoch and issuer key the token was derived with type the failures of the token.
+     * Retryable errors fail over to the next endpoint after a backoff, the router agents of
+     * the endpoints must share the token domain and the token ids database.
+     * Backend errors are only retried when the router agent did not spend the token.
+     * A request that timed out is not sent again, it might have been processed.
+     */
     pub async fn connect(
         &mut self,
         wg_key: PublicKey,
         token: VeronymousToken,
+        epoch: u64,
+        issuer_key: &PsPublicKey,
     ) -> Result<Connection, RouterClientError> {
-        // Assemble the request
-        let request = tonic::Request::new(ConnectionRequest {
-            wg_key: wg_key.to_vec(),
-            token: token.serialize(),
-        });
-
-        // Send the connection request
-        let response = self
-            .client
-            .create_connection(request)
-            .await
-            .map_err(|e| ConnectError(format!("Could not connect. {:?}", e)))?;
-
-        Ok(response.into_inner().try_into()?)
+        let token = token
+            .serialize()
+            .map_err(|e| EncodingError(format!("Could not encode token. {:?}", e)))?;
+
+        let issuer_key_id = issuer_key_id(issuer_key)
+            .map_err(|e| EncodingError(format!("Could not encode issuer key id. {:?}", e)))?;
+
+        let mut backoff = self.retry_policy.initial_backoff;
+        let mut attempts = 0;
+
+        loop {
+            // Assemble the request
+            let request = tonic::Request::new(ConnectionRequest {
+                wg_key: wg_key.to_vec(),
+                token: token.clone(),
+                epoch,
+                issuer_key_id: issuer_key_id.to_vec(),
+            });
+
+            // Send the connection request, failures are typed from the status details
+            let err = match self.clients[self.current].create_connection(request).await {
+                Ok(response) => return response.into_inner().try_into(),
+                Err(status) => RouterClientError::from(status),
+            };
+
+            attempts += 1;
+
+            if !err.is_retryable() || attempts >= self.retry_policy.max_attempts {
+                return Err(err);
+            }
+
+            // Fail over to the next endpoint
+            self.current = (self.current + 1) % self.clients.len();
+
+            // The retry delay of the router agent only holds for the same endpoint
+            let delay = match err.retry_after() {
+                Some(retry_after) if self.clients.len() == 1 => retry_after.max(backoff),
+                _ => backoff,
+            };
+
+            // Too long to wait, the caller gets the retry delay
+            if delay > self.retry_policy.max_backoff {
+                return Err(err);
+            }
+
+            tokio::time::sleep(delay).await;
+
+            backoff = (backoff * 2).min(self.retry_policy.max_backoff);
+        }
     }
 }
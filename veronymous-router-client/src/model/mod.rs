@@ -1,12 +1,124 @@
 use crate::error::RouterClientError;
 use crate::error::RouterClientError::DecodingError;
 use crate::grpc::router_agent_service::ConnectionResponse;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use crate::PublicKey;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+mod wg_quick;
 
 pub struct Connection {
     pub ipv4_address: Ipv4Addr,
 
     pub ipv6_address: Ipv6Addr,
+
+    // End of the epoch (unix seconds), the connection is removed after it
+    pub expiry: u64,
+
+    // Wireguard servers that accept the connection
+    pub servers: Vec<WireguardServer>,
+
+    pub dns: Vec<IpAddr>,
+
+    // None for the wg-quick default
+    pub mtu: Option<u16>,
+
+    // Networks routed through the tunnel
+    pub allowed_ips: Vec<AllowedIp>,
+}
+
+pub struct WireguardServer {
+    pub public_key: PublicKey,
+
+    pub endpoint_host: EndpointHost,
+
+    pub endpoint_port: u16,
+}
+
+// Network routed through the tunnel (CIDR)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllowedIp {
+    pub address: IpAddr,
+
+    pub prefix: u8,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EndpointHost {
+    Ip(IpAddr),
+
+    Domain(String),
+}
+
+impl FromStr for AllowedIp {
+    type Err = RouterClientError;
+
+    fn from_str(allowed_ip: &str) -> Result<Self, RouterClientError> {
+        let invalid = || DecodingError(format!("Invalid allowed ip {:?}.", allowed_ip));
+
+        let (address, prefix) = allowed_ip.split_once('/').ok_or_else(invalid)?;
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let prefix: u8 = prefix.parse().map_err(|_| invalid())?;
+
+        let max_prefix = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix > max_prefix {
+            return Err(invalid());
+        }
+
+        Ok(Self { address, prefix })
+    }
+}
+
+impl fmt::Display for AllowedIp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+/*
+* Ip literal or dns name: labels of 1 to 63 letters, digits and hyphens,
+* not starting or ending with a hyphen, at most 253 characters.
+*/
+impl FromStr for EndpointHost {
+    type Err = RouterClientError;
+
+    fn from_str(host: &str) -> Result<Self, RouterClientError> {
+        if let Ok(address) = host.parse() {
+            return Ok(Self::Ip(address));
+        }
+
+        let name = host.strip_suffix('.').unwrap_or(host);
+
+        let valid_label = |label: &str| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+
+        if name.is_empty() || name.len() > 253 || !name.split('.').all(valid_label) {
+            return Err(DecodingError(format!("Invalid endpoint host {:?}.", host)));
+        }
+
+        Ok(Self::Domain(host.to_string()))
+    }
+}
+
+// Ipv6 hosts are bracketed, to be followed by the port
+impl fmt::Display for EndpointHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ip(IpAddr::V6(address)) => write!(f, "[{}]", address),
+            Self::Ip(IpAddr::V4(address)) => write!(f, "{}", address),
+            Self::Domain(domain) => write!(f, "{}", domain),
+        }
+    }
 }
 
 impl TryFrom<ConnectionResponse> for Connection {
@@ -25,9 +137,130 @@ impl TryFrom<ConnectionResponse> for Connection {
             .map_err(|e| DecodingError(format!("Could not decode ipv4 address. {:?}", e)))?;
         let ipv6_address = Ipv6Addr::from(ipv6_address);
 
+        let servers = connection_response
+            .servers
+            .into_iter()
+            .map(|server| {
+                let public_key: PublicKey = server.public_key.try_into().map_err(|e| {
+                    DecodingError(format!("Could not decode server public key. {:?}", e))
+                })?;
+                let endpoint_port = server
+                    .endpoint_port
+                    .try_into()
+                    .map_err(|e| DecodingError(format!("Could not decode server port. {:?}", e)))?;
+
+                Ok(WireguardServer {
+                    public_key,
+                    endpoint_host: server.endpoint_host.parse()?,
+                    endpoint_port,
+                })
+            })
+            .collect::<Result<Vec<_>, RouterClientError>>()?;
+
+        let dns = connection_response
+            .dns
+            .iter()
+            .map(|dns| {
+                dns.parse()
+                    .map_err(|e| DecodingError(format!("Could not decode dns server. {:?}", e)))
+            })
+            .collect::<Result<Vec<_>, RouterClientError>>()?;
+
+        let mtu = match connection_response.mtu {
+            0 => None,
+            mtu => Some(
+                mtu.try_into()
+                    .map_err(|e| DecodingError(format!("Could not decode mtu. {:?}", e)))?,
+            ),
+        };
+
+        let allowed_ips = connection_response
+            .allowed_ips
+            .iter()
+            .map(|allowed_ip| allowed_ip.parse())
+            .collect::<Result<Vec<_>, RouterClientError>>()?;
+
         Ok(Self {
             ipv4_address,
             ipv6_address,
+            expiry: connection_response.expiry,
+            servers,
+            dns,
+            mtu,
+            allowed_ips,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::error::RouterClientError;
+    use crate::grpc::router_agent_service::{ConnectionResponse, WireguardServer};
+    use crate::model::{AllowedIp, Connection, EndpointHost};
+    use std::net::IpAddr;
+
+    fn connection_response(endpoint_host: &str, allowed_ip: &str) -> ConnectionResponse {
+        ConnectionResponse {
+            ipv4_address: vec![10, 8, 0, 2],
+            ipv6_address: vec![0; 16],
+            expiry: 1700000000,
+            servers: vec![WireguardServer {
+                public_key: vec![1; 32],
+                endpoint_host: endpoint_host.to_string(),
+                endpoint_port: 51820,
+            }],
+            dns: vec!["10.8.0.1".to_string()],
+            mtu: 0,
+            allowed_ips: vec![allowed_ip.to_string()],
+        }
+    }
+
+    #[test]
+    fn test_connection_response() {
+        let connection =
+            Connection::try_from(connection_response("wg1.ny.veronymous.io", "0.0.0.0/0")).unwrap();
+        assert_eq!(
+            connection.servers[0].endpoint_host,
+            EndpointHost::Domain("wg1.ny.veronymous.io".to_string())
+        );
+        assert_eq!(
+            connection.allowed_ips,
+            vec![AllowedIp {
+                address: IpAddr::from([0, 0, 0, 0]),
+                prefix: 0
+            }]
+        );
+
+        let connection = Connection::try_from(connection_response("2001:db8::1", "::/0")).unwrap();
+        assert_eq!(
+            connection.servers[0].endpoint_host,
+            EndpointHost::Ip("2001:db8::1".parse().unwrap())
+        );
+
+        // New lines would add wg-quick lines (PostUp)
+        for (endpoint_host, allowed_ip) in [
+            ("wg1.veronymous.io\nPostUp = touch /tmp/pwned", "0.0.0.0/0"),
+            ("wg1.veronymous.io", "0.0.0.0/0\nPostUp = touch /tmp/pwned"),
+        ] {
+            assert!(matches!(
+                Connection::try_from(connection_response(endpoint_host, allowed_ip)),
+                Err(RouterClientError::DecodingError(_))
+            ));
+        }
+
+        // Invalid hosts and networks
+        for endpoint_host in [
+            "",
+            "-wg.veronymous.io",
+            "wg..veronymous.io",
+            "wg_1.veronymous.io",
+        ] {
+            assert!(Connection::try_from(connection_response(endpoint_host, "0.0.0.0/0")).is_err());
+        }
+        for allowed_ip in ["0.0.0.0", "0.0.0.0/33", "::/129", "host/0"] {
+            assert!(
+                Connection::try_from(connection_response("wg1.veronymous.io", allowed_ip)).is_err()
+            );
+        }
+    }
+}
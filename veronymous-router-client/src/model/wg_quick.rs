@@ -0,0 +1,124 @@
+use crate::error::RouterClientError;
+use crate::error::RouterClientError::EncodingError;
+use crate::model::Connection;
+use std::fmt::Write;
+
+/*
+* wg-quick config of a connection.
+* The private key stays on the client, only its public key is sent to the router agent.
+*/
+
+impl Connection {
+    pub fn wg_quick_config(
+        &self,
+        private_key: &[u8; 32],
+        server: usize,
+    ) -> Result<String, RouterClientError> {
+        let server = self
+            .servers
+            .get(server)
+            .ok_or_else(|| EncodingError(format!("No wireguard server {}.", server)))?;
+
+        let mut config = String::new();
+
+        // 1) Interface
+        writeln!(config, "# Expires at {} (unix seconds)", self.expiry).unwrap();
+        writeln!(config, "[Interface]").unwrap();
+        writeln!(config, "PrivateKey = {}", base64::encode(private_key)).unwrap();
+        writeln!(
+            config,
+            "Address = {}/32, {}/128",
+            self.ipv4_address, self.ipv6_address
+        )
+        .unwrap();
+
+        if !self.dns.is_empty() {
+            let dns: Vec<String> = self.dns.iter().map(|dns| dns.to_string()).collect();
+            writeln!(config, "DNS = {}", dns.join(", ")).unwrap();
+        }
+
+        if let Some(mtu) = self.mtu {
+            writeln!(config, "MTU = {}", mtu).unwrap();
+        }
+
+        // 2) Peer
+        writeln!(config).unwrap();
+        writeln!(config, "[Peer]").unwrap();
+        writeln!(config, "PublicKey = {}", base64::encode(server.public_key)).unwrap();
+
+        writeln!(
+            config,
+            "Endpoint = {}:{}",
+            server.endpoint_host, server.endpoint_port
+        )
+        .unwrap();
+
+        if !self.allowed_ips.is_empty() {
+            let allowed_ips: Vec<String> = self
+                .allowed_ips
+                .iter()
+                .map(|allowed_ip| allowed_ip.to_string())
+                .collect();
+            writeln!(config, "AllowedIPs = {}", allowed_ips.join(", ")).unwrap();
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{Connection, EndpointHost, WireguardServer};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn test_wg_quick_config() {
+        let connection = Connection {
+            ipv4_address: Ipv4Addr::new(10, 8, 0, 2),
+            ipv6_address: "fd5d:4d78:92de::2".parse::<Ipv6Addr>().unwrap(),
+            expiry: 1700000000,
+            servers: vec![
+                WireguardServer {
+                    public_key: [1u8; 32],
+                    endpoint_host: EndpointHost::Domain("wg1.ny.veronymous.io".to_string()),
+                    endpoint_port: 51820,
+                },
+                WireguardServer {
+                    public_key: [2u8; 32],
+                    endpoint_host: EndpointHost::Ip("2001:db8::1".parse().unwrap()),
+                    endpoint_port: 51821,
+                },
+            ],
+            dns: vec!["10.8.0.1".parse().unwrap()],
+            mtu: Some(1420),
+            allowed_ips: vec!["0.0.0.0/0".parse().unwrap(), "::/0".parse().unwrap()],
+        };
+
+        let config = connection.wg_quick_config(&[3u8; 32], 0).unwrap();
+        assert_eq!(
+            config,
+            format!(
+                "# Expires at 1700000000 (unix seconds)\n\
+                [Interface]\n\
+                PrivateKey = {}\n\
+                Address = 10.8.0.2/32, fd5d:4d78:92de::2/128\n\
+                DNS = 10.8.0.1\n\
+                MTU = 1420\n\
+                \n\
+                [Peer]\n\
+                PublicKey = {}\n\
+                Endpoint = wg1.ny.veronymous.io:51820\n\
+                AllowedIPs = 0.0.0.0/0, ::/0\n",
+                base64::encode([3u8; 32]),
+                base64::encode([1u8; 32])
+            )
+        );
+
+        // Ipv6 endpoint
+        let config = connection.wg_quick_config(&[3u8; 32], 1).unwrap();
+        assert!(config.contains("Endpoint = [2001:db8::1]:51821\n"));
+
+        // No server
+        assert!(connection.wg_quick_config(&[3u8; 32], 2).is_err());
+    }
+}
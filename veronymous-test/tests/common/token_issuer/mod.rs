@@ -77,7 +77,13 @@ impl TokenManager {
     }
 
     // Issues a root token_issuer and derives an auth token_issuer from it
-    pub async fn get_auth_token(&mut self, num_tokens: usize) -> Vec<VeronymousToken> {
+    // The auth tokens are bound to the wireguard key
+    // Returns the tokens with their epoch and issuer key
+    pub async fn get_auth_token(
+        &mut self,
+        num_tokens: usize,
+        wg_key: &[u8],
+    ) -> (Vec<VeronymousToken>, u64, PsPublicKey) {
         // Get the issuer info
         // let issuer_info = self
         //     .token_info_service
@@ -101,6 +107,7 @@ impl TokenManager {
 
         let ps_params = PsParams::deserialize(&issuer_info.params).unwrap();
         let public_key = PsPublicKey::deserialize(&issuer_info.public_key).unwrap();
+        let connection_limit = issuer_info.connection_limit.max(1) as u64;
 
         // Generate the secret key
         let mut rng = thread_rng();
@@ -111,7 +118,7 @@ impl TokenManager {
         // Create the root token_issuer request
         let token_request =
             create_root_token_request(&token_id, &blinding, &public_key, &ps_params).unwrap();
-        let token_request = token_request.serialize();
+        let token_request = token_request.serialize().unwrap();
 
         // Send the root token_issuer request
         let token_response = match Self::is_in_buffer() {
@@ -150,19 +157,16 @@ impl TokenManager {
         for _ in 0..num_tokens {
             // Derive an authentication token_issuer
             let auth_token = root_token
-                .derive_token(
-                    AUTH_DOMAIN.as_bytes(),
-                    epoch,
-                    &public_key,
-                    &ps_params,
-                    &mut rng,
-                )
+                .derive_builder(AUTH_DOMAIN.as_bytes(), epoch, &public_key, &ps_params)
+                .connection(0, connection_limit)
+                .context(wg_key)
+                .derive(&mut rng)
                 .unwrap();
 
             auth_tokens.push(auth_token);
         }
 
-        auth_tokens
+        (auth_tokens, epoch, public_key)
     }
 
 
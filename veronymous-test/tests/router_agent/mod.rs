@@ -1,6 +1,7 @@
 use std::fs;
 use crate::common::token_issuer::TokenManager;
 use tokio::test;
+use veronymous_router_client::error::RouterClientError;
 use veronymous_router_client::model::Connection;
 use veronymous_router_client::{PublicKey, VeronymousRouterClient};
 
@@ -21,18 +22,22 @@ async fn create_connection() {
 
     // Connect to the client
     println!("Establishing connection...");
-    let mut client = VeronymousRouterClient::new(&ROUTER_AGENT_ENDPOINT.to_string(), &tls_ca)
+    let mut client = VeronymousRouterClient::new(ROUTER_AGENT_ENDPOINT, Some(&tls_ca))
         .await
         .unwrap();
 
-    // Issue an authentication token_issuer
-    let auth_token = token_issuer.get_auth_token(1).await.remove(0);
-
     // Wireguard key
     let wg_key: PublicKey = base64::decode(WG_KEY_1).unwrap().try_into().unwrap();
 
+    // Issue an authentication token_issuer
+    let (mut auth_tokens, epoch, issuer_key) = token_issuer.get_auth_token(1, &wg_key).await;
+    let auth_token = auth_tokens.remove(0);
+
     // Create a connection
-    let connection: Connection = client.connect(wg_key, auth_token).await.unwrap();
+    let connection: Connection = client
+        .connect(wg_key, auth_token, epoch, &issuer_key)
+        .await
+        .unwrap();
 
     println!("Connection created!");
     println!(
@@ -53,20 +58,23 @@ async fn connect_token_reuse() {
 
     // Connect to the client
     println!("Establishing connection...");
-    let mut client = VeronymousRouterClient::new(&ROUTER_AGENT_ENDPOINT.to_string(), &tls_ca)
+    let mut client = VeronymousRouterClient::new(ROUTER_AGENT_ENDPOINT, Some(&tls_ca))
         .await
         .unwrap();
 
+    // Wireguard key
+    let wg_key: PublicKey = base64::decode(WG_KEY_2).unwrap().try_into().unwrap();
+
     // Issue an authentication token_issuer
-    let mut auth_tokens = token_issuer.get_auth_token(2).await;
+    let (mut auth_tokens, epoch, issuer_key) = token_issuer.get_auth_token(2, &wg_key).await;
     let auth_token_1 = auth_tokens.remove(0);
     let auth_token_2 = auth_tokens.remove(0);
 
-    // Wireguard key
-    let wg_key: PublicKey = base64::decode(WG_KEY_2).unwrap().try_into().unwrap();
-
     // Create a connection
-    let connection: Connection = client.connect(wg_key.clone(), auth_token_1).await.unwrap();
+    let connection: Connection = client
+        .connect(wg_key.clone(), auth_token_1, epoch, &issuer_key)
+        .await
+        .unwrap();
 
     println!("Connection created!");
     println!(
@@ -77,6 +85,8 @@ async fn connect_token_reuse() {
 
     // Try authenticating again
     // Create a connection
-    let result = client.connect(wg_key.clone(), auth_token_2).await;
-    assert!(result.is_err());
+    let result = client
+        .connect(wg_key.clone(), auth_token_2, epoch, &issuer_key)
+        .await;
+    assert!(matches!(result, Err(RouterClientError::DoubleSpend(_))));
 }
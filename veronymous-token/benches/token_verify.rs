@@ -0,0 +1,116 @@
+/*
+* Per token verification cost on the router agent.
+* The agent deserializes the token, verifies it and reads its serial number.
+*/
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use crypto_common::rand_non_zero_fr;
+use ps_signatures::prepared::PreparedPsPublicKey;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use veronymous_token::issuer::TokenIssuer;
+use veronymous_token::root::RootVeronymousToken;
+use veronymous_token::root_exchange::{
+    complete_root_token, create_root_token_request, issue_root_token,
+};
+use veronymous_token::serde::Serializable;
+use veronymous_token::token::VeronymousToken;
+
+const DOMAIN: &[u8] = b"bench_domain";
+
+const NOW: u64 = 1643629600;
+
+const CONNECTION_LIMIT: u64 = 8;
+
+fn root_token(issuer: &TokenIssuer, rng: &mut StdRng) -> RootVeronymousToken {
+    let token_id = rand_non_zero_fr(rng);
+    let blinding = rand_non_zero_fr(rng);
+
+    let token_request =
+        create_root_token_request(&token_id, &blinding, &issuer.public_key, &issuer.params)
+            .unwrap();
+
+    let token_response = issue_root_token(
+        &token_request,
+        NOW + 30 * 24 * 60 * 60,
+        &[],
+        &issuer.signing_key,
+        &issuer.public_key,
+        &issuer.params,
+        rng,
+    )
+    .unwrap();
+
+    complete_root_token(
+        &token_response,
+        &token_id,
+        &blinding,
+        &issuer.public_key,
+        &issuer.params,
+    )
+    .unwrap()
+}
+
+fn bench_token_verify(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0);
+
+    let issuer = TokenIssuer::generate(&mut rng);
+    let root_token = root_token(&issuer, &mut rng);
+
+    let token = root_token
+        .derive_builder(DOMAIN, NOW, &issuer.public_key, &issuer.params)
+        .connection(0, CONNECTION_LIMIT)
+        .derive(&mut rng)
+        .unwrap();
+    let token_serialized = token.serialize().unwrap();
+
+    c.bench_function("root token signature verify", |b| {
+        b.iter(|| {
+            assert!(root_token
+                .verify(&issuer.public_key, &issuer.params)
+                .unwrap())
+        })
+    });
+
+    c.bench_function("token verify", |b| {
+        b.iter(|| {
+            assert!(token
+                .verify(
+                    DOMAIN,
+                    NOW,
+                    CONNECTION_LIMIT,
+                    None,
+                    None,
+                    &issuer.public_key,
+                    &issuer.params
+                )
+                .unwrap())
+        })
+    });
+
+    let prepared_key = PreparedPsPublicKey::new(&issuer.public_key, &issuer.params);
+
+    c.bench_function("token verify prepared", |b| {
+        b.iter(|| {
+            assert!(token
+                .verify_prepared(DOMAIN, NOW, CONNECTION_LIMIT, None, None, &prepared_key)
+                .unwrap())
+        })
+    });
+
+    // Full router agent path
+    c.bench_function("token deserialize verify serial number", |b| {
+        b.iter(|| {
+            let token = VeronymousToken::deserialize(&token_serialized).unwrap();
+
+            assert!(token
+                .verify_prepared(DOMAIN, NOW, CONNECTION_LIMIT, None, None, &prepared_key)
+                .unwrap());
+
+            token.serial_number().unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_token_verify);
+criterion_main!(benches);
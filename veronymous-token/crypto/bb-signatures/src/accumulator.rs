@@ -0,0 +1,204 @@
+/*
+* Pairing based universal accumulator with non-membership witnesses.
+* The accumulator secret key is a bb signing key (alpha): V = g1 ^ prod(y_i + alpha).
+* https://eprint.iacr.org/2020/777.pdf
+*/
+
+use crate::error::BbSignatureError;
+use crate::error::BbSignatureError::SerializationError;
+use crate::keys::{BbParams, BbPublicKey, BbSigningKey, SERIALIZED_G1_LENGTH};
+use crate::serde::{check_length, read_g1_point, Serializable};
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Bls12, Fr, G1};
+use pairing_plus::serdes::SerDes;
+use pairing_plus::{CurveProjective, Engine};
+use std::io::Cursor;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Accumulator(pub G1);
+
+impl Accumulator {
+    // Empty accumulator
+    pub fn new(params: &BbParams) -> Self {
+        Self(params.g1)
+    }
+
+    // V' = V ^ (y + alpha)
+    pub fn add(&mut self, element: &Fr, key: &BbSigningKey) {
+        let mut exponent = *element;
+        exponent.add_assign(&key.0);
+
+        self.0.mul_assign(exponent);
+    }
+
+    // V' = V ^ (1 / (y + alpha))
+    pub fn remove(&mut self, element: &Fr, key: &BbSigningKey) {
+        let mut exponent = *element;
+        exponent.add_assign(&key.0);
+
+        // y + alpha is 0 with negligible probability
+        if let Some(exponent) = exponent.inverse() {
+            self.0.mul_assign(exponent);
+        }
+    }
+
+    /*
+     * Issuer side witness for an element not in the accumulator.
+     * d = prod(y_i - y), C = (V / g1 ^ d) ^ (1 / (y + alpha))
+     * Returns None if the element is a member.
+     */
+    pub fn non_membership_witness(
+        &self,
+        element: &Fr,
+        members: &[Fr],
+        key: &BbSigningKey,
+        params: &BbParams,
+    ) -> Option<NonMembershipWitness> {
+        let mut d = Fr::one();
+        for member in members {
+            let mut difference = *member;
+            difference.sub_assign(element);
+            d.mul_assign(&difference);
+        }
+
+        if d.is_zero() {
+            return None;
+        }
+
+        let mut exponent = *element;
+        exponent.add_assign(&key.0);
+        let exponent = exponent.inverse()?;
+
+        let mut g1_d = params.g1;
+        g1_d.mul_assign(d);
+
+        let mut c = self.0;
+        c.sub_assign(&g1_d);
+        c.mul_assign(exponent);
+
+        Some(NonMembershipWitness { c, d })
+    }
+}
+
+impl Serializable for Accumulator {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_G1_LENGTH);
+
+        self.0
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize accumulator. {:?}", e)))?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_G1_LENGTH)?;
+
+        Ok(Self(read_g1_point(&mut Cursor::new(bytes))?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NonMembershipWitness {
+    pub c: G1,
+
+    // Non zero for non members
+    pub d: Fr,
+}
+
+impl NonMembershipWitness {
+    // e(C, g2 ^ y * public_key) * e(g1 ^ d, g2) == e(V, g2)
+    pub fn verify(
+        &self,
+        element: &Fr,
+        accumulator: &Accumulator,
+        public_key: &BbPublicKey,
+        params: &BbParams,
+    ) -> bool {
+        if self.d.is_zero() {
+            return false;
+        }
+
+        let mut g2_y_p = params.g2;
+        g2_y_p.mul_assign(*element);
+        g2_y_p.add_assign(&public_key.0);
+
+        let mut pairing_1 = Bls12::pairing(self.c, g2_y_p);
+
+        let mut g1_d = params.g1;
+        g1_d.mul_assign(self.d);
+        pairing_1.mul_assign(&Bls12::pairing(g1_d, params.g2));
+
+        let pairing_2 = Bls12::pairing(accumulator.0, params.g2);
+
+        pairing_1.eq(&pairing_2)
+    }
+
+    /*
+     * Public update after an element was added to the accumulator.
+     * C' = V + C * (y' - y), d' = d * (y' - y) where V is the accumulator before the addition.
+     */
+    pub fn update_on_add(&mut self, element: &Fr, added: &Fr, accumulator: &Accumulator) {
+        let mut difference = *added;
+        difference.sub_assign(element);
+
+        self.c.mul_assign(difference);
+        self.c.add_assign(&accumulator.0);
+
+        self.d.mul_assign(&difference);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::accumulator::Accumulator;
+    use crate::keys::{BbParams, BbSigningKey};
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::Fr;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_non_membership_witness() {
+        let mut rng = thread_rng();
+
+        let params = BbParams::generate(&mut rng);
+        let key = BbSigningKey::generate(&mut rng);
+        let public_key = key.derive_public_key(&params);
+
+        let members: Vec<Fr> = (0..3).map(|_| Fr::random(&mut rng)).collect();
+
+        let mut accumulator = Accumulator::new(&params);
+        for member in &members {
+            accumulator.add(member, &key);
+        }
+
+        // Non member
+        let element = Fr::random(&mut rng);
+        let mut witness = accumulator
+            .non_membership_witness(&element, &members, &key, &params)
+            .unwrap();
+        assert!(witness.verify(&element, &accumulator, &public_key, &params));
+
+        // Members don't have a witness
+        assert!(accumulator
+            .non_membership_witness(&members[0], &members, &key, &params)
+            .is_none());
+
+        // Witness is not valid for other elements
+        assert!(!witness.verify(&members[0], &accumulator, &public_key, &params));
+
+        // Public update
+        let added = Fr::random(&mut rng);
+        let previous_accumulator = accumulator.clone();
+        accumulator.add(&added, &key);
+
+        assert!(!witness.verify(&element, &accumulator, &public_key, &params));
+
+        witness.update_on_add(&element, &added, &previous_accumulator);
+        assert!(witness.verify(&element, &accumulator, &public_key, &params));
+
+        // Removing the element restores the previous accumulator
+        accumulator.remove(&added, &key);
+        assert_eq!(previous_accumulator, accumulator);
+    }
+}
@@ -0,0 +1,302 @@
+/*
+* Bb signature on a committed message (the signer never sees the message).
+* The holder sends commitment = g1 ^ b * h ^ m with a proof of knowledge of (m, b).
+* The signer picks e and returns signature = (g1 * commitment) ^ (1 / (e + s)).
+* Verification: e(signature, g2 ^ e * public_key) == e(g1 ^ (1 + b) * h ^ m, g2)
+* h is a second generator of G1 nobody knows the discrete log of with respect to g1.
+*/
+
+use crate::error::BbSignatureError;
+use crate::error::BbSignatureError::{ProofError, SerializationError, SigningError};
+use crate::keys::{
+    BbParams, BbPublicKey, BbSigningKey, SERIALIZED_FR_LENGTH, SERIALIZED_G1_LENGTH,
+};
+use crate::serde::{check_length, impl_base64_serde, read_fr, read_g1_point, Serializable};
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitting};
+use crypto_common::transcript::Transcript;
+use crypto_common::{multi_scalar_mul_const_time, multi_scalar_mul_var_time, rand_non_zero_fr};
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Bls12, Fr, G1};
+use pairing_plus::serdes::SerDes;
+use pairing_plus::{CurveProjective, Engine};
+use rand::CryptoRng;
+use std::io::Cursor;
+
+const TRANSCRIPT_PROTOCOL: &[u8] = b"bb_blind_signature_request_v1";
+
+const SERIALIZED_REQUEST_LENGTH: usize = 2 * SERIALIZED_G1_LENGTH + 2 * SERIALIZED_FR_LENGTH;
+
+const SERIALIZED_SIGNATURE_LENGTH: usize = SERIALIZED_G1_LENGTH + SERIALIZED_FR_LENGTH;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbBlindSignatureRequest {
+    pub commitment: G1,
+
+    pub randomness_commitment: G1,
+
+    pub message_response: Fr,
+
+    pub blinding_response: Fr,
+}
+
+impl BbBlindSignatureRequest {
+    pub fn new<R: CryptoRng + rand::RngCore>(
+        message: &Fr,
+        blinding: &Fr,
+        h: &G1,
+        params: &BbParams,
+        rng: &mut R,
+    ) -> Result<Self, BbSignatureError> {
+        // 1) commitment = g1 ^ b * h ^ m
+        let gens = [params.g1, *h];
+        let commitment = multi_scalar_mul_const_time(gens, [*blinding, *message]);
+
+        // 2) Proof of knowledge of (b, m)
+        let mut prover_committing = ProverCommitting::new();
+        prover_committing.commit(gens[0], Some(rand_non_zero_fr(rng)));
+        prover_committing.commit(gens[1], Some(rand_non_zero_fr(rng)));
+
+        let prover_committed = prover_committing.finish();
+
+        let mut challenge_bytes = prover_committed.challenge_bytes();
+        commitment.serialize(&mut challenge_bytes, false).unwrap();
+
+        let challenge = request_challenge(&challenge_bytes);
+
+        let mut proof = prover_committed
+            .generate_proof(&challenge, &[*blinding, *message])
+            .map_err(|e| ProofError(format!("Could not generate commitment proof. {:?}", e)))?;
+
+        Ok(Self {
+            commitment,
+            randomness_commitment: proof.commitment,
+            blinding_response: proof.responses.remove(0),
+            message_response: proof.responses.remove(0),
+        })
+    }
+
+    pub fn verify(&self, h: &G1, params: &BbParams) -> Result<bool, BbSignatureError> {
+        let gens = [params.g1, *h];
+        let proof = CommitmentProof::new(
+            self.randomness_commitment,
+            vec![self.blinding_response, self.message_response],
+        );
+
+        // Get the challenge
+        let mut challenge_bytes = proof.challenge_bytes(&gens);
+        self.commitment
+            .serialize(&mut challenge_bytes, false)
+            .unwrap();
+
+        let challenge = request_challenge(&challenge_bytes);
+
+        proof
+            .verify(&gens, &self.commitment, &challenge)
+            .map_err(|e| ProofError(format!("Could not verify commitment proof. {:?}", e)))
+    }
+}
+
+impl Serializable for BbBlindSignatureRequest {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_REQUEST_LENGTH);
+
+        for point in [&self.commitment, &self.randomness_commitment] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        for response in [&self.message_response, &self.blinding_response] {
+            response.serialize(&mut bytes, true).map_err(|e| {
+                SerializationError(format!("Could not serialize response. {:?}", e))
+            })?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_REQUEST_LENGTH)?;
+
+        let mut cursor = Cursor::new(bytes);
+
+        Ok(Self {
+            commitment: read_g1_point(&mut cursor)?,
+            randomness_commitment: read_g1_point(&mut cursor)?,
+            message_response: read_fr(&mut cursor)?,
+            blinding_response: read_fr(&mut cursor)?,
+        })
+    }
+}
+
+impl_base64_serde!(BbBlindSignatureRequest, BbBlindSignatureRequestVisitor);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbBlindSignature {
+    pub signature: G1,
+
+    pub e: Fr,
+}
+
+impl BbBlindSignature {
+    // Verify the request proof and sign the committed message
+    pub fn sign_request<R: CryptoRng + rand::RngCore>(
+        request: &BbBlindSignatureRequest,
+        h: &G1,
+        signing_key: &BbSigningKey,
+        params: &BbParams,
+        rng: &mut R,
+    ) -> Result<Self, BbSignatureError> {
+        // 1) Verify the commitment proof
+        if !request.verify(h, params)? {
+            return Err(SigningError(
+                "Commitment proof verification failed.".to_string(),
+            ));
+        }
+
+        // 2) 1 / (e + s)
+        let e = rand_non_zero_fr(rng);
+
+        let mut exponent = e;
+        exponent.add_assign(&signing_key.0);
+        let exponent = exponent
+            .inverse()
+            .ok_or_else(|| SigningError("Cannot sign this request.".to_string()))?;
+
+        // 3) signature = (g1 * commitment) ^ (1 / (e + s))
+        let mut base = params.g1;
+        base.add_assign(&request.commitment);
+
+        Ok(Self {
+            signature: multi_scalar_mul_const_time([base], [exponent]),
+            e,
+        })
+    }
+
+    pub fn verify(
+        &self,
+        message: &Fr,
+        blinding: &Fr,
+        h: &G1,
+        public_key: &BbPublicKey,
+        params: &BbParams,
+    ) -> bool {
+        if self.signature.is_zero() {
+            return false;
+        }
+
+        // e(signature, g2 ^ e * public_key) * e(-(g1 ^ (1 + b) * h ^ m), g2) == 1
+        let mut g2_e_p = params.g2;
+        g2_e_p.mul_assign(self.e);
+        g2_e_p.add_assign(&public_key.0);
+
+        let mut exponent = *blinding;
+        exponent.add_assign(&Fr::one());
+
+        let mut base = multi_scalar_mul_var_time([params.g1, *h], [exponent, *message]);
+        base.negate();
+
+        let product = Bls12::pairing_product(self.signature, g2_e_p, base, params.g2);
+
+        product == <Bls12 as Engine>::Fqk::one()
+    }
+}
+
+impl Serializable for BbBlindSignature {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_SIGNATURE_LENGTH);
+
+        self.signature
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize signature. {:?}", e)))?;
+        self.e
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize e. {:?}", e)))?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_SIGNATURE_LENGTH)?;
+
+        let mut cursor = Cursor::new(bytes);
+
+        Ok(Self {
+            signature: read_g1_point(&mut cursor)?,
+            e: read_fr(&mut cursor)?,
+        })
+    }
+}
+
+impl_base64_serde!(BbBlindSignature, BbBlindSignatureVisitor);
+
+fn request_challenge(challenge_bytes: &[u8]) -> Fr {
+    let mut transcript = Transcript::new(TRANSCRIPT_PROTOCOL);
+    transcript.append_message(b"request", challenge_bytes);
+
+    transcript.challenge_fr(b"challenge")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blind_signature::{BbBlindSignature, BbBlindSignatureRequest};
+    use crate::keys::{BbParams, BbSigningKey};
+    use crate::serde::Serializable;
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::{Fr, G1};
+    use pairing_plus::CurveProjective;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_bb_blind_signature() {
+        let mut rng = thread_rng();
+
+        // Setup
+        let params = BbParams::generate(&mut rng);
+        let h = G1::random(&mut rng);
+
+        let signing_key = BbSigningKey::generate(&mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        // 1) Request a signature on a hidden message
+        let message = Fr::random(&mut rng);
+        let blinding = Fr::random(&mut rng);
+
+        let request =
+            BbBlindSignatureRequest::new(&message, &blinding, &h, &params, &mut rng).unwrap();
+
+        let request_serialized = request.serialize().unwrap();
+        let request_deserialized =
+            BbBlindSignatureRequest::deserialize(&request_serialized).unwrap();
+
+        assert_eq!(request, request_deserialized);
+
+        // 2) Sign
+        let signature =
+            BbBlindSignature::sign_request(&request, &h, &signing_key, &params, &mut rng).unwrap();
+
+        let signature_serialized = signature.serialize().unwrap();
+        let signature_deserialized = BbBlindSignature::deserialize(&signature_serialized).unwrap();
+
+        assert_eq!(signature, signature_deserialized);
+
+        // 3) Verify
+        assert!(signature.verify(&message, &blinding, &h, &public_key, &params));
+
+        let other_message = Fr::random(&mut rng);
+        assert!(!signature.verify(&other_message, &blinding, &h, &public_key, &params));
+
+        let other_public_key = BbSigningKey::generate(&mut rng).derive_public_key(&params);
+        assert!(!signature.verify(&message, &blinding, &h, &other_public_key, &params));
+
+        // Tampered request
+        let mut bad_request = request.clone();
+        bad_request.message_response = Fr::random(&mut rng);
+
+        assert!(!bad_request.verify(&h, &params).unwrap());
+        assert!(
+            BbBlindSignature::sign_request(&bad_request, &h, &signing_key, &params, &mut rng)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+#[derive(Clone, Debug, Error)]
+pub enum BbSignatureError {
+    #[error("Invalid argument. {0}")]
+    InvalidArgumentError(String),
+
+    #[error("Signing error. {0}")]
+    SigningError(String),
+
+    #[error("Proof error. {0}")]
+    ProofError(String),
+
+    #[error("Serialization error. {0}")]
+    SerializationError(String),
+
+    #[error("Deserialization error. {0}")]
+    DeserializationError(String),
+
+    #[error("Identity point. {0}")]
+    IdentityPointError(String),
+
+    #[error("Point not in the prime order subgroup. {0}")]
+    SubgroupError(String),
+}
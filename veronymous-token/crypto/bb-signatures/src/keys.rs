@@ -1,9 +1,22 @@
+use crate::error::BbSignatureError;
+use crate::error::BbSignatureError::SerializationError;
+use crate::serde::{
+    check_length, impl_base64_serde, read_fr, read_g1_point, read_g2_point, Serializable,
+};
 use crypto_common::rand_non_zero_fr;
 use pairing_plus::bls12_381::{Fr, G1, G2};
+use pairing_plus::serdes::SerDes;
 use pairing_plus::CurveProjective;
 use rand::CryptoRng;
+use std::io::Cursor;
 
-#[derive(Clone, Debug)]
+pub(crate) const SERIALIZED_G1_LENGTH: usize = 48;
+pub(crate) const SERIALIZED_G2_LENGTH: usize = 96;
+pub(crate) const SERIALIZED_FR_LENGTH: usize = 32;
+
+const SERIALIZED_PARAMS_LENGTH: usize = SERIALIZED_G1_LENGTH + SERIALIZED_G2_LENGTH;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct BbParams {
     pub g1: G1,
     pub g2: G2,
@@ -18,7 +31,36 @@ impl BbParams {
     }
 }
 
-#[derive(Clone, Debug)]
+impl Serializable for BbParams {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_PARAMS_LENGTH);
+
+        self.g1
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize g1. {:?}", e)))?;
+
+        self.g2
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize g2. {:?}", e)))?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_PARAMS_LENGTH)?;
+
+        let mut cursor = Cursor::new(bytes);
+
+        let g1 = read_g1_point(&mut cursor)?;
+        let g2 = read_g2_point(&mut cursor)?;
+
+        Ok(Self { g1, g2 })
+    }
+}
+
+impl_base64_serde!(BbParams, BbParamsVisitor);
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct BbSigningKey(pub Fr);
 
 impl BbSigningKey {
@@ -27,19 +69,58 @@ impl BbSigningKey {
     }
 
     pub fn derive_public_key(&self, params: &BbParams) -> BbPublicKey {
-        let mut g = params.g2.clone();
-        g.mul_assign(self.0.clone());
+        let mut g = params.g2;
+        g.mul_assign(self.0);
 
         BbPublicKey(g)
     }
 }
 
-#[derive(Clone, Debug)]
+impl Serializable for BbSigningKey {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_FR_LENGTH);
+
+        self.0
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize signing key. {:?}", e)))?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_FR_LENGTH)?;
+
+        Ok(Self(read_fr(&mut Cursor::new(bytes))?))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct BbPublicKey(pub G2);
 
+impl Serializable for BbPublicKey {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_G2_LENGTH);
+
+        self.0
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize public key. {:?}", e)))?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_G2_LENGTH)?;
+
+        Ok(Self(read_g2_point(&mut Cursor::new(bytes))?))
+    }
+}
+
+impl_base64_serde!(BbPublicKey, BbPublicKeyVisitor);
+
 #[cfg(test)]
 mod tests {
-    use crate::keys::{BbParams, BbSigningKey};
+    use crate::keys::{BbParams, BbPublicKey, BbSigningKey};
+    use crate::serde::Serializable;
     use rand::thread_rng;
 
     #[test]
@@ -49,10 +130,31 @@ mod tests {
         // 1) Generate the parameters
         let params = BbParams::generate(&mut rng);
 
+        let params_serialized = params.serialize().unwrap();
+        let params_deserialized = BbParams::deserialize(&params_serialized).unwrap();
+
+        assert_eq!(params, params_deserialized);
+
         // 2) Generate the secret key
         let secret_key = BbSigningKey::generate(&mut rng);
 
+        let secret_key_serialized = secret_key.serialize().unwrap();
+        let secret_key_deserialized = BbSigningKey::deserialize(&secret_key_serialized).unwrap();
+
+        assert_eq!(secret_key, secret_key_deserialized);
+
         // 3) Derive public key
-        let _public_key = secret_key.derive_public_key(&params);
+        let public_key = secret_key.derive_public_key(&params);
+
+        let public_key_serialized = public_key.serialize().unwrap();
+        let public_key_deserialized = BbPublicKey::deserialize(&public_key_serialized).unwrap();
+
+        assert_eq!(public_key, public_key_deserialized);
+
+        // Trailing bytes
+        let mut bad_bytes = public_key_serialized.clone();
+        bad_bytes.push(0);
+
+        assert!(BbPublicKey::deserialize(&bad_bytes).is_err());
     }
 }
@@ -1,2 +1,8 @@
+pub mod accumulator;
+pub mod blind_signature;
+pub mod error;
 pub mod keys;
+pub mod pok_blind_sig;
+pub mod pok_sig;
+pub mod serde;
 pub mod signature;
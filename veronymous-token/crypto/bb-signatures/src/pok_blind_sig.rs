@@ -0,0 +1,385 @@
+/*
+* Proof of knowledge of a bb signature on a committed message (see blind_signature).
+* signature' = signature ^ r, d = (g1 ^ (1 + b) * h ^ m) ^ r, signature_bar = signature' ^ (-e) * d
+* (= signature' ^ s). The verifier checks e(signature_bar, g2) == e(signature', public_key) with
+* the proofs of knowledge of e in signature_bar / d = (1 / signature') ^ e
+* and of (1 / r, m, b) in g1 = d ^ (1 / r) * (1 / h) ^ m * (1 / g1) ^ b.
+* Every element is randomized: proofs of the same signature cannot be linked.
+* The message response can be linked to other proofs by sharing its blinding factor.
+*/
+
+use crate::blind_signature::BbBlindSignature;
+use crate::error::BbSignatureError;
+use crate::error::BbSignatureError::{ProofError, SerializationError};
+use crate::keys::{BbParams, BbPublicKey, SERIALIZED_FR_LENGTH, SERIALIZED_G1_LENGTH};
+use crate::serde::{check_length, read_fr, read_g1_point, Serializable};
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitted, ProverCommitting};
+use crypto_common::{multi_scalar_mul_const_time, rand_non_zero_fr};
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Bls12, Fr, G1};
+use pairing_plus::serdes::SerDes;
+use pairing_plus::{CurveProjective, Engine};
+use rand::CryptoRng;
+use std::io::Cursor;
+
+const SERIALIZED_POK_LENGTH: usize = 5 * SERIALIZED_G1_LENGTH + 4 * SERIALIZED_FR_LENGTH;
+
+pub struct BbPokOfBlindSignatureCommitted {
+    sigma_prime: G1,
+
+    sigma_bar: G1,
+
+    d: G1,
+
+    e: Fr,
+
+    r_inverse: Fr,
+
+    message: Fr,
+
+    blinding: Fr,
+
+    e_committed: ProverCommitted<G1>,
+
+    committed: ProverCommitted<G1>,
+}
+
+impl BbPokOfBlindSignatureCommitted {
+    pub fn new<R: CryptoRng + rand::RngCore>(
+        signature: &BbBlindSignature,
+        message: &Fr,
+        blinding: &Fr,
+        message_blinding_factor: Option<Fr>,
+        h: &G1,
+        params: &BbParams,
+        rng: &mut R,
+    ) -> Result<Self, BbSignatureError> {
+        // 1) Randomize the signature and the signed commitment
+        let r = rand_non_zero_fr(rng);
+        let r_inverse = r
+            .inverse()
+            .ok_or_else(|| ProofError("Could not randomize the signature.".to_string()))?;
+
+        let sigma_prime = multi_scalar_mul_const_time([signature.signature], [r]);
+
+        let mut exponent = *blinding;
+        exponent.add_assign(&Fr::one());
+        exponent.mul_assign(&r);
+
+        let mut message_r = *message;
+        message_r.mul_assign(&r);
+
+        let d = multi_scalar_mul_const_time([params.g1, *h], [exponent, message_r]);
+
+        // 2) signature_bar = (1 / signature') ^ e * d
+        let e_gens = e_pok_gens(&sigma_prime);
+
+        let mut sigma_bar = multi_scalar_mul_const_time(e_gens, [signature.e]);
+        sigma_bar.add_assign(&d);
+
+        // 3) Commit to the randomness
+        let mut e_committing = ProverCommitting::new();
+        e_committing.commit(e_gens[0], Some(rand_non_zero_fr(rng)));
+
+        let gens = pok_gens(&d, h, params);
+
+        let mut committing = ProverCommitting::new();
+        committing.commit(gens[0], Some(rand_non_zero_fr(rng)));
+        committing.commit(
+            gens[1],
+            Some(message_blinding_factor.unwrap_or_else(|| rand_non_zero_fr(rng))),
+        );
+        committing.commit(gens[2], Some(rand_non_zero_fr(rng)));
+
+        Ok(Self {
+            sigma_prime,
+            sigma_bar,
+            d,
+            e: signature.e,
+            r_inverse,
+            message: *message,
+            blinding: *blinding,
+            e_committed: e_committing.finish(),
+            committed: committing.finish(),
+        })
+    }
+
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.e_committed.challenge_bytes();
+        bytes.append(&mut self.committed.challenge_bytes());
+
+        self.sigma_prime.serialize(&mut bytes, false).unwrap();
+        self.sigma_bar.serialize(&mut bytes, false).unwrap();
+        self.d.serialize(&mut bytes, false).unwrap();
+
+        bytes
+    }
+
+    pub fn generate_proof(
+        self,
+        challenge: &Fr,
+    ) -> Result<BbPokOfBlindSignatureProof, BbSignatureError> {
+        let mut e_proof = self
+            .e_committed
+            .generate_proof(challenge, &[self.e])
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        let mut proof = self
+            .committed
+            .generate_proof(challenge, &[self.r_inverse, self.message, self.blinding])
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        Ok(BbPokOfBlindSignatureProof {
+            sigma_prime: self.sigma_prime,
+            sigma_bar: self.sigma_bar,
+            d: self.d,
+            e_randomness_commitment: e_proof.commitment,
+            randomness_commitment: proof.commitment,
+            e_response: e_proof.responses.remove(0),
+            r_inverse_response: proof.responses.remove(0),
+            message_response: proof.responses.remove(0),
+            blinding_response: proof.responses.remove(0),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbPokOfBlindSignatureProof {
+    pub sigma_prime: G1,
+
+    pub sigma_bar: G1,
+
+    pub d: G1,
+
+    pub e_randomness_commitment: G1,
+
+    pub randomness_commitment: G1,
+
+    pub e_response: Fr,
+
+    pub r_inverse_response: Fr,
+
+    pub message_response: Fr,
+
+    pub blinding_response: Fr,
+}
+
+impl BbPokOfBlindSignatureProof {
+    pub fn challenge_bytes(&self, h: &G1, params: &BbParams) -> Vec<u8> {
+        let (e_proof, proof) = self.commitment_proofs();
+
+        let mut bytes = e_proof.challenge_bytes(&e_pok_gens(&self.sigma_prime));
+        bytes.append(&mut proof.challenge_bytes(&pok_gens(&self.d, h, params)));
+
+        self.sigma_prime.serialize(&mut bytes, false).unwrap();
+        self.sigma_bar.serialize(&mut bytes, false).unwrap();
+        self.d.serialize(&mut bytes, false).unwrap();
+
+        bytes
+    }
+
+    pub fn verify(
+        &self,
+        h: &G1,
+        public_key: &BbPublicKey,
+        params: &BbParams,
+        challenge: &Fr,
+    ) -> Result<bool, BbSignatureError> {
+        // 1) signature' != 1 and d != 1
+        if self.sigma_prime.is_zero() || self.d.is_zero() {
+            return Ok(false);
+        }
+
+        let (e_proof, proof) = self.commitment_proofs();
+
+        // 2) Proof of knowledge of e in signature_bar / d = (1 / signature') ^ e
+        let mut sigma_bar_d = self.sigma_bar;
+        sigma_bar_d.sub_assign(&self.d);
+
+        if !e_proof
+            .verify(&e_pok_gens(&self.sigma_prime), &sigma_bar_d, challenge)
+            .map_err(|e| ProofError(format!("Could not verify proof of knowledge. {:?}", e)))?
+        {
+            return Ok(false);
+        }
+
+        // 3) Proof of knowledge of (1 / r, m, b) in g1 = d ^ (1 / r) * (1 / h) ^ m * (1 / g1) ^ b
+        if !proof
+            .verify(&pok_gens(&self.d, h, params), &params.g1, challenge)
+            .map_err(|e| ProofError(format!("Could not verify proof of knowledge. {:?}", e)))?
+        {
+            return Ok(false);
+        }
+
+        // 4) e(signature_bar, g2) * e(-signature', public_key) == 1
+        let mut negated_sigma_prime = self.sigma_prime;
+        negated_sigma_prime.negate();
+
+        let product =
+            Bls12::pairing_product(self.sigma_bar, params.g2, negated_sigma_prime, public_key.0);
+
+        Ok(product == <Bls12 as Engine>::Fqk::one())
+    }
+
+    fn commitment_proofs(&self) -> (CommitmentProof<G1>, CommitmentProof<G1>) {
+        (
+            CommitmentProof::new(self.e_randomness_commitment, vec![self.e_response]),
+            CommitmentProof::new(
+                self.randomness_commitment,
+                vec![
+                    self.r_inverse_response,
+                    self.message_response,
+                    self.blinding_response,
+                ],
+            ),
+        )
+    }
+}
+
+impl Serializable for BbPokOfBlindSignatureProof {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_POK_LENGTH);
+
+        for point in [
+            &self.sigma_prime,
+            &self.sigma_bar,
+            &self.d,
+            &self.e_randomness_commitment,
+            &self.randomness_commitment,
+        ] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        for response in [
+            &self.e_response,
+            &self.r_inverse_response,
+            &self.message_response,
+            &self.blinding_response,
+        ] {
+            response.serialize(&mut bytes, true).map_err(|e| {
+                SerializationError(format!("Could not serialize response. {:?}", e))
+            })?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_POK_LENGTH)?;
+
+        let mut cursor = Cursor::new(bytes);
+
+        Ok(Self {
+            sigma_prime: read_g1_point(&mut cursor)?,
+            sigma_bar: read_g1_point(&mut cursor)?,
+            d: read_g1_point(&mut cursor)?,
+            e_randomness_commitment: read_g1_point(&mut cursor)?,
+            randomness_commitment: read_g1_point(&mut cursor)?,
+            e_response: read_fr(&mut cursor)?,
+            r_inverse_response: read_fr(&mut cursor)?,
+            message_response: read_fr(&mut cursor)?,
+            blinding_response: read_fr(&mut cursor)?,
+        })
+    }
+}
+
+// [1 / signature']
+fn e_pok_gens(sigma_prime: &G1) -> [G1; 1] {
+    let mut negated_sigma_prime = *sigma_prime;
+    negated_sigma_prime.negate();
+
+    [negated_sigma_prime]
+}
+
+// [d, 1 / h, 1 / g1]
+fn pok_gens(d: &G1, h: &G1, params: &BbParams) -> [G1; 3] {
+    let mut negated_h = *h;
+    negated_h.negate();
+
+    let mut negated_g1 = params.g1;
+    negated_g1.negate();
+
+    [*d, negated_h, negated_g1]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blind_signature::{BbBlindSignature, BbBlindSignatureRequest};
+    use crate::keys::{BbParams, BbSigningKey};
+    use crate::pok_blind_sig::{BbPokOfBlindSignatureCommitted, BbPokOfBlindSignatureProof};
+    use crate::serde::Serializable;
+    use crypto_common::hash_to_fr;
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::{Fr, G1};
+    use pairing_plus::CurveProjective;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_pok_blind_sig() {
+        let mut rng = thread_rng();
+
+        // Setup
+        let params = BbParams::generate(&mut rng);
+        let h = G1::random(&mut rng);
+
+        let signing_key = BbSigningKey::generate(&mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        let message = Fr::random(&mut rng);
+        let blinding = Fr::random(&mut rng);
+
+        let request =
+            BbBlindSignatureRequest::new(&message, &blinding, &h, &params, &mut rng).unwrap();
+        let signature =
+            BbBlindSignature::sign_request(&request, &h, &signing_key, &params, &mut rng).unwrap();
+
+        // 1) Commit
+        let committed = BbPokOfBlindSignatureCommitted::new(
+            &signature, &message, &blinding, None, &h, &params, &mut rng,
+        )
+        .unwrap();
+        let challenge = hash_to_fr(committed.challenge_bytes());
+
+        // 2) Generate the proof
+        let proof = committed.generate_proof(&challenge).unwrap();
+
+        // Serialize de serialize
+        let proof_serialized = proof.serialize().unwrap();
+        let proof_deserialized =
+            BbPokOfBlindSignatureProof::deserialize(&proof_serialized).unwrap();
+
+        assert_eq!(proof, proof_deserialized);
+
+        // 3) Verify
+        let challenge = hash_to_fr(proof.challenge_bytes(&h, &params));
+
+        assert!(proof.verify(&h, &public_key, &params, &challenge).unwrap());
+
+        // Other public key
+        let other_public_key = BbSigningKey::generate(&mut rng).derive_public_key(&params);
+
+        assert!(!proof
+            .verify(&h, &other_public_key, &params, &challenge)
+            .unwrap());
+
+        // Signature on another message
+        let other_message = Fr::random(&mut rng);
+        let committed = BbPokOfBlindSignatureCommitted::new(
+            &signature,
+            &other_message,
+            &blinding,
+            None,
+            &h,
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+        let challenge = hash_to_fr(committed.challenge_bytes());
+        let proof = committed.generate_proof(&challenge).unwrap();
+
+        assert!(!proof.verify(&h, &public_key, &params, &challenge).unwrap());
+    }
+}
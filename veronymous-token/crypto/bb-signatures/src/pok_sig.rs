@@ -0,0 +1,273 @@
+/*
+* Proof of knowledge of a bb signature on a hidden message.
+* signature' = signature ^ r, signature_bar = signature' ^ (-m) * g1 ^ r (= signature' ^ s)
+* The verifier checks e(signature_bar, g2) == e(signature', public_key) and the proof of
+* knowledge of (m, r) in signature_bar = (1 / signature') ^ m * g1 ^ r.
+* The message response can be linked to other proofs by sharing its blinding factor.
+*/
+
+use crate::error::BbSignatureError;
+use crate::error::BbSignatureError::{ProofError, SerializationError};
+use crate::keys::{BbParams, BbPublicKey, SERIALIZED_FR_LENGTH, SERIALIZED_G1_LENGTH};
+use crate::serde::{check_length, read_fr, read_g1_point, Serializable};
+use crate::signature::BbSignature;
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitted, ProverCommitting};
+use crypto_common::rand_non_zero_fr;
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Bls12, Fr, G1};
+use pairing_plus::serdes::SerDes;
+use pairing_plus::{CurveProjective, Engine};
+use rand::CryptoRng;
+use std::io::Cursor;
+
+const SERIALIZED_POK_LENGTH: usize = 3 * SERIALIZED_G1_LENGTH + 2 * SERIALIZED_FR_LENGTH;
+
+pub struct BbPokOfSignatureCommitted {
+    sigma_prime: G1,
+
+    sigma_bar: G1,
+
+    message: Fr,
+
+    blinding_r: Fr,
+
+    committed: ProverCommitted<G1>,
+}
+
+impl BbPokOfSignatureCommitted {
+    pub fn new<R: CryptoRng + rand::RngCore>(
+        signature: &BbSignature,
+        message: &Fr,
+        message_blinding_factor: Option<Fr>,
+        params: &BbParams,
+        rng: &mut R,
+    ) -> Self {
+        // 1) Randomize the signature
+        let blinding_r = rand_non_zero_fr(rng);
+
+        let mut sigma_prime = signature.0;
+        sigma_prime.mul_assign(blinding_r);
+
+        // 2) signature_bar = (1 / signature') ^ m * g1 ^ r
+        let gens = pok_gens(&sigma_prime, params);
+
+        let mut sigma_bar = gens[0];
+        sigma_bar.mul_assign(*message);
+
+        let mut g1_r = params.g1;
+        g1_r.mul_assign(blinding_r);
+        sigma_bar.add_assign(&g1_r);
+
+        // 3) Commit to the randomness
+        let mut committing = ProverCommitting::new();
+        committing.commit(gens[0], message_blinding_factor);
+        committing.commit(gens[1], None);
+
+        Self {
+            sigma_prime,
+            sigma_bar,
+            message: *message,
+            blinding_r,
+            committed: committing.finish(),
+        }
+    }
+
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.committed.challenge_bytes();
+
+        self.sigma_prime.serialize(&mut bytes, false).unwrap();
+        self.sigma_bar.serialize(&mut bytes, false).unwrap();
+
+        bytes
+    }
+
+    pub fn generate_proof(self, challenge: &Fr) -> Result<BbPokOfSignatureProof, BbSignatureError> {
+        let mut proof = self
+            .committed
+            .generate_proof(challenge, &[self.message, self.blinding_r])
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        Ok(BbPokOfSignatureProof {
+            sigma_prime: self.sigma_prime,
+            sigma_bar: self.sigma_bar,
+            randomness_commitment: proof.commitment,
+            message_response: proof.responses.remove(0),
+            blinding_response: proof.responses.remove(0),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbPokOfSignatureProof {
+    pub sigma_prime: G1,
+
+    pub sigma_bar: G1,
+
+    pub randomness_commitment: G1,
+
+    pub message_response: Fr,
+
+    pub blinding_response: Fr,
+}
+
+impl BbPokOfSignatureProof {
+    pub fn challenge_bytes(&self, params: &BbParams) -> Vec<u8> {
+        let mut bytes = self
+            .commitment_proof()
+            .challenge_bytes(&pok_gens(&self.sigma_prime, params));
+
+        self.sigma_prime.serialize(&mut bytes, false).unwrap();
+        self.sigma_bar.serialize(&mut bytes, false).unwrap();
+
+        bytes
+    }
+
+    pub fn verify(
+        &self,
+        public_key: &BbPublicKey,
+        params: &BbParams,
+        challenge: &Fr,
+    ) -> Result<bool, BbSignatureError> {
+        // 1) signature' != 1
+        if self.sigma_prime.is_zero() {
+            return Ok(false);
+        }
+
+        // 2) Proof of knowledge of (m, r)
+        if !self
+            .commitment_proof()
+            .verify(
+                &pok_gens(&self.sigma_prime, params),
+                &self.sigma_bar,
+                challenge,
+            )
+            .map_err(|e| ProofError(format!("Could not verify proof of knowledge. {:?}", e)))?
+        {
+            return Ok(false);
+        }
+
+        // 3) e(signature_bar, g2) * e(-signature', public_key) == 1
+        let mut negated_sigma_prime = self.sigma_prime;
+        negated_sigma_prime.negate();
+
+        let product =
+            Bls12::pairing_product(self.sigma_bar, params.g2, negated_sigma_prime, public_key.0);
+
+        Ok(product == <Bls12 as Engine>::Fqk::one())
+    }
+
+    fn commitment_proof(&self) -> CommitmentProof<G1> {
+        CommitmentProof::new(
+            self.randomness_commitment,
+            vec![self.message_response, self.blinding_response],
+        )
+    }
+}
+
+impl Serializable for BbPokOfSignatureProof {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_POK_LENGTH);
+
+        for point in [
+            &self.sigma_prime,
+            &self.sigma_bar,
+            &self.randomness_commitment,
+        ] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        for response in [&self.message_response, &self.blinding_response] {
+            response.serialize(&mut bytes, true).map_err(|e| {
+                SerializationError(format!("Could not serialize response. {:?}", e))
+            })?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_POK_LENGTH)?;
+
+        let mut cursor = Cursor::new(bytes);
+
+        Ok(Self {
+            sigma_prime: read_g1_point(&mut cursor)?,
+            sigma_bar: read_g1_point(&mut cursor)?,
+            randomness_commitment: read_g1_point(&mut cursor)?,
+            message_response: read_fr(&mut cursor)?,
+            blinding_response: read_fr(&mut cursor)?,
+        })
+    }
+}
+
+// [1 / signature', g1]
+fn pok_gens(sigma_prime: &G1, params: &BbParams) -> [G1; 2] {
+    let mut negated_sigma_prime = *sigma_prime;
+    negated_sigma_prime.negate();
+
+    [negated_sigma_prime, params.g1]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keys::{BbParams, BbSigningKey};
+    use crate::pok_sig::{BbPokOfSignatureCommitted, BbPokOfSignatureProof};
+    use crate::serde::Serializable;
+    use crate::signature::BbSignature;
+    use crypto_common::hash_to_fr;
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::Fr;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_pok_sig() {
+        let mut rng = thread_rng();
+
+        // Setup
+        let params = BbParams::generate(&mut rng);
+
+        let signing_key = BbSigningKey::generate(&mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        let message = Fr::random(&mut rng);
+        let signature = BbSignature::new(&message, &signing_key, &params).unwrap();
+
+        // 1) Commit
+        let committed =
+            BbPokOfSignatureCommitted::new(&signature, &message, None, &params, &mut rng);
+        let challenge = hash_to_fr(committed.challenge_bytes());
+
+        // 2) Generate the proof
+        let proof = committed.generate_proof(&challenge).unwrap();
+
+        // Serialize de serialize
+        let proof_serialized = proof.serialize().unwrap();
+        let proof_deserialized = BbPokOfSignatureProof::deserialize(&proof_serialized).unwrap();
+
+        assert_eq!(proof, proof_deserialized);
+
+        // 3) Verify
+        let challenge = hash_to_fr(proof.challenge_bytes(&params));
+
+        assert!(proof.verify(&public_key, &params, &challenge).unwrap());
+
+        // Other public key
+        let other_public_key = BbSigningKey::generate(&mut rng).derive_public_key(&params);
+
+        assert!(!proof
+            .verify(&other_public_key, &params, &challenge)
+            .unwrap());
+
+        // Signature on another message
+        let other_message = Fr::random(&mut rng);
+        let committed =
+            BbPokOfSignatureCommitted::new(&signature, &other_message, None, &params, &mut rng);
+        let challenge = hash_to_fr(committed.challenge_bytes());
+        let proof = committed.generate_proof(&challenge).unwrap();
+
+        assert!(!proof.verify(&public_key, &params, &challenge).unwrap());
+    }
+}
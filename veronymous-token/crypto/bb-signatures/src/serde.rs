@@ -0,0 +1,163 @@
+use crate::error::BbSignatureError;
+use crate::error::BbSignatureError::{DeserializationError, IdentityPointError, SubgroupError};
+use pairing_plus::bls12_381::{Fr, G1, G2};
+use pairing_plus::serdes::SerDes;
+use pairing_plus::{CurveProjective, SubgroupCheck};
+use std::io::Cursor;
+
+pub trait Serializable {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError>;
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError>
+    where
+        Self: Sized;
+}
+
+// Rejects the identity and the points outside of the prime order subgroup
+pub fn read_g1_point(bytes: &mut Cursor<&[u8]>) -> Result<G1, BbSignatureError> {
+    let point = match G1::deserialize(bytes, true) {
+        Ok(point) => point,
+        Err(err) => {
+            return Err(DeserializationError(format!(
+                "Could not decode G1 point. {:?}",
+                err
+            )))
+        }
+    };
+
+    check_point(&point, "G1 point")?;
+
+    Ok(point)
+}
+
+// Rejects the identity and the points outside of the prime order subgroup
+pub fn read_g2_point(bytes: &mut Cursor<&[u8]>) -> Result<G2, BbSignatureError> {
+    let point = match G2::deserialize(bytes, true) {
+        Ok(point) => point,
+        Err(err) => {
+            return Err(DeserializationError(format!(
+                "Could not decode G2 point. {:?}",
+                err
+            )))
+        }
+    };
+
+    check_point(&point, "G2 point")?;
+
+    Ok(point)
+}
+
+pub fn read_fr(bytes: &mut Cursor<&[u8]>) -> Result<Fr, BbSignatureError> {
+    match Fr::deserialize(bytes, true) {
+        Ok(field) => Ok(field),
+        Err(err) => Err(DeserializationError(format!(
+            "Could not decode Fr. {:?}",
+            err
+        ))),
+    }
+}
+
+fn check_point<C: CurveProjective>(point: &C, name: &str) -> Result<(), BbSignatureError>
+where
+    C::Affine: SubgroupCheck,
+{
+    if point.is_zero() {
+        return Err(IdentityPointError(format!("{} is the identity.", name)));
+    }
+
+    if !point.into_affine().in_subgroup() {
+        return Err(SubgroupError(format!(
+            "{} is not in the prime order subgroup.",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+// Rejects the trailing bytes of a fixed size encoding
+pub fn check_length(bytes: &[u8], expected: usize) -> Result<(), BbSignatureError> {
+    if bytes.len() != expected {
+        return Err(DeserializationError(format!(
+            "Invalid number of bytes. Got {} but expected {}",
+            bytes.len(),
+            expected
+        )));
+    }
+
+    Ok(())
+}
+
+/*
+* serde Serialize/Deserialize as a base64 string of the Serializable encoding.
+*/
+macro_rules! impl_base64_serde {
+    ($type:ty, $visitor:ident) => {
+        impl serde::Serialize for $type {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                use serde::ser::Error;
+
+                let bytes = crate::serde::Serializable::serialize(self)
+                    .map_err(|e| S::Error::custom(format!("{:?}", e)))?;
+                let string = base64::encode(&bytes);
+
+                serializer.serialize_str(string.as_str())
+            }
+        }
+
+        struct $visitor;
+
+        impl<'de> serde::de::Visitor<'de> for $visitor {
+            type Value = $type;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("Expecting a string.")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                self.visit_borrowed_str(v)
+            }
+
+            fn visit_borrowed_str<E>(self, string: &'de str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let bytes = base64::decode(string).map_err(|e| E::custom(format!("{:?}", e)))?;
+
+                self.visit_bytes(&bytes)
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                self.visit_borrowed_str(&v)
+            }
+
+            fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                <$type as crate::serde::Serializable>::deserialize(bytes)
+                    .map_err(|e| E::custom(format!("{:?}", e)))
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                deserializer.deserialize_string($visitor)
+            }
+        }
+    };
+}
+
+pub(crate) use impl_base64_serde;
@@ -1,42 +1,130 @@
-use crate::keys::{BbParams, BbPublicKey, BbSigningKey};
+use crate::error::BbSignatureError;
+use crate::error::BbSignatureError::{SerializationError, SigningError};
+use crate::keys::{BbParams, BbPublicKey, BbSigningKey, SERIALIZED_G1_LENGTH};
+use crate::serde::{check_length, impl_base64_serde, read_g1_point, Serializable};
+use crypto_common::{multi_scalar_mul_var_time, rand_non_zero_fr};
 use ff_zeroize::Field;
 use pairing_plus::bls12_381::{Bls12, Fr, G1};
+use pairing_plus::serdes::SerDes;
 use pairing_plus::{CurveProjective, Engine};
+use rand::CryptoRng;
+use std::io::Cursor;
 
-#[derive(Clone, Debug)]
-pub struct BbSignature(G1);
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbSignature(pub G1);
 
 impl BbSignature {
-    pub fn new(message: &Fr, key: &BbSigningKey, params: &BbParams) -> Self {
+    pub fn new(
+        message: &Fr,
+        key: &BbSigningKey,
+        params: &BbParams,
+    ) -> Result<Self, BbSignatureError> {
         // 1) 1 / (m + s)
         let mut ms = *message;
         ms.add_assign(&key.0);
-        let ms = ms.inverse().unwrap();
+        let ms = ms
+            .inverse()
+            .ok_or_else(|| SigningError("Cannot sign this message.".to_string()))?;
 
-        let mut signature = params.g1.clone();
+        let mut signature = params.g1;
         signature.mul_assign(ms);
 
-        Self(signature)
+        Ok(Self(signature))
     }
 
     pub fn verify(&self, message: &Fr, public_key: &BbPublicKey, params: &BbParams) -> bool {
-        // Pairing 1 = e(signature, g2 ^ m * public_key) = e(g1, g2)
-        let mut g_m_p = params.g2.clone();
+        if self.0.is_zero() {
+            return false;
+        }
+
+        // e(signature, g2 ^ m * public_key) * e(-g1, g2) == 1
+        let mut g_m_p = params.g2;
         g_m_p.mul_assign(*message);
         g_m_p.add_assign(&public_key.0);
 
-        let pairing_1 = Bls12::pairing(self.0, g_m_p);
+        let mut negated_g1 = params.g1;
+        negated_g1.negate();
+
+        let product = Bls12::pairing_product(self.0, g_m_p, negated_g1, params.g2);
+
+        product == <Bls12 as Engine>::Fqk::one()
+    }
+
+    /*
+     * Verify the signatures of the same key with a single multi pairing.
+     * Each signature is weighted by a random d_i:
+     * e(sum(d_i * m_i * signature_i) - sum(d_i) * g1, g2) * e(sum(d_i * signature_i), public_key) == 1
+     * An empty batch does not verify.
+     */
+    pub fn batch_verify<R: CryptoRng + rand::RngCore>(
+        signatures: &[(BbSignature, Fr)],
+        public_key: &BbPublicKey,
+        params: &BbParams,
+        rng: &mut R,
+    ) -> bool {
+        if signatures.is_empty()
+            || signatures
+                .iter()
+                .any(|(signature, _)| signature.0.is_zero())
+        {
+            return false;
+        }
+
+        let mut points = Vec::with_capacity(signatures.len() + 1);
+        let mut weighted_messages = Vec::with_capacity(signatures.len() + 1);
+        let mut weights = Vec::with_capacity(signatures.len());
+        let mut weight_sum = Fr::zero();
+
+        for (signature, message) in signatures {
+            let weight = rand_non_zero_fr(rng);
+
+            let mut weighted_message = weight;
+            weighted_message.mul_assign(message);
+
+            points.push(signature.0);
+            weighted_messages.push(weighted_message);
+            weights.push(weight);
+            weight_sum.add_assign(&weight);
+        }
+
+        let signature_sum = multi_scalar_mul_var_time(&points, &weights);
+
+        weight_sum.negate();
+        points.push(params.g1);
+        weighted_messages.push(weight_sum);
+
+        let message_sum = multi_scalar_mul_var_time(&points, &weighted_messages);
+
+        let product = Bls12::pairing_product(message_sum, params.g2, signature_sum, public_key.0);
+
+        product == <Bls12 as Engine>::Fqk::one()
+    }
+}
+
+impl Serializable for BbSignature {
+    fn serialize(&self) -> Result<Vec<u8>, BbSignatureError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_G1_LENGTH);
+
+        self.0
+            .serialize(&mut bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize signature. {:?}", e)))?;
 
-        // pairing_2 = e(g1, g2)
-        let pairing_2 = Bls12::pairing(params.g1, params.g2);
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, BbSignatureError> {
+        check_length(bytes, SERIALIZED_G1_LENGTH)?;
 
-        pairing_1.eq(&pairing_2)
+        Ok(Self(read_g1_point(&mut Cursor::new(bytes))?))
     }
 }
 
+impl_base64_serde!(BbSignature, BbSignatureVisitor);
+
 #[cfg(test)]
 mod tests {
     use crate::keys::{BbParams, BbSigningKey};
+    use crate::serde::Serializable;
     use crate::signature::BbSignature;
     use ff_zeroize::Field;
     use pairing_plus::bls12_381::Fr;
@@ -54,7 +142,7 @@ mod tests {
 
         // Sign a message
         let message = Fr::random(&mut rng);
-        let signature = BbSignature::new(&message, &signing_key, &params);
+        let signature = BbSignature::new(&message, &signing_key, &params).unwrap();
 
         // Verify the signature
         let verification_result = signature.verify(&message, &public_key, &params);
@@ -66,5 +154,57 @@ mod tests {
         let verification_result = signature.verify(&bad_message, &public_key, &params);
 
         assert_eq!(false, verification_result);
+
+        // Serialize de serialize
+        let signature_serialized = signature.serialize().unwrap();
+        let signature_deserialized = BbSignature::deserialize(&signature_serialized).unwrap();
+
+        assert_eq!(signature, signature_deserialized);
+    }
+
+    #[test]
+    fn test_bb_signature_batch_verify() {
+        let mut rng = thread_rng();
+
+        let params = BbParams::generate(&mut rng);
+
+        let signing_key = BbSigningKey::generate(&mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        let mut signatures: Vec<(BbSignature, Fr)> = (0..5)
+            .map(|_| {
+                let message = Fr::random(&mut rng);
+
+                (
+                    BbSignature::new(&message, &signing_key, &params).unwrap(),
+                    message,
+                )
+            })
+            .collect();
+
+        assert!(BbSignature::batch_verify(
+            &signatures,
+            &public_key,
+            &params,
+            &mut rng
+        ));
+
+        // One bad signature fails the batch
+        signatures[3].1 = Fr::random(&mut rng);
+
+        assert!(!BbSignature::batch_verify(
+            &signatures,
+            &public_key,
+            &params,
+            &mut rng
+        ));
+
+        // Empty batch
+        assert!(!BbSignature::batch_verify(
+            &[],
+            &public_key,
+            &params,
+            &mut rng
+        ));
     }
 }
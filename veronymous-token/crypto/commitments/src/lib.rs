@@ -1,3 +1,5 @@
 pub mod error;
 pub mod pedersen_commitment;
 pub mod pok_pedersen_commitment;
+pub mod pok_range;
+pub mod pok_set_membership;
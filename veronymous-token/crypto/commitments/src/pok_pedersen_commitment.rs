@@ -4,7 +4,7 @@
 */
 
 use crate::error::CommitmentError;
-use crypto_common::multi_scalar_mul_const_time;
+use crypto_common::{multi_scalar_mul_const_time, multi_scalar_mul_var_time};
 use ff_zeroize::{Field, PrimeField};
 use pairing_plus::bls12_381::Fr;
 use pairing_plus::serdes::SerDes;
@@ -146,7 +146,7 @@ impl<C: CurveProjective + SerDes> CommitmentProof<C> {
         points.push(*commitment);
         scalars.push(*challenge);
 
-        let mut calculated_commitment = multi_scalar_mul_const_time(points, scalars);
+        let mut calculated_commitment = multi_scalar_mul_var_time(points, scalars);
         calculated_commitment.sub_assign(&self.commitment);
 
         Ok(calculated_commitment.is_zero())
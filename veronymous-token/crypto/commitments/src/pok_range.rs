@@ -0,0 +1,303 @@
+/*
+* Range proof for a pedersen commitment C = g ^ v * h ^ r with v in [0, 2 ^ n).
+* v is decomposed in bits; each bit commitment C_i = g ^ b_i * h ^ r_i opens to 0 or 1
+* and the product of C_i ^ (2 ^ i) is C.
+*/
+
+use crate::error::CommitmentError;
+use crate::pok_set_membership::{SetMembershipCommitted, SetMembershipProof};
+use crypto_common::{fr_from_u64, multi_scalar_mul_const_time, multi_scalar_mul_var_time};
+use ff_zeroize::{Field, PrimeField};
+use pairing_plus::bls12_381::Fr;
+use pairing_plus::serdes::SerDes;
+use pairing_plus::CurveProjective;
+use rand::{CryptoRng, RngCore};
+
+pub const MAX_RANGE_BITS: usize = 64;
+
+// Number of bits needed to prove that a value is lower than the bound
+pub fn bits_for_bound(bound: u64) -> usize {
+    let max_value = bound.saturating_sub(1);
+
+    ((u64::BITS - max_value.leading_zeros()) as usize).max(1)
+}
+
+#[derive(Clone, Debug)]
+pub struct RangeProofCommitted<C: CurveProjective + SerDes> {
+    bit_commitments: Vec<C>,
+    bit_blindings: Vec<Fr>,
+    bit_proofs: Vec<SetMembershipCommitted<C>>,
+}
+
+impl<C: CurveProjective + SerDes> RangeProofCommitted<C> {
+    pub fn new<R: CryptoRng + RngCore>(
+        value_gen: &C,
+        blinding_gen: &C,
+        value: u64,
+        blinding: &Fr,
+        bits: usize,
+        rng: &mut R,
+    ) -> Result<Self, CommitmentError>
+    where
+        <<C as CurveProjective>::Scalar as PrimeField>::Repr: From<Fr>,
+    {
+        if bits == 0 || bits > MAX_RANGE_BITS {
+            return Err(CommitmentError::InvalidArgumentError(format!(
+                "Number of bits({}) must be between 1 and {}.",
+                bits, MAX_RANGE_BITS
+            )));
+        }
+
+        if bits < MAX_RANGE_BITS && value >> bits != 0 {
+            return Err(CommitmentError::InvalidArgumentError(format!(
+                "Value does not fit in {} bits.",
+                bits
+            )));
+        }
+
+        let bit_set = [Fr::zero(), Fr::one()];
+
+        // Bit blindings: r = sum(r_i * 2 ^ i). The last one is fixed by the others.
+        let mut bit_blindings = Vec::with_capacity(bits);
+        let mut remaining_blinding = *blinding;
+        let mut power_of_two = Fr::one();
+
+        for _ in 0..bits - 1 {
+            let bit_blinding = Fr::random(&mut *rng);
+
+            let mut weighted = bit_blinding;
+            weighted.mul_assign(&power_of_two);
+            remaining_blinding.sub_assign(&weighted);
+
+            bit_blindings.push(bit_blinding);
+            power_of_two.double();
+        }
+
+        let mut last_blinding = remaining_blinding;
+        last_blinding.mul_assign(&power_of_two.inverse().unwrap());
+        bit_blindings.push(last_blinding);
+
+        // Commit to every bit and prove it is 0 or 1
+        let mut bit_commitments = Vec::with_capacity(bits);
+        let mut bit_proofs = Vec::with_capacity(bits);
+
+        for (i, bit_blinding) in bit_blindings.iter().enumerate() {
+            let bit = fr_from_u64((value >> i) & 1);
+
+            let bit_commitment =
+                multi_scalar_mul_const_time([*value_gen, *blinding_gen], [bit, *bit_blinding]);
+
+            bit_proofs.push(SetMembershipCommitted::new(
+                value_gen,
+                blinding_gen,
+                &bit_commitment,
+                &bit_set,
+                &bit,
+                rng,
+            )?);
+            bit_commitments.push(bit_commitment);
+        }
+
+        Ok(Self {
+            bit_commitments,
+            bit_blindings,
+            bit_proofs,
+        })
+    }
+
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for bit_commitment in &self.bit_commitments {
+            bit_commitment.serialize(&mut bytes, false).unwrap();
+        }
+
+        for bit_proof in &self.bit_proofs {
+            bytes.append(&mut bit_proof.challenge_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn generate_proof(self, challenge: &Fr) -> RangeProof<C> {
+        let bit_proofs = self
+            .bit_proofs
+            .into_iter()
+            .zip(self.bit_blindings.iter())
+            .map(|(bit_proof, bit_blinding)| bit_proof.generate_proof(challenge, bit_blinding))
+            .collect();
+
+        RangeProof {
+            bit_commitments: self.bit_commitments,
+            bit_proofs,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RangeProof<C: CurveProjective + SerDes> {
+    pub bit_commitments: Vec<C>,
+
+    pub bit_proofs: Vec<SetMembershipProof<C>>,
+}
+
+impl<C: CurveProjective + SerDes> RangeProof<C> {
+    pub fn bits(&self) -> usize {
+        self.bit_commitments.len()
+    }
+
+    pub fn challenge_bytes(&self, value_gen: &C, blinding_gen: &C) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for bit_commitment in &self.bit_commitments {
+            bit_commitment.serialize(&mut bytes, false).unwrap();
+        }
+
+        for bit_proof in &self.bit_proofs {
+            bytes.append(&mut bit_proof.challenge_bytes(value_gen, blinding_gen));
+        }
+
+        bytes
+    }
+
+    pub fn verify(
+        &self,
+        value_gen: &C,
+        blinding_gen: &C,
+        commitment: &C,
+        bits: usize,
+        challenge: &Fr,
+    ) -> Result<bool, CommitmentError>
+    where
+        <<C as CurveProjective>::Scalar as PrimeField>::Repr: From<Fr>,
+    {
+        if bits == 0 || bits > MAX_RANGE_BITS {
+            return Err(CommitmentError::InvalidArgumentError(format!(
+                "Number of bits({}) must be between 1 and {}.",
+                bits, MAX_RANGE_BITS
+            )));
+        }
+
+        if self.bit_commitments.len() != bits || self.bit_proofs.len() != bits {
+            return Ok(false);
+        }
+
+        // C == prod(C_i ^ (2 ^ i))
+        let mut powers_of_two = Vec::with_capacity(bits);
+        let mut power_of_two = Fr::one();
+        for _ in 0..bits {
+            powers_of_two.push(power_of_two);
+            power_of_two.double();
+        }
+
+        let mut calculated_commitment =
+            multi_scalar_mul_var_time(&self.bit_commitments, &powers_of_two);
+        calculated_commitment.sub_assign(commitment);
+
+        if !calculated_commitment.is_zero() {
+            return Ok(false);
+        }
+
+        // Every bit is 0 or 1
+        let bit_set = [Fr::zero(), Fr::one()];
+
+        for (bit_commitment, bit_proof) in self.bit_commitments.iter().zip(&self.bit_proofs) {
+            if !bit_proof.verify(value_gen, blinding_gen, bit_commitment, &bit_set, challenge)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pedersen_commitment::PedersenCommitmentCommitting;
+    use crate::pok_range::{bits_for_bound, RangeProofCommitted};
+    use crypto_common::{fr_from_u64, hash_to_fr};
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::{Fr, G1};
+    use pairing_plus::serdes::SerDes;
+    use pairing_plus::CurveProjective;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_bits_for_bound() {
+        assert_eq!(1, bits_for_bound(1));
+        assert_eq!(1, bits_for_bound(2));
+        assert_eq!(2, bits_for_bound(3));
+        assert_eq!(2, bits_for_bound(4));
+        assert_eq!(3, bits_for_bound(5));
+        assert_eq!(64, bits_for_bound(u64::MAX));
+    }
+
+    #[test]
+    fn test_range_proof() {
+        let mut rng = thread_rng();
+
+        let g = G1::random(&mut rng);
+        let h = G1::random(&mut rng);
+
+        let value = 45u64;
+        let blinding = Fr::random(&mut rng);
+        let bits = 8;
+
+        let commitment = PedersenCommitmentCommitting::new(
+            Some(vec![g, h]),
+            Some(vec![fr_from_u64(value), blinding]),
+        )
+        .unwrap()
+        .finish();
+
+        // 1) Commit
+        let committed = RangeProofCommitted::new(&g, &h, value, &blinding, bits, &mut rng).unwrap();
+
+        let mut challenge_bytes = committed.challenge_bytes();
+        commitment.0.serialize(&mut challenge_bytes, false).unwrap();
+        let challenge = hash_to_fr(challenge_bytes);
+
+        // 2) Generate the proof
+        let proof = committed.generate_proof(&challenge);
+
+        // 3) Verify
+        let mut challenge_bytes = proof.challenge_bytes(&g, &h);
+        commitment.0.serialize(&mut challenge_bytes, false).unwrap();
+        let challenge = hash_to_fr(challenge_bytes);
+
+        assert!(proof
+            .verify(&g, &h, &commitment.0, bits, &challenge)
+            .unwrap());
+
+        // Different number of bits
+        assert!(!proof
+            .verify(&g, &h, &commitment.0, bits - 1, &challenge)
+            .unwrap());
+
+        // Different commitment
+        let other_commitment = PedersenCommitmentCommitting::new(
+            Some(vec![g, h]),
+            Some(vec![fr_from_u64(value + 1), blinding]),
+        )
+        .unwrap()
+        .finish();
+
+        assert!(!proof
+            .verify(&g, &h, &other_commitment.0, bits, &challenge)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_value_out_of_range() {
+        let mut rng = thread_rng();
+
+        let g = G1::random(&mut rng);
+        let h = G1::random(&mut rng);
+
+        let blinding = Fr::random(&mut rng);
+
+        assert!(RangeProofCommitted::new(&g, &h, 256, &blinding, 8, &mut rng).is_err());
+        assert!(RangeProofCommitted::new(&g, &h, 255, &blinding, 8, &mut rng).is_ok());
+    }
+}
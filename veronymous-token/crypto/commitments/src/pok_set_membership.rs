@@ -0,0 +1,346 @@
+/*
+* Proof that a pedersen commitment C = g ^ v * h ^ r opens to a value v of a public set.
+* Disjunctive (OR) composition of schnorr proofs, one branch per set element.
+* https://www.win.tue.nl/~berry/papers/crypto94.pdf
+*/
+
+use crate::error::CommitmentError;
+use crypto_common::{multi_scalar_mul_const_time, multi_scalar_mul_var_time};
+use ff_zeroize::{Field, PrimeField};
+use pairing_plus::bls12_381::Fr;
+use pairing_plus::serdes::SerDes;
+use pairing_plus::CurveProjective;
+use rand::{CryptoRng, RngCore};
+
+#[derive(Clone, Debug)]
+pub struct SetMembershipCommitted<C: CurveProjective + SerDes> {
+    value_gen: C,
+    blinding_gen: C,
+
+    // Position of the committed value in the set
+    position: usize,
+
+    // Randomness of the real branch
+    blinding_factor: Fr,
+
+    // Simulated challenges and responses (the real branch is set on proof generation)
+    challenges: Vec<Fr>,
+    responses: Vec<Fr>,
+
+    // Randomness commitment for each branch
+    commitments: Vec<C>,
+}
+
+impl<C: CurveProjective + SerDes> SetMembershipCommitted<C> {
+    pub fn new<R: CryptoRng + RngCore>(
+        value_gen: &C,
+        blinding_gen: &C,
+        commitment: &C,
+        set: &[Fr],
+        value: &Fr,
+        rng: &mut R,
+    ) -> Result<Self, CommitmentError>
+    where
+        <<C as CurveProjective>::Scalar as PrimeField>::Repr: From<Fr>,
+    {
+        let position = match set.iter().position(|element| element == value) {
+            Some(position) => position,
+            None => {
+                return Err(CommitmentError::InvalidArgumentError(
+                    "Committed value is not a member of the set.".to_string(),
+                ))
+            }
+        };
+
+        let mut challenges = Vec::with_capacity(set.len());
+        let mut responses = Vec::with_capacity(set.len());
+        let mut commitments = Vec::with_capacity(set.len());
+
+        let blinding_factor = Fr::random(&mut *rng);
+
+        for (i, element) in set.iter().enumerate() {
+            if i == position {
+                // Real branch: A = h ^ blinding_factor
+                let mut randomness_commitment = *blinding_gen;
+                randomness_commitment.mul_assign(blinding_factor);
+
+                challenges.push(Fr::zero());
+                responses.push(Fr::zero());
+                commitments.push(randomness_commitment);
+            } else {
+                // Simulated branch: A = h ^ z * (C / g ^ s) ^ c
+                let challenge = Fr::random(&mut *rng);
+                let response = Fr::random(&mut *rng);
+
+                let statement = branch_statement(value_gen, commitment, element);
+                let randomness_commitment =
+                    multi_scalar_mul_const_time([*blinding_gen, statement], [response, challenge]);
+
+                challenges.push(challenge);
+                responses.push(response);
+                commitments.push(randomness_commitment);
+            }
+        }
+
+        Ok(Self {
+            value_gen: *value_gen,
+            blinding_gen: *blinding_gen,
+            position,
+            blinding_factor,
+            challenges,
+            responses,
+            commitments,
+        })
+    }
+
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Vec<u8> {
+        challenge_bytes(&self.value_gen, &self.blinding_gen, &self.commitments)
+    }
+
+    /// The real branch challenge is the challenge minus the simulated ones.
+    /// Its response is blinding_factor - challenge * blinding.
+    pub fn generate_proof(self, challenge: &Fr, blinding: &Fr) -> SetMembershipProof<C> {
+        let mut challenges = self.challenges;
+        let mut responses = self.responses;
+
+        let mut real_challenge = *challenge;
+        for (i, simulated_challenge) in challenges.iter().enumerate() {
+            if i != self.position {
+                real_challenge.sub_assign(simulated_challenge);
+            }
+        }
+
+        let mut c = real_challenge;
+        c.mul_assign(blinding);
+        let mut real_response = self.blinding_factor;
+        real_response.sub_assign(&c);
+
+        challenges[self.position] = real_challenge;
+        responses[self.position] = real_response;
+
+        SetMembershipProof {
+            commitments: self.commitments,
+            challenges,
+            responses,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetMembershipProof<C: CurveProjective + SerDes> {
+    // Randomness commitment for each branch
+    pub commitments: Vec<C>,
+
+    pub challenges: Vec<Fr>,
+
+    pub responses: Vec<Fr>,
+}
+
+impl<C: CurveProjective + SerDes> SetMembershipProof<C> {
+    pub fn challenge_bytes(&self, value_gen: &C, blinding_gen: &C) -> Vec<u8> {
+        challenge_bytes(value_gen, blinding_gen, &self.commitments)
+    }
+
+    pub fn verify(
+        &self,
+        value_gen: &C,
+        blinding_gen: &C,
+        commitment: &C,
+        set: &[Fr],
+        challenge: &Fr,
+    ) -> Result<bool, CommitmentError>
+    where
+        <<C as CurveProjective>::Scalar as PrimeField>::Repr: From<Fr>,
+    {
+        if self.commitments.len() != set.len()
+            || self.challenges.len() != set.len()
+            || self.responses.len() != set.len()
+        {
+            return Ok(false);
+        }
+
+        // The branch challenges must add up to the challenge
+        let mut challenge_sum = Fr::zero();
+        for branch_challenge in &self.challenges {
+            challenge_sum.add_assign(branch_challenge);
+        }
+
+        if challenge_sum != *challenge {
+            return Ok(false);
+        }
+
+        // h ^ z * (C / g ^ s) ^ c == A for every branch
+        for (i, element) in set.iter().enumerate() {
+            let statement = branch_statement(value_gen, commitment, element);
+
+            let mut calculated_commitment = multi_scalar_mul_var_time(
+                [*blinding_gen, statement],
+                [self.responses[i], self.challenges[i]],
+            );
+            calculated_commitment.sub_assign(&self.commitments[i]);
+
+            if !calculated_commitment.is_zero() {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+// C / g ^ s
+fn branch_statement<C: CurveProjective>(value_gen: &C, commitment: &C, element: &Fr) -> C
+where
+    <<C as CurveProjective>::Scalar as PrimeField>::Repr: From<Fr>,
+{
+    let mut g_s = *value_gen;
+    g_s.mul_assign(*element);
+
+    let mut statement = *commitment;
+    statement.sub_assign(&g_s);
+
+    statement
+}
+
+fn challenge_bytes<C: CurveProjective + SerDes>(
+    value_gen: &C,
+    blinding_gen: &C,
+    commitments: &[C],
+) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    value_gen.serialize(&mut bytes, false).unwrap();
+    blinding_gen.serialize(&mut bytes, false).unwrap();
+
+    for commitment in commitments {
+        commitment.serialize(&mut bytes, false).unwrap();
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pedersen_commitment::PedersenCommitmentCommitting;
+    use crate::pok_set_membership::SetMembershipCommitted;
+    use crypto_common::hash_to_fr;
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::{Fr, G1};
+    use pairing_plus::serdes::SerDes;
+    use pairing_plus::CurveProjective;
+    use rand::rngs::StdRng;
+    use rand::{thread_rng, SeedableRng};
+
+    #[test]
+    fn test_set_membership_proof() {
+        let mut rng = thread_rng();
+
+        let g = G1::random(&mut rng);
+        let h = G1::random(&mut rng);
+
+        let set = vec![
+            Fr::random(&mut rng),
+            Fr::random(&mut rng),
+            Fr::random(&mut rng),
+        ];
+        let value = set[1];
+        let blinding = Fr::random(&mut rng);
+
+        // 1) Commit to the value
+        let commitment =
+            PedersenCommitmentCommitting::new(Some(vec![g, h]), Some(vec![value, blinding]))
+                .unwrap()
+                .finish();
+
+        // 2) Prover commits to the branches
+        let committed =
+            SetMembershipCommitted::new(&g, &h, &commitment.0, &set, &value, &mut rng).unwrap();
+
+        let mut challenge_bytes = committed.challenge_bytes();
+        commitment.0.serialize(&mut challenge_bytes, false).unwrap();
+        let challenge = hash_to_fr(challenge_bytes);
+
+        // 3) Generate the proof
+        let proof = committed.generate_proof(&challenge, &blinding);
+
+        // 4) Verify the proof
+        let mut challenge_bytes = proof.challenge_bytes(&g, &h);
+        commitment.0.serialize(&mut challenge_bytes, false).unwrap();
+        let challenge = hash_to_fr(challenge_bytes);
+
+        assert!(proof
+            .verify(&g, &h, &commitment.0, &set, &challenge)
+            .unwrap());
+
+        // Value not in the verifier's set
+        let mut bad_set = set.clone();
+        bad_set[1] = Fr::random(&mut rng);
+
+        assert!(!proof
+            .verify(&g, &h, &commitment.0, &bad_set, &challenge)
+            .unwrap());
+
+        // Wrong challenge
+        assert!(!proof
+            .verify(&g, &h, &commitment.0, &set, &Fr::random(&mut rng))
+            .unwrap());
+
+        // Missing branch
+        assert!(!proof
+            .verify(&g, &h, &commitment.0, &set[..2], &challenge)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_seeded_set_membership_proof() {
+        let mut rng = thread_rng();
+
+        let g = G1::random(&mut rng);
+        let h = G1::random(&mut rng);
+
+        let set = vec![Fr::zero(), Fr::one()];
+        let value = Fr::one();
+        let blinding = Fr::random(&mut rng);
+
+        let commitment =
+            PedersenCommitmentCommitting::new(Some(vec![g, h]), Some(vec![value, blinding]))
+                .unwrap()
+                .finish();
+        let challenge = Fr::random(&mut rng);
+
+        // The same rng seed gives the same proof
+        let proofs: Vec<_> = (0..2)
+            .map(|_| {
+                let mut seeded_rng = StdRng::seed_from_u64(42);
+
+                SetMembershipCommitted::new(&g, &h, &commitment.0, &set, &value, &mut seeded_rng)
+                    .unwrap()
+                    .generate_proof(&challenge, &blinding)
+            })
+            .collect();
+
+        assert_eq!(proofs[0], proofs[1]);
+    }
+
+    #[test]
+    fn test_value_not_in_set() {
+        let mut rng = thread_rng();
+
+        let g = G1::random(&mut rng);
+        let h = G1::random(&mut rng);
+
+        let set = vec![Fr::zero(), Fr::one()];
+        let value = Fr::random(&mut rng);
+        let blinding = Fr::random(&mut rng);
+
+        let commitment =
+            PedersenCommitmentCommitting::new(Some(vec![g, h]), Some(vec![value, blinding]))
+                .unwrap()
+                .finish();
+
+        assert!(
+            SetMembershipCommitted::new(&g, &h, &commitment.0, &set, &value, &mut rng).is_err()
+        );
+    }
+}
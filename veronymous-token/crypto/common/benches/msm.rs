@@ -0,0 +1,44 @@
+/*
+* Constant time vs variable time multi scalar multiplication
+* for the number of bases of the token proofs.
+*/
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use crypto_common::{multi_scalar_mul_const_time, multi_scalar_mul_var_time};
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Fr, G1, G2};
+use pairing_plus::CurveProjective;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+const BASE_COUNTS: [usize; 4] = [1, 2, 4, 32];
+
+fn bench_msm<C: CurveProjective>(c: &mut Criterion, group_name: &str) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut group = c.benchmark_group(group_name);
+
+    for count in BASE_COUNTS {
+        let bases: Vec<C> = (0..count).map(|_| C::random(&mut rng)).collect();
+        let scalars: Vec<Fr> = (0..count).map(|_| Fr::random(&mut rng)).collect();
+
+        group.bench_with_input(BenchmarkId::new("const time", count), &count, |b, _| {
+            b.iter(|| multi_scalar_mul_const_time(&bases, &scalars))
+        });
+        group.bench_with_input(BenchmarkId::new("var time", count), &count, |b, _| {
+            b.iter(|| multi_scalar_mul_var_time(&bases, &scalars))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_g1(c: &mut Criterion) {
+    bench_msm::<G1>(c, "msm g1");
+}
+
+fn bench_g2(c: &mut Criterion) {
+    bench_msm::<G2>(c, "msm g2");
+}
+
+criterion_group!(benches, bench_g1, bench_g2);
+criterion_main!(benches);
@@ -5,13 +5,22 @@
 use blake2::digest::generic_array::GenericArray;
 use blake2::digest::{Input, VariableOutput};
 use ff_zeroize::{Field, PrimeField};
-use pairing_plus::bls12_381::Fr;
+use pairing_plus::bls12_381::{Fr, FrRepr};
 use pairing_plus::hash_to_field::BaseFromRO;
-use pairing_plus::{CurveAffine, CurveProjective};
+use pairing_plus::CurveProjective;
 use rand::CryptoRng;
 
+pub mod msm;
+pub mod transcript;
+
+pub use msm::{multi_scalar_mul_const_time, multi_scalar_mul_var_time};
+
 pub const FR_UNCOMPRESSED_SIZE: usize = 48;
 
+// Bits of the scalar per fixed base table window
+const FIXED_BASE_WINDOW_BITS: usize = 4;
+const FIXED_BASE_WINDOWS: usize = 256 / FIXED_BASE_WINDOW_BITS;
+
 pub fn rand_non_zero_fr<R: CryptoRng + rand::RngCore>(rng: &mut R) -> Fr {
     let mut r = Fr::random(rng);
     loop {
@@ -22,23 +31,9 @@ pub fn rand_non_zero_fr<R: CryptoRng + rand::RngCore>(rng: &mut R) -> Fr {
     }
 }
 
-pub fn multi_scalar_mul_const_time<G: AsRef<[C]>, S: AsRef<[Fr]>, C: CurveProjective>(
-    bases: G,
-    scalars: S,
-) -> C {
-    let bases: Vec<_> = bases.as_ref().iter().map(|b| b.into_affine()).collect();
-    let scalars: Vec<[u64; 4]> = scalars
-        .as_ref()
-        .iter()
-        .map(|s| {
-            let mut t = [0u64; 4];
-            t.clone_from_slice(s.into_repr().as_ref());
-            t
-        })
-        .collect();
-    // Annoying step to keep the borrow checker happy
-    let s: Vec<&[u64; 4]> = scalars.iter().map(|u| u).collect();
-    C::Affine::sum_of_products(bases.as_slice(), s.as_slice())
+pub fn fr_from_u64(value: u64) -> Fr {
+    // Any u64 is lower than the field modulus
+    Fr::from_repr(FrRepr::from(value)).unwrap()
 }
 
 pub fn hash_to_fr<I: AsRef<[u8]>>(data: I) -> Fr {
@@ -50,3 +45,59 @@ pub fn hash_to_fr<I: AsRef<[u8]>>(data: I) -> Fr {
     });
     Fr::from_okm(&res)
 }
+
+/*
+* Precomputed multiples of a fixed base: windows[i][j - 1] = base ^ (j * 16 ^ i).
+* A multiplication is one mixed addition per window and no doubling.
+* Note: Variable time, only for public scalars (verification).
+*/
+#[derive(Clone, Debug)]
+pub struct FixedBaseTable<C: CurveProjective> {
+    windows: Vec<Vec<C::Affine>>,
+}
+
+impl<C: CurveProjective> FixedBaseTable<C> {
+    pub fn new(base: &C) -> Self {
+        let window_size = (1 << FIXED_BASE_WINDOW_BITS) - 1;
+
+        let mut points = Vec::with_capacity(FIXED_BASE_WINDOWS * window_size);
+        let mut window_base = *base;
+        for _ in 0..FIXED_BASE_WINDOWS {
+            let mut point = window_base;
+            for _ in 0..window_size {
+                points.push(point);
+                point.add_assign(&window_base);
+            }
+
+            // Next window base = window base ^ 16
+            window_base = point;
+        }
+
+        C::batch_normalization(&mut points);
+
+        let windows = points
+            .chunks(window_size)
+            .map(|window| window.iter().map(|point| point.into_affine()).collect())
+            .collect();
+
+        Self { windows }
+    }
+
+    pub fn mul(&self, scalar: &Fr) -> C {
+        let mask = (1 << FIXED_BASE_WINDOW_BITS) - 1;
+
+        let mut result = C::zero();
+        for (limb_index, limb) in scalar.into_repr().as_ref().iter().enumerate() {
+            for nibble_index in 0..(64 / FIXED_BASE_WINDOW_BITS) {
+                let digit = ((limb >> (nibble_index * FIXED_BASE_WINDOW_BITS)) & mask) as usize;
+
+                if digit != 0 {
+                    let window = limb_index * (64 / FIXED_BASE_WINDOW_BITS) + nibble_index;
+                    result.add_assign_mixed(&self.windows[window][digit - 1]);
+                }
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,547 @@
+/*
+* Multi scalar multiplication: prod(bases_i ^ scalars_i).
+* Secret scalars (prover side) must go through multi_scalar_mul_const_time.
+* Public scalars (verifier side) can use the faster multi_scalar_mul_var_time.
+*/
+
+use ff_zeroize::{Field, PrimeField};
+use pairing_plus::bls12_381::Fr;
+use pairing_plus::{CurveAffine, CurveProjective};
+use std::hint::black_box;
+
+// Bits of the scalar per signed digit
+const WINDOW_BITS: usize = 4;
+
+// Odd digits of a scalar below 2 ^ 256
+const DIGITS: usize = 64;
+
+// Table of the odd multiples -15 * base..15 * base
+const TABLE_SIZE: usize = 16;
+
+// Number of bases from which Pippenger is faster than interleaved wNAF
+const PIPPENGER_THRESHOLD: usize = 64;
+
+/*
+* Same sequence of doublings, table reads and additions for every scalar, the bases are public.
+* The scalar is made odd (k + 1 or k + 2) and recoded into signed odd digits in [-15, 15],
+* so every window adds a table point and no digit is zero. The base is subtracted once or
+* twice at the end to undo the first step.
+* Every table entry is read and the digit's entry is kept with a mask. The points are added
+* with the complete formulas of Renes, Costello and Batina, without the exceptional cases
+* of the identity and doubling. The field arithmetic is the one of pairing_plus.
+*/
+pub fn multi_scalar_mul_const_time<G: AsRef<[C]>, S: AsRef<[Fr]>, C: CurveProjective>(
+    bases: G,
+    scalars: S,
+) -> C {
+    let b3 = curve_coeff_b3::<C>();
+
+    let (tables, corrections): (Vec<_>, Vec<_>) = bases
+        .as_ref()
+        .iter()
+        .map(|base| {
+            let (table, corrections) = odd_multiples(base);
+            (
+                table.iter().map(Point::from_normalized).collect::<Vec<_>>(),
+                corrections.map(|correction| Point::from_normalized(&correction)),
+            )
+        })
+        .unzip();
+    let recoded: Vec<_> = scalars.as_ref().iter().map(recode_scalar).collect();
+
+    let mut result = Point::identity();
+    for digit in (0..DIGITS).rev() {
+        for _ in 0..WINDOW_BITS {
+            result = result.double(&b3);
+        }
+
+        for (table, (indices, _)) in tables.iter().zip(&recoded) {
+            result = result.add(&Point::select(table, indices[digit] as usize), &b3);
+        }
+    }
+
+    for (correction, (_, parity)) in corrections.iter().zip(&recoded) {
+        result = result.add(&Point::select(correction, *parity), &b3);
+    }
+
+    result.into_projective()
+}
+
+/*
+* Interleaved wNAF with shared doublings, Pippenger's bucket method for many bases.
+* The running time depends on the scalars.
+*/
+pub fn multi_scalar_mul_var_time<G: AsRef<[C]>, S: AsRef<[Fr]>, C: CurveProjective>(
+    bases: G,
+    scalars: S,
+) -> C {
+    let bases = bases.as_ref();
+    let scalars = scalars.as_ref();
+
+    if bases.len().min(scalars.len()) >= PIPPENGER_THRESHOLD {
+        return pippenger(bases, scalars);
+    }
+
+    let tables: Vec<_> = bases
+        .iter()
+        .map(|base| {
+            odd_multiples(base)
+                .0
+                .iter()
+                .map(|point| point.into_affine())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let digits: Vec<_> = scalars.iter().map(wnaf_digits).collect();
+
+    let length = digits.iter().map(|digits| digits.len()).max().unwrap_or(0);
+
+    let mut result = C::zero();
+    for i in (0..length).rev() {
+        result.double();
+
+        for (table, digits) in tables.iter().zip(&digits) {
+            match digits.get(i) {
+                Some(digit) if *digit != 0 => {
+                    result.add_assign_mixed(&table[((digit + 15) / 2) as usize])
+                }
+                _ => {}
+            }
+        }
+    }
+
+    result
+}
+
+fn pippenger<C: CurveProjective>(bases: &[C], scalars: &[Fr]) -> C {
+    let bases: Vec<_> = bases.iter().map(|b| b.into_affine()).collect();
+    let scalars: Vec<[u64; 4]> = scalars
+        .iter()
+        .map(|s| {
+            let mut t = [0u64; 4];
+            t.clone_from_slice(s.into_repr().as_ref());
+            t
+        })
+        .collect();
+    // Annoying step to keep the borrow checker happy
+    let s: Vec<&[u64; 4]> = scalars.iter().collect();
+    C::Affine::sum_of_products(bases.as_slice(), s.as_slice())
+}
+
+// table[i] = (2 * i - 15) * base, with the corrections [-base, -2 * base]. Normalized (z = 1)
+fn odd_multiples<C: CurveProjective>(base: &C) -> (Vec<C>, [C; 2]) {
+    let mut double = *base;
+    double.double();
+
+    let mut points = Vec::with_capacity(TABLE_SIZE + 2);
+    let mut point = *base;
+    for _ in 0..TABLE_SIZE / 2 {
+        points.push(point);
+        point.add_assign(&double);
+    }
+
+    let mut negated_base = *base;
+    negated_base.negate();
+    let mut negated_double = double;
+    negated_double.negate();
+    points.push(negated_base);
+    points.push(negated_double);
+
+    C::batch_normalization(&mut points);
+
+    let positive = &points[..TABLE_SIZE / 2];
+
+    let mut table = Vec::with_capacity(TABLE_SIZE);
+    for point in positive.iter().rev() {
+        let mut negated = *point;
+        negated.negate();
+        table.push(negated);
+    }
+    table.extend_from_slice(positive);
+
+    let corrections = [points[TABLE_SIZE / 2], points[TABLE_SIZE / 2 + 1]];
+
+    (table, corrections)
+}
+
+/*
+* Homogeneous projective point (X : Y : Z) of affine (X / Z, Y / Z), the identity is (0 : 1 : 0).
+* pairing_plus points are jacobian (X / Z ^ 2, Y / Z ^ 3).
+*/
+#[derive(Clone, Copy)]
+struct Point<F: Field> {
+    x: F,
+    y: F,
+    z: F,
+}
+
+impl<F: Field> Point<F> {
+    fn identity() -> Self {
+        Self {
+            x: F::zero(),
+            y: F::one(),
+            z: F::zero(),
+        }
+    }
+
+    // From a normalized public point
+    fn from_normalized<C: CurveProjective<Base = F>>(point: &C) -> Self {
+        if point.is_zero() {
+            return Self::identity();
+        }
+
+        let (x, y, _) = point.as_tuple();
+
+        Self {
+            x: *x,
+            y: *y,
+            z: F::one(),
+        }
+    }
+
+    // (X * Z, Y * Z ^ 2, Z), the identity has Z = 0
+    fn into_projective<C: CurveProjective<Base = F>>(self) -> C {
+        let mut x = self.x;
+        x.mul_assign(&self.z);
+
+        let mut y = self.z;
+        y.square();
+        y.mul_assign(&self.y);
+
+        let mut point = C::zero();
+
+        // Safety: (X * Z, Y * Z ^ 2, Z) is the same point in jacobian coordinates
+        unsafe {
+            let (point_x, point_y, point_z) = point.as_tuple_mut();
+            *point_x = x;
+            *point_y = y;
+            *point_z = self.z;
+        }
+
+        point
+    }
+
+    // Reads every point of the table, keeps table[index]
+    fn select(table: &[Self], index: usize) -> Self {
+        let mut selected = Self::identity();
+
+        for (i, point) in table.iter().enumerate() {
+            let choice = ct_eq(i, index);
+
+            conditional_assign(&mut selected.x, &point.x, choice);
+            conditional_assign(&mut selected.y, &point.y, choice);
+            conditional_assign(&mut selected.z, &point.z, choice);
+        }
+
+        selected
+    }
+
+    // Complete addition for a = 0 (Renes, Costello, Batina; algorithm 7)
+    fn add(&self, other: &Self, b3: &F) -> Self {
+        let mut t0 = self.x;
+        t0.mul_assign(&other.x);
+        let mut t1 = self.y;
+        t1.mul_assign(&other.y);
+        let mut t2 = self.z;
+        t2.mul_assign(&other.z);
+
+        let mut t3 = self.x;
+        t3.add_assign(&self.y);
+        let mut t4 = other.x;
+        t4.add_assign(&other.y);
+        t3.mul_assign(&t4);
+
+        t4 = t0;
+        t4.add_assign(&t1);
+        t3.sub_assign(&t4);
+        t4 = self.y;
+        t4.add_assign(&self.z);
+
+        let mut x3 = other.y;
+        x3.add_assign(&other.z);
+        t4.mul_assign(&x3);
+        x3 = t1;
+        x3.add_assign(&t2);
+
+        t4.sub_assign(&x3);
+        x3 = self.x;
+        x3.add_assign(&self.z);
+        let mut y3 = other.x;
+        y3.add_assign(&other.z);
+
+        x3.mul_assign(&y3);
+        y3 = t0;
+        y3.add_assign(&t2);
+        let mut y3_tmp = x3;
+        y3_tmp.sub_assign(&y3);
+        y3 = y3_tmp;
+
+        x3 = t0;
+        x3.double();
+        t0.add_assign(&x3);
+        t2.mul_assign(b3);
+
+        let mut z3 = t1;
+        z3.add_assign(&t2);
+        t1.sub_assign(&t2);
+        y3.mul_assign(b3);
+
+        x3 = t4;
+        x3.mul_assign(&y3);
+        t2 = t3;
+        t2.mul_assign(&t1);
+        t2.sub_assign(&x3);
+        x3 = t2;
+
+        y3.mul_assign(&t0);
+        t1.mul_assign(&z3);
+        y3.add_assign(&t1);
+
+        t0.mul_assign(&t3);
+        z3.mul_assign(&t4);
+        z3.add_assign(&t0);
+
+        Self {
+            x: x3,
+            y: y3,
+            z: z3,
+        }
+    }
+
+    // Doubling for a = 0 (Renes, Costello, Batina; algorithm 9)
+    fn double(&self, b3: &F) -> Self {
+        let mut t0 = self.y;
+        t0.square();
+
+        let mut z3 = t0;
+        z3.double();
+        z3.double();
+        z3.double();
+
+        let mut t1 = self.y;
+        t1.mul_assign(&self.z);
+        let mut t2 = self.z;
+        t2.square();
+        t2.mul_assign(b3);
+
+        let mut x3 = t2;
+        x3.mul_assign(&z3);
+        let mut y3 = t0;
+        y3.add_assign(&t2);
+        z3.mul_assign(&t1);
+
+        t1 = t2;
+        t1.double();
+        t2.add_assign(&t1);
+        t0.sub_assign(&t2);
+
+        y3.mul_assign(&t0);
+        y3.add_assign(&x3);
+        t1 = self.x;
+        t1.mul_assign(&self.y);
+
+        x3 = t0;
+        x3.mul_assign(&t1);
+        x3.double();
+
+        Self {
+            x: x3,
+            y: y3,
+            z: z3,
+        }
+    }
+}
+
+// 3 * b of the curve y ^ 2 = x ^ 3 + b, from its generator
+fn curve_coeff_b3<C: CurveProjective>() -> C::Base {
+    let mut generator = C::Affine::one().into_projective();
+    C::batch_normalization(std::slice::from_mut(&mut generator));
+
+    let (x, y, _) = generator.as_tuple();
+
+    let mut b = *y;
+    b.square();
+    let mut x3 = *x;
+    x3.square();
+    x3.mul_assign(x);
+    b.sub_assign(&x3);
+
+    let mut b3 = b;
+    b3.double();
+    b3.add_assign(&b);
+
+    b3
+}
+
+// 1 if a == b, 0 otherwise, without branches
+fn ct_eq(a: usize, b: usize) -> u8 {
+    let difference = (a ^ b) as u64;
+    let not_equal = ((difference | difference.wrapping_neg()) >> 63) as u8;
+
+    black_box(1 ^ not_equal)
+}
+
+// target = source if choice is 1, through the bytes of the field element
+fn conditional_assign<F: Field>(target: &mut F, source: &F, choice: u8) {
+    let mask = black_box(choice).wrapping_neg();
+
+    // Safety: the pairing_plus field elements are arrays of u64 limbs, without padding
+    let (target_bytes, source_bytes) = unsafe {
+        (
+            std::slice::from_raw_parts_mut(target as *mut F as *mut u8, std::mem::size_of::<F>()),
+            std::slice::from_raw_parts(source as *const F as *const u8, std::mem::size_of::<F>()),
+        )
+    };
+
+    for (target_byte, source_byte) in target_bytes.iter_mut().zip(source_bytes) {
+        *target_byte ^= mask & (*target_byte ^ *source_byte);
+    }
+}
+
+// Table indices of the signed odd digits of k + 1 + parity, least significant first
+fn recode_scalar(scalar: &Fr) -> ([u8; DIGITS], usize) {
+    let mut k = [0u64; 4];
+    k.clone_from_slice(scalar.into_repr().as_ref());
+
+    // 1) k + 1 if k is even, k + 2 if k is odd. Fr is below 2 ^ 255, no overflow
+    let parity = (k[0] & 1) as usize;
+
+    let mut carry = 1 + parity as u64;
+    for limb in k.iter_mut() {
+        let (sum, overflow) = limb.overflowing_add(carry);
+        *limb = sum;
+        carry = overflow as u64;
+    }
+
+    // 2) digit = (k mod 32) - 16, k = (k - digit) / 16 stays odd
+    let mut indices = [0u8; DIGITS];
+    for index in indices.iter_mut().take(DIGITS - 1) {
+        let low = k[0] & 0x1f;
+        *index = (low >> 1) as u8;
+
+        k[0] = (k[0] & !0x1f) | 0x10;
+        for i in 0..3 {
+            k[i] = (k[i] >> WINDOW_BITS) | (k[i + 1] << (64 - WINDOW_BITS));
+        }
+        k[3] >>= WINDOW_BITS;
+    }
+
+    // 3) The last digit is the odd remainder in [1, 15]
+    indices[DIGITS - 1] = (TABLE_SIZE as u64 / 2 + (k[0] >> 1)) as u8;
+
+    (indices, parity)
+}
+
+// Width 5 non adjacent form: odd digits in [-15, 15] separated by zeros, least significant first
+fn wnaf_digits(scalar: &Fr) -> Vec<i8> {
+    let mut k = [0u64; 4];
+    k.clone_from_slice(scalar.into_repr().as_ref());
+
+    let mut digits = Vec::with_capacity(257);
+    while k.iter().any(|limb| *limb != 0) {
+        let mut digit = 0i64;
+
+        if k[0] & 1 == 1 {
+            digit = (k[0] & 0x1f) as i64;
+            if digit > 15 {
+                digit -= 32;
+            }
+
+            // k - digit, with a carry when the digit is negative
+            if digit > 0 {
+                k[0] -= digit as u64;
+            } else {
+                let mut carry = (-digit) as u64;
+                for limb in k.iter_mut() {
+                    let (sum, overflow) = limb.overflowing_add(carry);
+                    *limb = sum;
+                    carry = overflow as u64;
+                }
+            }
+        }
+
+        digits.push(digit as i8);
+
+        for i in 0..3 {
+            k[i] = (k[i] >> 1) | (k[i + 1] << 63);
+        }
+        k[3] >>= 1;
+    }
+
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::msm::{multi_scalar_mul_const_time, multi_scalar_mul_var_time};
+    use ff_zeroize::{Field, PrimeField};
+    use pairing_plus::bls12_381::{Fr, FrRepr, G1, G2};
+    use pairing_plus::CurveProjective;
+    use rand::thread_rng;
+
+    fn naive<C: CurveProjective<Scalar = Fr>>(bases: &[C], scalars: &[Fr]) -> C {
+        let mut result = C::zero();
+        for (base, scalar) in bases.iter().zip(scalars) {
+            let mut point = *base;
+            point.mul_assign(*scalar);
+            result.add_assign(&point);
+        }
+
+        result
+    }
+
+    fn check<C: CurveProjective<Scalar = Fr>>(bases: &[C], scalars: &[Fr]) {
+        let expected = naive(bases, scalars);
+
+        assert_eq!(multi_scalar_mul_const_time(bases, scalars), expected);
+        assert_eq!(multi_scalar_mul_var_time(bases, scalars), expected);
+    }
+
+    #[test]
+    fn test_multi_scalar_mul() {
+        let mut rng = thread_rng();
+
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+
+        // Edge scalars
+        let edge_scalars = [
+            Fr::zero(),
+            Fr::one(),
+            Fr::from_repr(FrRepr::from(2)).unwrap(),
+            Fr::from_repr(FrRepr::from(15)).unwrap(),
+            Fr::from_repr(FrRepr::from(16)).unwrap(),
+            minus_one,
+        ];
+
+        for scalar in &edge_scalars {
+            check(&[G1::random(&mut rng)], &[*scalar]);
+            check(&[G2::random(&mut rng)], &[*scalar]);
+        }
+
+        // Random scalars, Pippenger from 64 bases
+        for count in [1, 2, 5, 64] {
+            let scalars: Vec<Fr> = (0..count).map(|_| Fr::random(&mut rng)).collect();
+
+            let g1_bases: Vec<G1> = (0..count).map(|_| G1::random(&mut rng)).collect();
+            check(&g1_bases, &scalars);
+
+            let g2_bases: Vec<G2> = (0..count).map(|_| G2::random(&mut rng)).collect();
+            check(&g2_bases, &scalars);
+        }
+
+        // Same base twice
+        let base = G1::random(&mut rng);
+        check(&[base, base], &[Fr::random(&mut rng), Fr::random(&mut rng)]);
+
+        // Identity base
+        check(
+            &[G1::zero(), base],
+            &[Fr::random(&mut rng), Fr::random(&mut rng)],
+        );
+
+        // No bases
+        let bases: Vec<G1> = Vec::new();
+        assert!(multi_scalar_mul_const_time(&bases, []).is_zero());
+    }
+}
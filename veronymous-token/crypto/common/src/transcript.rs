@@ -0,0 +1,111 @@
+/*
+* Fiat-Shamir transcript in the style of Merlin.
+* The transcript starts with a versioned protocol identifier and every message is framed
+* by its label and length, so no two sequences of messages hash to the same challenge.
+* Challenges are appended back to the transcript, later challenges depend on them.
+*/
+
+use crate::hash_to_fr;
+use ff_zeroize::PrimeField;
+use pairing_plus::bls12_381::Fr;
+
+const TRANSCRIPT_VERSION: &[u8] = b"veronymous_transcript_v1";
+
+#[derive(Clone, Debug)]
+pub struct Transcript {
+    bytes: Vec<u8>,
+}
+
+impl Transcript {
+    // Protocol identifiers include their version, e.g. b"veronymous_token_v1"
+    pub fn new(protocol: &'static [u8]) -> Self {
+        let mut transcript = Self { bytes: Vec::new() };
+        transcript.append_message(b"transcript", TRANSCRIPT_VERSION);
+        transcript.append_message(b"protocol", protocol);
+
+        transcript
+    }
+
+    pub fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        self.append_framed(label);
+        self.append_framed(message);
+    }
+
+    pub fn append_u64(&mut self, label: &'static [u8], value: u64) {
+        self.append_message(label, &value.to_be_bytes());
+    }
+
+    pub fn challenge_fr(&mut self, label: &'static [u8]) -> Fr {
+        self.append_message(b"challenge_label", label);
+
+        let challenge = hash_to_fr(&self.bytes);
+
+        let mut challenge_bytes = Vec::with_capacity(32);
+        for limb in challenge.into_repr().as_ref().iter().rev() {
+            challenge_bytes.extend_from_slice(&limb.to_be_bytes());
+        }
+        self.append_message(b"challenge", &challenge_bytes);
+
+        challenge
+    }
+
+    // |length|bytes|
+    fn append_framed(&mut self, bytes: &[u8]) {
+        self.bytes
+            .extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+        self.bytes.extend_from_slice(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::transcript::Transcript;
+
+    #[test]
+    fn test_transcript() {
+        let mut transcript = Transcript::new(b"test_protocol_v1");
+        transcript.append_message(b"message", b"hello");
+        transcript.append_u64(b"number", 7);
+
+        let mut same_transcript = transcript.clone();
+
+        let challenge = transcript.challenge_fr(b"challenge");
+        assert_eq!(challenge, same_transcript.challenge_fr(b"challenge"));
+
+        // The challenge is ratcheted
+        assert_ne!(challenge, transcript.challenge_fr(b"challenge"));
+
+        // Other protocol
+        let mut other_transcript = Transcript::new(b"test_protocol_v2");
+        other_transcript.append_message(b"message", b"hello");
+        other_transcript.append_u64(b"number", 7);
+
+        assert_ne!(challenge, other_transcript.challenge_fr(b"challenge"));
+
+        // Same bytes with other framing
+        let mut framed_1 = Transcript::new(b"test_protocol_v1");
+        framed_1.append_message(b"message", b"ab");
+        framed_1.append_message(b"message", b"c");
+
+        let mut framed_2 = Transcript::new(b"test_protocol_v1");
+        framed_2.append_message(b"message", b"a");
+        framed_2.append_message(b"message", b"bc");
+
+        assert_ne!(
+            framed_1.challenge_fr(b"challenge"),
+            framed_2.challenge_fr(b"challenge")
+        );
+
+        // Other label
+        let mut labelled_1 = Transcript::new(b"test_protocol_v1");
+        labelled_1.append_message(b"message_1", b"hello");
+
+        let mut labelled_2 = Transcript::new(b"test_protocol_v1");
+        labelled_2.append_message(b"message_2", b"hello");
+
+        assert_ne!(
+            labelled_1.challenge_fr(b"challenge"),
+            labelled_2.challenge_fr(b"challenge")
+        );
+    }
+}
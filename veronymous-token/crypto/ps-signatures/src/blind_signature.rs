@@ -1,16 +1,300 @@
 use crate::error::PsSignatureError;
+use crate::error::PsSignatureError::{DeserializationError, SerializationError, SigningError};
 use crate::keys::{PsParams, PsPublicKey, PsSigningKey};
+use crate::serde::{read_fr, read_g1_point, Serializable};
 use crate::signature::PsSignature;
+use byteorder::ReadBytesExt;
 use commitments::pedersen_commitment::PedersenCommitmentCommitting;
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitting};
+use crypto_common::multi_scalar_mul_var_time;
+use crypto_common::transcript::Transcript;
 use ff_zeroize::Field;
 use pairing_plus::bls12_381::{Fr, G1};
+use pairing_plus::serdes::SerDes;
 use pairing_plus::CurveProjective;
 use rand::CryptoRng;
+use std::io::Cursor;
+
+const TRANSCRIPT_PROTOCOL: &[u8] = b"ps_blind_signature_request_v1";
+
+/*
+* Blind signature request on the hidden messages at any indices.
+* commitment = g ^ t * prod(Y_i ^ m_i) with a proof of knowledge of the hidden messages and t.
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlindSignatureRequest {
+    // Sorted indices of the hidden messages
+    pub hidden_indices: Vec<usize>,
+
+    pub commitment: G1,
+
+    pub randomness_commitment: G1,
+
+    // Hidden message responses followed by the blinding response
+    pub responses: Vec<Fr>,
+}
+
+impl BlindSignatureRequest {
+    pub fn new(
+        hidden_messages: &[(usize, Fr)],
+        blinding: &Fr,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<Self, PsSignatureError> {
+        let mut hidden_messages = hidden_messages.to_vec();
+        hidden_messages.sort_by_key(|(index, _)| *index);
+
+        let hidden_indices: Vec<usize> = hidden_messages.iter().map(|(index, _)| *index).collect();
+        check_hidden_indices(&hidden_indices, public_key)?;
+
+        // 1) Hidden messages commitment
+        let gens = request_gens(&hidden_indices, public_key, params);
+
+        let mut secrets: Vec<Fr> = hidden_messages
+            .iter()
+            .map(|(_, message)| *message)
+            .collect();
+        secrets.push(*blinding);
+
+        let commitment =
+            PedersenCommitmentCommitting::new(Some(gens.clone()), Some(secrets.clone()))
+                .map_err(|e| SigningError(format!("Could not create commitment. {:?}", e)))?
+                .finish();
+
+        // 2) Proof of knowledge of the hidden messages and blinding
+        let mut prover_committing = ProverCommitting::new();
+        for gen in &gens {
+            prover_committing.commit(*gen, None);
+        }
+
+        let prover_committed = prover_committing.finish();
+
+        let mut challenge_bytes = prover_committed.challenge_bytes();
+        commitment.0.serialize(&mut challenge_bytes, false).unwrap();
+
+        let challenge = request_challenge(&challenge_bytes);
+
+        let proof = prover_committed
+            .generate_proof(&challenge, &secrets)
+            .map_err(|e| SigningError(format!("Could not generate commitment proof. {:?}", e)))?;
+
+        Ok(Self {
+            hidden_indices,
+            commitment: commitment.0,
+            randomness_commitment: proof.commitment,
+            responses: proof.responses,
+        })
+    }
+
+    pub fn verify(
+        &self,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<bool, PsSignatureError> {
+        check_hidden_indices(&self.hidden_indices, public_key)?;
+
+        if self.responses.len() != self.hidden_indices.len() + 1 {
+            return Ok(false);
+        }
+
+        let gens = request_gens(&self.hidden_indices, public_key, params);
+        let proof = CommitmentProof::new(self.randomness_commitment, self.responses.clone());
+
+        // Get the challenge
+        let mut challenge_bytes = proof.challenge_bytes(&gens);
+        self.commitment
+            .serialize(&mut challenge_bytes, false)
+            .unwrap();
+
+        let challenge = request_challenge(&challenge_bytes);
+
+        proof
+            .verify(&gens, &self.commitment, &challenge)
+            .map_err(|e| SigningError(format!("Could not verify commitment proof. {:?}", e)))
+    }
+}
+
+impl Serializable for BlindSignatureRequest {
+    // |hidden count|hidden indices|commitment|randomness commitment|responses|
+    fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
+        if self.hidden_indices.len() > u8::MAX as usize {
+            return Err(SerializationError(format!(
+                "Cannot serialize more than {} hidden messages.",
+                u8::MAX
+            )));
+        }
+
+        let mut bytes = Vec::new();
+
+        bytes.push(self.hidden_indices.len() as u8);
+        for index in &self.hidden_indices {
+            if *index > u8::MAX as usize {
+                return Err(SerializationError(format!(
+                    "Invalid hidden message index ({}).",
+                    index
+                )));
+            }
+            bytes.push(*index as u8);
+        }
+
+        for point in [&self.commitment, &self.randomness_commitment] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        for response in &self.responses {
+            response.serialize(&mut bytes, true).map_err(|e| {
+                SerializationError(format!("Could not serialize response. {:?}", e))
+            })?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let hidden_count = cursor
+            .read_u8()
+            .map_err(|e| DeserializationError(format!("Could not read hidden count. {:?}", e)))?;
+
+        let mut hidden_indices = Vec::with_capacity(hidden_count as usize);
+        for _ in 0..hidden_count {
+            hidden_indices.push(cursor.read_u8().map_err(|e| {
+                DeserializationError(format!("Could not read hidden index. {:?}", e))
+            })? as usize);
+        }
+
+        let commitment = read_g1_point(&mut cursor)?;
+        let randomness_commitment = read_g1_point(&mut cursor)?;
+
+        let mut responses = Vec::with_capacity(hidden_count as usize + 1);
+        for _ in 0..hidden_count as usize + 1 {
+            responses.push(read_fr(&mut cursor)?);
+        }
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized blind signature request has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            hidden_indices,
+            commitment,
+            randomness_commitment,
+            responses,
+        })
+    }
+}
+
+// Blinded signature with the messages added by the issuer
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlindSignatureResponse {
+    pub signature: PsSignature,
+
+    // Sorted (index, message) of the revealed messages
+    pub revealed_messages: Vec<(usize, Fr)>,
+}
+
+impl BlindSignatureResponse {
+    pub fn unblind(&self, blinding: &Fr) -> PsSignature {
+        PsBlindSignature::unblind(&self.signature, blinding)
+    }
+
+    // All the signed messages given the hidden ones
+    pub fn messages(&self, hidden_messages: &[(usize, Fr)]) -> Vec<Fr> {
+        let mut messages: Vec<(usize, Fr)> = hidden_messages
+            .iter()
+            .chain(self.revealed_messages.iter())
+            .cloned()
+            .collect();
+        messages.sort_by_key(|(index, _)| *index);
+
+        messages.into_iter().map(|(_, message)| message).collect()
+    }
+}
+
+impl Serializable for BlindSignatureResponse {
+    // |sigma_1|sigma_2|revealed count|(index, message)..|
+    fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
+        if self.revealed_messages.len() > u8::MAX as usize {
+            return Err(SerializationError(format!(
+                "Cannot serialize more than {} revealed messages.",
+                u8::MAX
+            )));
+        }
+
+        let mut bytes = Vec::new();
+
+        for point in [&self.signature.sigma_1, &self.signature.sigma_2] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        bytes.push(self.revealed_messages.len() as u8);
+        for (index, message) in &self.revealed_messages {
+            if *index > u8::MAX as usize {
+                return Err(SerializationError(format!(
+                    "Invalid revealed message index ({}).",
+                    index
+                )));
+            }
+            bytes.push(*index as u8);
+
+            message
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize message. {:?}", e)))?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let sigma_1 = read_g1_point(&mut cursor)?;
+        let sigma_2 = read_g1_point(&mut cursor)?;
+
+        let revealed_count = cursor
+            .read_u8()
+            .map_err(|e| DeserializationError(format!("Could not read revealed count. {:?}", e)))?;
+
+        let mut revealed_messages = Vec::with_capacity(revealed_count as usize);
+        for _ in 0..revealed_count {
+            let index = cursor.read_u8().map_err(|e| {
+                DeserializationError(format!("Could not read revealed index. {:?}", e))
+            })? as usize;
+
+            revealed_messages.push((index, read_fr(&mut cursor)?));
+        }
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized blind signature response has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            signature: PsSignature { sigma_1, sigma_2 },
+            revealed_messages,
+        })
+    }
+}
 
 pub struct PsBlindSignature {}
 
 impl PsBlindSignature {
-    // Note: Hidden messages are not verified by this function
+    // Signs the commitment with the last messages revealed.
+    // Note: Hidden messages are not verified by this function, see sign_request
     pub fn new<R: CryptoRng + rand::RngCore>(
         commitment: impl Into<G1>,
         messages: &[Fr],
@@ -26,24 +310,67 @@ impl PsBlindSignature {
             )));
         }
 
-        // Select random u
-        let u = Fr::random(rng);
-
-        // Sigma 1 = g ^ u
-        let mut sigma_1 = params.g.clone();
-        sigma_1.mul_assign(u);
-
         // Add the revealed messages to the commitment
         let mut commitment = commitment.into();
 
         Self::add_messages_to_commitment(&mut commitment, messages, &public_key)?;
 
-        // Sigma 2 = (XC) ^ u
-        let mut sigma_2 = signing_key.x_cap.clone();
-        sigma_2.add_assign(&commitment);
-        sigma_2.mul_assign(u);
+        Ok(Self::sign_commitment(commitment, signing_key, params, rng))
+    }
+
+    /*
+     * Verify the request proof and sign the hidden messages with the revealed ones.
+     * Every message index must be either hidden or revealed.
+     */
+    pub fn sign_request<R: CryptoRng + rand::RngCore>(
+        request: &BlindSignatureRequest,
+        revealed_messages: &[(usize, Fr)],
+        signing_key: &PsSigningKey,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+        rng: &mut R,
+    ) -> Result<BlindSignatureResponse, PsSignatureError> {
+        let mut revealed_messages = revealed_messages.to_vec();
+        revealed_messages.sort_by_key(|(index, _)| *index);
+
+        // 1) Hidden and revealed messages cover every message once
+        let mut indices: Vec<usize> = request
+            .hidden_indices
+            .iter()
+            .cloned()
+            .chain(revealed_messages.iter().map(|(index, _)| *index))
+            .collect();
+        indices.sort_unstable();
+
+        if indices != (0..public_key.y_cap.len()).collect::<Vec<usize>>() {
+            return Err(PsSignatureError::InvalidArgumentError(format!(
+                "Hidden and revealed messages must cover the {} messages.",
+                public_key.y_cap.len()
+            )));
+        }
 
-        Ok(PsSignature { sigma_1, sigma_2 })
+        // 2) Verify the hidden messages proof
+        if !request.verify(public_key, params)? {
+            return Err(SigningError(
+                "Hidden messages proof verification failed.".to_string(),
+            ));
+        }
+
+        // 3) Add the revealed messages to the commitment and sign
+        let (bases, messages): (Vec<G1>, Vec<Fr>) = revealed_messages
+            .iter()
+            .map(|(index, message)| (public_key.y_cap[*index], *message))
+            .unzip();
+
+        let mut commitment = request.commitment;
+        commitment.add_assign(&multi_scalar_mul_var_time(&bases, &messages));
+
+        let signature = Self::sign_commitment(commitment, signing_key, params, rng);
+
+        Ok(BlindSignatureResponse {
+            signature,
+            revealed_messages,
+        })
     }
 
     pub fn unblind(signature: &PsSignature, blinding: &Fr) -> PsSignature {
@@ -60,6 +387,27 @@ impl PsBlindSignature {
         }
     }
 
+    fn sign_commitment<R: CryptoRng + rand::RngCore>(
+        commitment: G1,
+        signing_key: &PsSigningKey,
+        params: &PsParams,
+        rng: &mut R,
+    ) -> PsSignature {
+        // Select random u
+        let u = Fr::random(rng);
+
+        // Sigma 1 = g ^ u
+        let mut sigma_1 = params.g.clone();
+        sigma_1.mul_assign(u);
+
+        // Sigma 2 = (XC) ^ u
+        let mut sigma_2 = signing_key.x_cap.clone();
+        sigma_2.add_assign(&commitment);
+        sigma_2.mul_assign(u);
+
+        PsSignature { sigma_1, sigma_2 }
+    }
+
     fn add_messages_to_commitment(
         hidden_messages: &mut G1,
         messages: &[Fr],
@@ -81,10 +429,53 @@ impl PsBlindSignature {
     }
 }
 
+fn request_challenge(challenge_bytes: &[u8]) -> Fr {
+    let mut transcript = Transcript::new(TRANSCRIPT_PROTOCOL);
+    transcript.append_message(b"request", challenge_bytes);
+
+    transcript.challenge_fr(b"challenge")
+}
+
+// Hidden indices must be sorted, unique and in the key
+fn check_hidden_indices(
+    hidden_indices: &[usize],
+    public_key: &PsPublicKey,
+) -> Result<(), PsSignatureError> {
+    if hidden_indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(PsSignatureError::InvalidArgumentError(
+            "Hidden message indices must be unique.".to_string(),
+        ));
+    }
+
+    if let Some(index) = hidden_indices
+        .iter()
+        .find(|index| **index >= public_key.y_cap.len())
+    {
+        return Err(PsSignatureError::InvalidArgumentError(format!(
+            "Hidden message index ({}) is out of bounds.",
+            index
+        )));
+    }
+
+    Ok(())
+}
+
+// [Y_i for the hidden indices, g]
+fn request_gens(hidden_indices: &[usize], public_key: &PsPublicKey, params: &PsParams) -> Vec<G1> {
+    let mut gens: Vec<G1> = hidden_indices
+        .iter()
+        .map(|index| public_key.y_cap[*index])
+        .collect();
+    gens.push(params.g);
+
+    gens
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::blind_signature::PsBlindSignature;
+    use crate::blind_signature::{BlindSignatureRequest, BlindSignatureResponse, PsBlindSignature};
     use crate::keys::{PsParams, PsSigningKey};
+    use crate::serde::Serializable;
     use crate::signature::PsSignature;
     use commitments::pedersen_commitment::PedersenCommitmentCommitting;
     use crypto_common::rand_non_zero_fr;
@@ -168,4 +559,115 @@ mod tests {
             .unwrap();
         assert_eq!(false, verification_result);
     }
+
+    #[test]
+    fn test_blind_signature_request() {
+        // Setup
+        let mut rng = thread_rng();
+
+        let params = PsParams::generate(&mut rng);
+        let signing_key = PsSigningKey::generate(5, &params, &mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        // Hidden messages at non contiguous indices
+        let hidden_messages = vec![(3, Fr::random(&mut rng)), (0, Fr::random(&mut rng))];
+        let revealed_messages = vec![
+            (1, Fr::random(&mut rng)),
+            (4, Fr::random(&mut rng)),
+            (2, Fr::random(&mut rng)),
+        ];
+
+        // 1) Create the request
+        let blinding_factor = rand_non_zero_fr(&mut rng);
+
+        let request =
+            BlindSignatureRequest::new(&hidden_messages, &blinding_factor, &public_key, &params)
+                .unwrap();
+
+        assert_eq!(vec![0, 3], request.hidden_indices);
+        assert!(request.verify(&public_key, &params).unwrap());
+
+        // Serialize de serialize
+        let request_serialized = request.serialize().unwrap();
+        let request = BlindSignatureRequest::deserialize(&request_serialized).unwrap();
+
+        // 2) Blind sign
+        let response = PsBlindSignature::sign_request(
+            &request,
+            &revealed_messages,
+            &signing_key,
+            &public_key,
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+
+        let response_serialized = response.serialize().unwrap();
+        let response_deserialized =
+            BlindSignatureResponse::deserialize(&response_serialized).unwrap();
+
+        assert_eq!(response, response_deserialized);
+
+        // 3) Unblind and verify
+        let signature = response.unblind(&blinding_factor);
+        let messages = response.messages(&hidden_messages);
+
+        assert_eq!(hidden_messages[1].1, messages[0]);
+        assert_eq!(revealed_messages[0].1, messages[1]);
+        assert_eq!(hidden_messages[0].1, messages[3]);
+
+        assert!(signature.verify(&messages, &public_key, &params).unwrap());
+
+        // Hidden and revealed messages must cover every message once
+        let result = PsBlindSignature::sign_request(
+            &request,
+            &revealed_messages[..2],
+            &signing_key,
+            &public_key,
+            &params,
+            &mut rng,
+        );
+        assert!(result.is_err());
+
+        let result = PsBlindSignature::sign_request(
+            &request,
+            &[revealed_messages.clone(), vec![(0, Fr::random(&mut rng))]].concat(),
+            &signing_key,
+            &public_key,
+            &params,
+            &mut rng,
+        );
+        assert!(result.is_err());
+
+        // Bad proof
+        let mut bad_request = request.clone();
+        bad_request.commitment = params.g;
+
+        assert!(!bad_request.verify(&public_key, &params).unwrap());
+
+        let result = PsBlindSignature::sign_request(
+            &bad_request,
+            &revealed_messages,
+            &signing_key,
+            &public_key,
+            &params,
+            &mut rng,
+        );
+        assert!(result.is_err());
+
+        // Out of bounds index
+        let result = BlindSignatureRequest::new(
+            &[(5, Fr::random(&mut rng))],
+            &blinding_factor,
+            &public_key,
+            &params,
+        );
+        assert!(result.is_err());
+
+        // Trailing bytes
+        let mut bad_bytes = request_serialized.clone();
+        bad_bytes.push(0);
+
+        assert!(BlindSignatureRequest::deserialize(&bad_bytes).is_err());
+    }
 }
@@ -8,9 +8,21 @@ pub enum PsSignatureError {
     #[error("Signing error. {0}")]
     SigningError(String),
 
+    #[error("Invalid key. {0}")]
+    InvalidKeyError(String),
+
+    #[error("Proof error. {0}")]
+    ProofError(String),
+
     #[error("Serialization error. {0}")]
     SerializationError(String),
 
     #[error("Deserialization error. {0}")]
     DeserializationError(String),
+
+    #[error("Identity point. {0}")]
+    IdentityPointError(String),
+
+    #[error("Point not in the prime order subgroup. {0}")]
+    SubgroupError(String),
 }
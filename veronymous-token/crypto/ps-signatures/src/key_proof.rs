@@ -0,0 +1,243 @@
+/*
+* Issuer proof of knowledge of the signing key.
+* Schnorr proofs of x and y_i in X_tilde = g_tilde ^ x and Y_tilde_i = g_tilde ^ y_i
+* with a challenge bound to the whole public key.
+*/
+
+use crate::error::PsSignatureError;
+use crate::error::PsSignatureError::{
+    DeserializationError, InvalidArgumentError, ProofError, SerializationError,
+};
+use crate::keys::{PsParams, PsPublicKey, PsSigningKey};
+use crate::serde::{read_fr, read_g2_point, Serializable};
+use byteorder::ReadBytesExt;
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitting};
+use crypto_common::transcript::Transcript;
+use pairing_plus::bls12_381::{Fr, G2};
+use pairing_plus::serdes::SerDes;
+use std::io::Cursor;
+
+const TRANSCRIPT_PROTOCOL: &[u8] = b"ps_key_proof_v1";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PsKeyProof {
+    // x then y_i randomness commitments
+    pub randomness_commitments: Vec<G2>,
+
+    pub responses: Vec<Fr>,
+}
+
+impl PsKeyProof {
+    pub fn new(
+        signing_key: &PsSigningKey,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<Self, PsSignatureError> {
+        if signing_key.y.len() != public_key.y_cap_tilde.len() {
+            return Err(InvalidArgumentError(format!(
+                "Signing key must have {} y.",
+                public_key.y_cap_tilde.len()
+            )));
+        }
+
+        // 1) Commit to the randomness of each secret
+        let committed: Vec<_> = (0..=signing_key.y.len())
+            .map(|_| {
+                let mut committing = ProverCommitting::new();
+                committing.commit(params.g_tilde, None);
+
+                committing.finish()
+            })
+            .collect();
+
+        // 2) Challenge bound to the public key
+        let mut challenge_bytes = public_key_bytes(public_key);
+        for committed in &committed {
+            challenge_bytes.append(&mut committed.challenge_bytes());
+        }
+
+        let challenge = challenge(&challenge_bytes);
+
+        // 3) Responses
+        let secrets = std::iter::once(&signing_key.x).chain(&signing_key.y);
+
+        let mut randomness_commitments = Vec::with_capacity(committed.len());
+        let mut responses = Vec::with_capacity(committed.len());
+        for (committed, secret) in committed.into_iter().zip(secrets) {
+            let proof = committed
+                .generate_proof(&challenge, &[*secret])
+                .map_err(|e| ProofError(format!("Could not generate key proof. {:?}", e)))?;
+
+            randomness_commitments.push(proof.commitment);
+            responses.push(proof.responses[0]);
+        }
+
+        Ok(Self {
+            randomness_commitments,
+            responses,
+        })
+    }
+
+    pub fn verify(
+        &self,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<bool, PsSignatureError> {
+        let count = public_key.y_cap_tilde.len() + 1;
+
+        if self.randomness_commitments.len() != count || self.responses.len() != count {
+            return Ok(false);
+        }
+
+        let gens = [params.g_tilde];
+
+        let proofs: Vec<CommitmentProof<G2>> = self
+            .randomness_commitments
+            .iter()
+            .zip(&self.responses)
+            .map(|(randomness_commitment, response)| {
+                CommitmentProof::new(*randomness_commitment, vec![*response])
+            })
+            .collect();
+
+        // Get the challenge
+        let mut challenge_bytes = public_key_bytes(public_key);
+        for proof in &proofs {
+            challenge_bytes.append(&mut proof.challenge_bytes(&gens));
+        }
+
+        let challenge = challenge(&challenge_bytes);
+
+        let commitments = std::iter::once(&public_key.x_cap_tilde).chain(&public_key.y_cap_tilde);
+
+        for (proof, commitment) in proofs.iter().zip(commitments) {
+            if !proof
+                .verify(&gens, commitment, &challenge)
+                .map_err(|e| ProofError(format!("Could not verify key proof. {:?}", e)))?
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl Serializable for PsKeyProof {
+    // |count|randomness commitments|responses|
+    fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
+        if self.randomness_commitments.len() > u8::MAX as usize
+            || self.randomness_commitments.len() != self.responses.len()
+        {
+            return Err(SerializationError(
+                "Invalid number of key proof responses.".to_string(),
+            ));
+        }
+
+        let mut bytes = vec![self.randomness_commitments.len() as u8];
+
+        for randomness_commitment in &self.randomness_commitments {
+            randomness_commitment
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        for response in &self.responses {
+            response.serialize(&mut bytes, true).map_err(|e| {
+                SerializationError(format!("Could not serialize response. {:?}", e))
+            })?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let count = cursor
+            .read_u8()
+            .map_err(|e| DeserializationError(format!("Could not read count. {:?}", e)))?
+            as usize;
+
+        let randomness_commitments = (0..count)
+            .map(|_| read_g2_point(&mut cursor))
+            .collect::<Result<Vec<G2>, _>>()?;
+        let responses = (0..count)
+            .map(|_| read_fr(&mut cursor))
+            .collect::<Result<Vec<Fr>, _>>()?;
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized key proof has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            randomness_commitments,
+            responses,
+        })
+    }
+}
+
+fn challenge(challenge_bytes: &[u8]) -> Fr {
+    let mut transcript = Transcript::new(TRANSCRIPT_PROTOCOL);
+    transcript.append_message(b"key_proof", challenge_bytes);
+
+    transcript.challenge_fr(b"challenge")
+}
+
+fn public_key_bytes(public_key: &PsPublicKey) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    public_key.x_cap_tilde.serialize(&mut bytes, false).unwrap();
+    for y_cap in &public_key.y_cap {
+        y_cap.serialize(&mut bytes, false).unwrap();
+    }
+    for y_cap_tilde in &public_key.y_cap_tilde {
+        y_cap_tilde.serialize(&mut bytes, false).unwrap();
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key_proof::PsKeyProof;
+    use crate::keys::{PsParams, PsSigningKey};
+    use crate::serde::Serializable;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_key_proof() {
+        let mut rng = thread_rng();
+
+        let params = PsParams::generate(&mut rng);
+        let signing_key = PsSigningKey::generate(3, &params, &mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        let proof = PsKeyProof::new(&signing_key, &public_key, &params).unwrap();
+
+        assert!(proof.verify(&public_key, &params).unwrap());
+
+        // Serialize de serialize
+        let proof_serialized = proof.serialize().unwrap();
+        let proof_deserialized = PsKeyProof::deserialize(&proof_serialized).unwrap();
+
+        assert_eq!(proof, proof_deserialized);
+
+        // Proof for another key
+        let other_public_key =
+            PsSigningKey::generate(3, &params, &mut rng).derive_public_key(&params);
+
+        assert!(!proof.verify(&other_public_key, &params).unwrap());
+
+        // Signing key of another key
+        let other_signing_key = PsSigningKey::generate(3, &params, &mut rng);
+        let proof = PsKeyProof::new(&other_signing_key, &public_key, &params).unwrap();
+
+        assert!(!proof.verify(&public_key, &params).unwrap());
+    }
+}
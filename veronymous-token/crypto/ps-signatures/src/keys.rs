@@ -2,9 +2,11 @@ use byteorder::ReadBytesExt;
 use crypto_common::rand_non_zero_fr;
 use ff_zeroize::Field;
 use pairing_plus::bls12_381::Fr;
-use pairing_plus::bls12_381::{G1, G2};
+use pairing_plus::bls12_381::{Bls12, G1, G2};
+use pairing_plus::hash_to_curve::HashToCurve;
+use pairing_plus::hash_to_field::ExpandMsgXmd;
 use pairing_plus::serdes::SerDes;
-use pairing_plus::CurveProjective;
+use pairing_plus::{CurveProjective, Engine};
 use rand::CryptoRng;
 use serde::de::Visitor;
 use serde::ser::Error;
@@ -13,8 +15,10 @@ use std::fmt::Formatter;
 use std::io::Cursor;
 
 use crate::error::PsSignatureError;
-use crate::error::PsSignatureError::{DeserializationError, SerializationError};
-use crate::serde::{read_fr, read_g1_point, read_g2_point, Serializable};
+use crate::error::PsSignatureError::{
+    DeserializationError, InvalidArgumentError, InvalidKeyError, SerializationError,
+};
+use crate::serde::{check_point, read_fr, read_g1_point, read_g2_point, Serializable};
 
 const SERIALIZED_G1_LENGTH: usize = 48;
 const SERIALIZED_G2_LENGTH: usize = 96;
@@ -23,12 +27,21 @@ const SERIALIZED_FR_LENGTH: usize = 32;
 
 const SERIALIZED_PARAMS_LENGTH: usize = SERIALIZED_G1_LENGTH + SERIALIZED_G2_LENGTH;
 
+const PARAMS_G1_DST: &[u8] = b"BLS12381G1_XMD:BLAKE2B_PS_PARAMS:1_0_0";
+const PARAMS_G2_DST: &[u8] = b"BLS12381G2_XMD:BLAKE2B_PS_PARAMS:1_0_0";
+
+// Seed of the parameters shared by the issuers
+pub const DEFAULT_PARAMS_SEED: &[u8] = b"veronymous_ps_params";
+
 // TODO: Enforce consistent network order (Big-endian)
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct PsParams {
     pub g: G1,
     pub g_tilde: G2,
+
+    // Public seed the generators are hashed from. None if they were picked at random
+    pub seed: Option<Vec<u8>>,
 }
 
 impl PsParams {
@@ -36,11 +49,55 @@ impl PsParams {
         Self {
             g: G1::random(rng),
             g_tilde: G2::random(rng),
+            seed: None,
+        }
+    }
+
+    // Nothing up my sleeve generators. Nobody knows a trapdoor between them
+    pub fn from_seed(seed: &[u8]) -> Result<Self, PsSignatureError> {
+        if seed.is_empty() || seed.len() > u8::MAX as usize {
+            return Err(InvalidArgumentError(format!(
+                "Seed must have between 1 and {} bytes.",
+                u8::MAX
+            )));
+        }
+
+        let (g, g_tilde) = derive_generators(seed);
+
+        Ok(Self {
+            g,
+            g_tilde,
+            seed: Some(seed.to_vec()),
+        })
+    }
+
+    // Generators are not the identity, are in the prime order subgroups and match the seed
+    pub fn validate(&self) -> Result<(), PsSignatureError> {
+        check_point(&self.g, "g")?;
+        check_point(&self.g_tilde, "g_tilde")?;
+
+        if let Some(seed) = &self.seed {
+            if derive_generators(seed) != (self.g, self.g_tilde) {
+                return Err(InvalidKeyError(
+                    "Generators are not derived from the seed.".to_string(),
+                ));
+            }
         }
+
+        Ok(())
     }
 }
 
+fn derive_generators(seed: &[u8]) -> (G1, G2) {
+    let g = <G1 as HashToCurve<ExpandMsgXmd<blake2::Blake2b>>>::hash_to_curve(seed, PARAMS_G1_DST);
+    let g_tilde =
+        <G2 as HashToCurve<ExpandMsgXmd<blake2::Blake2b>>>::hash_to_curve(seed, PARAMS_G2_DST);
+
+    (g, g_tilde)
+}
+
 impl Serializable for PsParams {
+    // |g|g_tilde| followed by |seed length|seed| for seeded parameters
     fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
         let mut vec = Vec::with_capacity(SERIALIZED_PARAMS_LENGTH);
 
@@ -52,13 +109,25 @@ impl Serializable for PsParams {
             .serialize(&mut vec, true)
             .map_err(|e| SerializationError(format!("Could not serialize g. {:?}", e)))?;
 
+        if let Some(seed) = &self.seed {
+            if seed.is_empty() || seed.len() > u8::MAX as usize {
+                return Err(SerializationError(format!(
+                    "Seed must have between 1 and {} bytes.",
+                    u8::MAX
+                )));
+            }
+
+            vec.push(seed.len() as u8);
+            vec.extend_from_slice(seed);
+        }
+
         Ok(vec)
     }
 
     fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError> {
-        if bytes.len() != SERIALIZED_PARAMS_LENGTH {
+        if bytes.len() < SERIALIZED_PARAMS_LENGTH {
             return Err(DeserializationError(format!(
-                "Invalid number of bytes. Got {} but expected {}",
+                "Invalid number of bytes. Got {} but expected at least {}",
                 bytes.len(),
                 SERIALIZED_PARAMS_LENGTH
             )));
@@ -66,11 +135,22 @@ impl Serializable for PsParams {
 
         let mut cursor = Cursor::new(bytes);
 
-        let g = read_g1_point(&mut cursor).map_err(|e| DeserializationError(format!("{:?}", e)))?;
-        let g_tilde =
-            read_g2_point(&mut cursor).map_err(|e| DeserializationError(format!("{:?}", e)))?;
-
-        Ok(Self { g, g_tilde })
+        let g = read_g1_point(&mut cursor)?;
+        let g_tilde = read_g2_point(&mut cursor)?;
+
+        let seed = match &bytes[SERIALIZED_PARAMS_LENGTH..] {
+            [] => None,
+            [length, seed @ ..] if *length != 0 && *length as usize == seed.len() => {
+                Some(seed.to_vec())
+            }
+            _ => {
+                return Err(DeserializationError(
+                    "Invalid params seed length.".to_string(),
+                ))
+            }
+        };
+
+        Ok(Self { g, g_tilde, seed })
     }
 }
 
@@ -211,7 +291,8 @@ impl Serializable for PsSigningKey {
             .map_err(|e| SerializationError(format!("Could not serialize x. {:?}", e)))?;
 
         for y in &self.y {
-            y.serialize(&mut bytes, true).unwrap();
+            y.serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize y. {:?}", e)))?;
         }
 
         Ok(bytes)
@@ -260,6 +341,47 @@ pub struct PsPublicKey {
     pub y_cap_tilde: Vec<G2>,
 }
 
+impl PsPublicKey {
+    /*
+     * Check that the key is well formed before using it.
+     * The points are not the identity and are in the prime order subgroups
+     * and Y_i and Y_tilde_i have the same discrete log: e(Y_i, g_tilde) == e(g, Y_tilde_i).
+     */
+    pub fn validate(&self, params: &PsParams) -> Result<(), PsSignatureError> {
+        params.validate()?;
+
+        if self.y_cap.is_empty() || self.y_cap.len() != self.y_cap_tilde.len() {
+            return Err(InvalidKeyError(format!(
+                "Key must have the same number of y_cap ({}) and y_cap_tilde ({}).",
+                self.y_cap.len(),
+                self.y_cap_tilde.len()
+            )));
+        }
+
+        check_point(&self.x_cap_tilde, "x_cap_tilde")?;
+
+        let mut negated_g = params.g;
+        negated_g.negate();
+
+        for (i, (y_cap, y_cap_tilde)) in self.y_cap.iter().zip(&self.y_cap_tilde).enumerate() {
+            check_point(y_cap, &format!("y_cap[{}]", i))?;
+            check_point(y_cap_tilde, &format!("y_cap_tilde[{}]", i))?;
+
+            // e(Y_i, g_tilde) * e(-g, Y_tilde_i) == 1
+            let product = Bls12::pairing_product(*y_cap, params.g_tilde, negated_g, *y_cap_tilde);
+
+            if product != <Bls12 as Engine>::Fqk::one() {
+                return Err(InvalidKeyError(format!(
+                    "y_cap[{}] and y_cap_tilde[{}] do not match.",
+                    i, i
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl Serializable for PsPublicKey {
     // |y_cap|x_cap_tilde|y_cap_tilde
     fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
@@ -281,6 +403,15 @@ impl Serializable for PsPublicKey {
                 u8::MAX
             )));
         }
+
+        // A single count is encoded for y_cap and y_cap_tilde
+        if self.y_cap.len() != self.y_cap_tilde.len() {
+            return Err(SerializationError(format!(
+                "Key must have the same number of y_cap ({}) and y_cap_tilde ({}).",
+                self.y_cap.len(),
+                self.y_cap_tilde.len()
+            )));
+        }
         bytes.push(self.y_cap.len() as u8);
 
         // Serialize y_cap
@@ -334,6 +465,12 @@ impl Serializable for PsPublicKey {
             })?);
         }
 
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized public key has trailing bytes.".to_string(),
+            ));
+        }
+
         Ok(Self {
             y_cap,
             x_cap_tilde,
@@ -406,8 +543,10 @@ impl<'de> Deserialize<'de> for PsPublicKey {
 
 #[cfg(test)]
 mod tests {
-    use crate::keys::{PsParams, PsPublicKey, PsSigningKey};
+    use crate::keys::{PsParams, PsPublicKey, PsSigningKey, DEFAULT_PARAMS_SEED};
     use crate::serde::Serializable;
+    use pairing_plus::bls12_381::{G1, G2};
+    use pairing_plus::CurveProjective;
 
     #[test]
     fn generate_key_pair_test() {
@@ -433,5 +572,90 @@ mod tests {
         let public_key_deserialized = PsPublicKey::deserialize(&public_key_serialized).unwrap();
 
         assert_eq!(public_key, public_key_deserialized);
+
+        // Trailing bytes
+        let mut bad_public_key_serialized = public_key_serialized.clone();
+        bad_public_key_serialized.push(0);
+
+        assert!(PsPublicKey::deserialize(&bad_public_key_serialized).is_err());
+
+        // Different number of y_cap and y_cap_tilde
+        let mut bad_public_key = public_key.clone();
+        bad_public_key.y_cap_tilde.pop();
+
+        assert!(bad_public_key.serialize().is_err());
+    }
+
+    #[test]
+    fn test_validate_public_key() {
+        let mut rng = rand::thread_rng();
+
+        let params = PsParams::generate(&mut rng);
+        let public_key = PsSigningKey::generate(3, &params, &mut rng).derive_public_key(&params);
+
+        assert!(public_key.validate(&params).is_ok());
+
+        // Y and Y tilde with different discrete logs
+        let mut bad_public_key = public_key.clone();
+        bad_public_key.y_cap.swap(0, 1);
+
+        assert!(bad_public_key.validate(&params).is_err());
+
+        // Identity
+        let mut bad_public_key = public_key.clone();
+        bad_public_key.x_cap_tilde = G2::zero();
+
+        assert!(bad_public_key.validate(&params).is_err());
+
+        let mut bad_public_key = public_key.clone();
+        bad_public_key.y_cap[2] = G1::zero();
+        bad_public_key.y_cap_tilde[2] = G2::zero();
+
+        assert!(bad_public_key.validate(&params).is_err());
+
+        // Missing Y tilde
+        let mut bad_public_key = public_key.clone();
+        bad_public_key.y_cap_tilde.pop();
+
+        assert!(bad_public_key.validate(&params).is_err());
+
+        // Other parameters
+        let other_params = PsParams::generate(&mut rng);
+
+        assert!(public_key.validate(&other_params).is_err());
+    }
+
+    #[test]
+    fn test_params_from_seed() {
+        let params = PsParams::from_seed(DEFAULT_PARAMS_SEED).unwrap();
+
+        assert!(params.validate().is_ok());
+        assert_eq!(params, PsParams::from_seed(DEFAULT_PARAMS_SEED).unwrap());
+        assert_ne!(params, PsParams::from_seed(b"other_seed").unwrap());
+
+        // Serialize de serialize
+        let params_serialized = params.serialize().unwrap();
+        let params_deserialized = PsParams::deserialize(&params_serialized).unwrap();
+
+        assert_eq!(params, params_deserialized);
+
+        // Generators not derived from the seed
+        let mut bad_params = params.clone();
+        bad_params.seed = Some(b"other_seed".to_vec());
+
+        assert!(bad_params.validate().is_err());
+
+        let mut bad_params = params.clone();
+        bad_params.g_tilde = G2::one();
+
+        assert!(bad_params.validate().is_err());
+
+        // Truncated seed
+        let mut bad_params_serialized = params_serialized.clone();
+        bad_params_serialized.pop();
+
+        assert!(PsParams::deserialize(&bad_params_serialized).is_err());
+
+        assert!(PsParams::from_seed(&[]).is_err());
     }
 }
@@ -6,7 +6,11 @@ pub type Blinding = Fr;
 
 pub mod blind_signature;
 pub mod error;
+pub mod key_proof;
 pub mod keys;
+pub mod pok;
 pub mod pok_sig;
+pub mod prepared;
 pub mod serde;
 pub mod signature;
+pub mod threshold;
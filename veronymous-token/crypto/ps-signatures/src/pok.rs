@@ -0,0 +1,666 @@
+/*
+* Proof of knowledge of a ps signature on a message vector with partial disclosure.
+* The signature is randomized (pok_sig) and the hidden messages are committed to in
+* commitment = g_tilde ^ t * prod(Y_tilde_i ^ m_i) with a proof of knowledge of (m_i, t).
+* The verifier adds the revealed messages to the commitment and checks
+* e(sigma_1', X_tilde * commitment * prod(Y_tilde_j ^ m_j)) == e(sigma_2', g_tilde).
+* Hidden message responses can be linked to other proofs by sharing their blinding factor.
+*/
+
+use crate::error::PsSignatureError;
+use crate::error::PsSignatureError::{
+    DeserializationError, InvalidArgumentError, ProofError, SerializationError,
+};
+use crate::keys::{PsParams, PsPublicKey};
+use crate::pok_sig::PsPokOfSignatureProof;
+use crate::prepared::PreparedPsPublicKey;
+use crate::serde::{read_fr, read_g1_point, read_g2_point, Serializable};
+use crate::signature::PsSignature;
+use byteorder::ReadBytesExt;
+use commitments::pedersen_commitment::PedersenCommitmentCommitting;
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitted, ProverCommitting};
+use crypto_common::transcript::Transcript;
+use crypto_common::{multi_scalar_mul_var_time, rand_non_zero_fr};
+use pairing_plus::bls12_381::{Fr, G2};
+use pairing_plus::serdes::SerDes;
+use pairing_plus::CurveProjective;
+use rand::CryptoRng;
+use std::io::Cursor;
+
+const TRANSCRIPT_PROTOCOL: &[u8] = b"ps_signature_pok_v1";
+
+pub struct PsPokBuilder<'a> {
+    signature: &'a PsSignature,
+
+    messages: &'a [Fr],
+
+    revealed_indices: Vec<usize>,
+
+    blinding_factors: Vec<(usize, Fr)>,
+
+    public_key: &'a PsPublicKey,
+
+    params: &'a PsParams,
+}
+
+impl<'a> PsPokBuilder<'a> {
+    // Messages at the indices are revealed to the verifier
+    pub fn reveal(mut self, indices: &[usize]) -> Self {
+        self.revealed_indices.extend_from_slice(indices);
+        self
+    }
+
+    // Blinding factor of a hidden message, to link its response to another proof
+    pub fn blinding_factor(mut self, index: usize, blinding_factor: Fr) -> Self {
+        self.blinding_factors.push((index, blinding_factor));
+        self
+    }
+
+    pub fn commit<R: CryptoRng + rand::RngCore>(
+        self,
+        rng: &mut R,
+    ) -> Result<PsPokCommitted, PsSignatureError> {
+        let message_count = self.public_key.y_cap_tilde.len();
+
+        if self.messages.len() != message_count {
+            return Err(InvalidArgumentError(format!(
+                "Expected {} messages. Got {}.",
+                message_count,
+                self.messages.len()
+            )));
+        }
+
+        let mut revealed_indices = self.revealed_indices;
+        revealed_indices.sort_unstable();
+        revealed_indices.dedup();
+
+        if let Some(index) = revealed_indices
+            .iter()
+            .find(|index| **index >= message_count)
+        {
+            return Err(InvalidArgumentError(format!(
+                "Revealed message index ({}) is out of bounds.",
+                index
+            )));
+        }
+
+        if let Some((index, _)) = self
+            .blinding_factors
+            .iter()
+            .find(|(index, _)| *index >= message_count || revealed_indices.contains(index))
+        {
+            return Err(InvalidArgumentError(format!(
+                "Blinding factor index ({}) is not a hidden message.",
+                index
+            )));
+        }
+
+        let hidden_indices = hidden_indices(&revealed_indices, message_count);
+
+        // 1) Randomize the signature
+        let blinding_t = rand_non_zero_fr(rng);
+
+        let signature = PsPokOfSignatureProof::new(self.signature, Some(blinding_t), rng);
+
+        // 2) Commit to the hidden messages
+        let gens = pok_gens(&hidden_indices, self.public_key, self.params);
+
+        let mut secrets: Vec<Fr> = hidden_indices
+            .iter()
+            .map(|index| self.messages[*index])
+            .collect();
+        secrets.push(blinding_t);
+
+        let commitment =
+            PedersenCommitmentCommitting::new(Some(gens.clone()), Some(secrets.clone()))
+                .map_err(|e| ProofError(format!("Could not create commitment. {:?}", e)))?
+                .finish();
+
+        // 3) Commit to the randomness
+        let mut committing = ProverCommitting::new();
+        for (index, gen) in hidden_indices.iter().zip(&gens) {
+            let blinding_factor = self
+                .blinding_factors
+                .iter()
+                .find(|(blinding_index, _)| blinding_index == index)
+                .map(|(_, blinding_factor)| *blinding_factor);
+
+            committing.commit(*gen, blinding_factor);
+        }
+        committing.commit(self.params.g_tilde, None);
+
+        Ok(PsPokCommitted {
+            signature,
+            commitment: commitment.0,
+            revealed_indices,
+            secrets,
+            committed: committing.finish(),
+        })
+    }
+
+    // Complete non-interactive proof
+    pub fn prove<R: CryptoRng + rand::RngCore>(
+        self,
+        rng: &mut R,
+    ) -> Result<PsPok, PsSignatureError> {
+        let committed = self.commit(rng)?;
+        let challenge = challenge(&committed.challenge_bytes());
+
+        committed.generate_proof(&challenge)
+    }
+}
+
+pub struct PsPokCommitted {
+    signature: PsPokOfSignatureProof,
+
+    commitment: G2,
+
+    revealed_indices: Vec<usize>,
+
+    // Hidden messages followed by t
+    secrets: Vec<Fr>,
+
+    committed: ProverCommitted<G2>,
+}
+
+impl PsPokCommitted {
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Vec<u8> {
+        challenge_bytes(
+            self.committed.challenge_bytes(),
+            &self.commitment,
+            &self.signature,
+        )
+    }
+
+    pub fn generate_proof(self, challenge: &Fr) -> Result<PsPok, PsSignatureError> {
+        let proof = self
+            .committed
+            .generate_proof(challenge, &self.secrets)
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        Ok(PsPok {
+            signature: self.signature,
+            commitment: self.commitment,
+            randomness_commitment: proof.commitment,
+            revealed_indices: self.revealed_indices,
+            responses: proof.responses,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PsPok {
+    // Randomized signature
+    pub signature: PsPokOfSignatureProof,
+
+    // Hidden messages commitment
+    pub commitment: G2,
+
+    pub randomness_commitment: G2,
+
+    // Sorted indices of the revealed messages
+    pub revealed_indices: Vec<usize>,
+
+    // Hidden message responses in index order followed by the t response
+    pub responses: Vec<Fr>,
+}
+
+impl PsPok {
+    pub fn builder<'a>(
+        signature: &'a PsSignature,
+        messages: &'a [Fr],
+        public_key: &'a PsPublicKey,
+        params: &'a PsParams,
+    ) -> PsPokBuilder<'a> {
+        PsPokBuilder {
+            signature,
+            messages,
+            revealed_indices: Vec::new(),
+            blinding_factors: Vec::new(),
+            public_key,
+            params,
+        }
+    }
+
+    // Response of the hidden message at the index
+    pub fn hidden_response(&self, index: usize) -> Option<Fr> {
+        if self.revealed_indices.contains(&index) {
+            return None;
+        }
+
+        let position = index - self.revealed_indices.iter().filter(|i| **i < index).count();
+
+        match position + 1 < self.responses.len() {
+            true => Some(self.responses[position]),
+            false => None,
+        }
+    }
+
+    pub fn challenge_bytes(&self, public_key: &PsPublicKey, params: &PsParams) -> Vec<u8> {
+        let hidden_indices = hidden_indices(&self.revealed_indices, public_key.y_cap_tilde.len());
+        let gens = pok_gens(&hidden_indices, public_key, params);
+
+        challenge_bytes(
+            self.commitment_proof().challenge_bytes(&gens),
+            &self.commitment,
+            &self.signature,
+        )
+    }
+
+    // Verify the complete non-interactive proof
+    pub fn verify(
+        &self,
+        revealed_messages: &[(usize, Fr)],
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<bool, PsSignatureError> {
+        let challenge = challenge(&self.challenge_bytes(public_key, params));
+
+        self.verify_with_challenge(revealed_messages, public_key, params, &challenge)
+    }
+
+    // Verify with a challenge shared with other proofs
+    pub fn verify_with_challenge(
+        &self,
+        revealed_messages: &[(usize, Fr)],
+        public_key: &PsPublicKey,
+        params: &PsParams,
+        challenge: &Fr,
+    ) -> Result<bool, PsSignatureError> {
+        let revealed_messages = match self.check_revealed_messages(revealed_messages, public_key) {
+            Some(revealed_messages) => revealed_messages,
+            None => return Ok(false),
+        };
+
+        // 2) Proof of knowledge of the hidden messages and t
+        let hidden_indices = hidden_indices(&self.revealed_indices, public_key.y_cap_tilde.len());
+        let gens = pok_gens(&hidden_indices, public_key, params);
+
+        if !self
+            .commitment_proof()
+            .verify(&gens, &self.commitment, challenge)
+            .map_err(|e| ProofError(format!("Could not verify proof of knowledge. {:?}", e)))?
+        {
+            return Ok(false);
+        }
+
+        // 3) Signature on the hidden and revealed messages
+        let (bases, messages): (Vec<G2>, Vec<Fr>) = revealed_messages
+            .iter()
+            .map(|(index, message)| (public_key.y_cap_tilde[*index], *message))
+            .unzip();
+
+        let mut payload_commitment = self.commitment;
+        payload_commitment.add_assign(&multi_scalar_mul_var_time(&bases, &messages));
+
+        Ok(self
+            .signature
+            .verify(public_key, params, payload_commitment))
+    }
+
+    // Same as verify_with_challenge with the fixed base tables and pairing lines of the key
+    pub fn verify_with_challenge_prepared(
+        &self,
+        revealed_messages: &[(usize, Fr)],
+        prepared_key: &PreparedPsPublicKey,
+        challenge: &Fr,
+    ) -> Result<bool, PsSignatureError> {
+        let public_key = &prepared_key.public_key;
+
+        let revealed_messages = match self.check_revealed_messages(revealed_messages, public_key) {
+            Some(revealed_messages) => revealed_messages,
+            None => return Ok(false),
+        };
+
+        // 2) Proof of knowledge of the hidden messages and t:
+        // randomness commitment == prod(Y_tilde_i ^ z_i) * g_tilde ^ z_t * commitment ^ c
+        let hidden_indices = hidden_indices(&self.revealed_indices, public_key.y_cap_tilde.len());
+
+        let mut calculated_commitment = self.commitment;
+        calculated_commitment.mul_assign(*challenge);
+        for (index, response) in hidden_indices.iter().zip(&self.responses) {
+            calculated_commitment.add_assign(&mul_y_cap_tilde(prepared_key, *index, response)?);
+        }
+        calculated_commitment
+            .add_assign(&prepared_key.mul_g_tilde(&self.responses[hidden_indices.len()]));
+
+        if calculated_commitment != self.randomness_commitment {
+            return Ok(false);
+        }
+
+        // 3) Signature on the hidden and revealed messages
+        let mut payload_commitment = self.commitment;
+        for (index, message) in &revealed_messages {
+            payload_commitment.add_assign(&mul_y_cap_tilde(prepared_key, *index, message)?);
+        }
+
+        Ok(self
+            .signature
+            .verify_prepared(prepared_key, payload_commitment))
+    }
+
+    // 1) The revealed messages are the ones of the proof, sorted by index
+    fn check_revealed_messages(
+        &self,
+        revealed_messages: &[(usize, Fr)],
+        public_key: &PsPublicKey,
+    ) -> Option<Vec<(usize, Fr)>> {
+        let message_count = public_key.y_cap_tilde.len();
+
+        let mut revealed_messages = revealed_messages.to_vec();
+        revealed_messages.sort_by_key(|(index, _)| *index);
+
+        if !revealed_messages
+            .iter()
+            .map(|(index, _)| *index)
+            .eq(self.revealed_indices.iter().cloned())
+        {
+            return None;
+        }
+
+        if self
+            .revealed_indices
+            .windows(2)
+            .any(|pair| pair[0] >= pair[1])
+            || self
+                .revealed_indices
+                .iter()
+                .any(|index| *index >= message_count)
+            || self.responses.len() != message_count - self.revealed_indices.len() + 1
+        {
+            return None;
+        }
+
+        Some(revealed_messages)
+    }
+
+    fn commitment_proof(&self) -> CommitmentProof<G2> {
+        CommitmentProof::new(self.randomness_commitment, self.responses.clone())
+    }
+}
+
+impl Serializable for PsPok {
+    // |sigma_1|sigma_2|commitment|randomness commitment|revealed count|revealed indices|response count|responses|
+    fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
+        if self.revealed_indices.len() > u8::MAX as usize || self.responses.len() > u8::MAX as usize
+        {
+            return Err(SerializationError(format!(
+                "Cannot serialize more than {} messages.",
+                u8::MAX
+            )));
+        }
+
+        let mut bytes = Vec::new();
+
+        for point in [&self.signature.sigma_1, &self.signature.sigma_2] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        for point in [&self.commitment, &self.randomness_commitment] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        bytes.push(self.revealed_indices.len() as u8);
+        for index in &self.revealed_indices {
+            if *index > u8::MAX as usize {
+                return Err(SerializationError(format!(
+                    "Invalid revealed message index ({}).",
+                    index
+                )));
+            }
+            bytes.push(*index as u8);
+        }
+
+        bytes.push(self.responses.len() as u8);
+        for response in &self.responses {
+            response.serialize(&mut bytes, true).map_err(|e| {
+                SerializationError(format!("Could not serialize response. {:?}", e))
+            })?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let pok = read_pok(&mut cursor)?;
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized signature proof of knowledge has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(pok)
+    }
+}
+
+// Reads a serialized proof from the cursor
+pub fn read_pok(cursor: &mut Cursor<&[u8]>) -> Result<PsPok, PsSignatureError> {
+    let signature = PsPokOfSignatureProof {
+        sigma_1: read_g1_point(cursor)?,
+        sigma_2: read_g1_point(cursor)?,
+    };
+
+    let commitment = read_g2_point(cursor)?;
+    let randomness_commitment = read_g2_point(cursor)?;
+
+    let revealed_count = cursor
+        .read_u8()
+        .map_err(|e| DeserializationError(format!("Could not read revealed count. {:?}", e)))?;
+
+    let mut revealed_indices = Vec::with_capacity(revealed_count as usize);
+    for _ in 0..revealed_count {
+        revealed_indices.push(
+            cursor.read_u8().map_err(|e| {
+                DeserializationError(format!("Could not read revealed index. {:?}", e))
+            })? as usize,
+        );
+    }
+
+    let response_count = cursor
+        .read_u8()
+        .map_err(|e| DeserializationError(format!("Could not read response count. {:?}", e)))?;
+
+    if response_count == 0 {
+        return Err(DeserializationError(
+            "Signature proof of knowledge must have a t response.".to_string(),
+        ));
+    }
+
+    let mut responses = Vec::with_capacity(response_count as usize);
+    for _ in 0..response_count {
+        responses.push(read_fr(cursor)?);
+    }
+
+    Ok(PsPok {
+        signature,
+        commitment,
+        randomness_commitment,
+        revealed_indices,
+        responses,
+    })
+}
+
+fn mul_y_cap_tilde(
+    prepared_key: &PreparedPsPublicKey,
+    index: usize,
+    scalar: &Fr,
+) -> Result<G2, PsSignatureError> {
+    prepared_key
+        .mul_y_cap_tilde(index, scalar)
+        .ok_or_else(|| InvalidArgumentError(format!("No Y_tilde at index {}.", index)))
+}
+
+fn hidden_indices(revealed_indices: &[usize], message_count: usize) -> Vec<usize> {
+    (0..message_count)
+        .filter(|index| !revealed_indices.contains(index))
+        .collect()
+}
+
+// [Y_tilde_i for the hidden indices, g_tilde]
+fn pok_gens(hidden_indices: &[usize], public_key: &PsPublicKey, params: &PsParams) -> Vec<G2> {
+    let mut gens: Vec<G2> = hidden_indices
+        .iter()
+        .map(|index| public_key.y_cap_tilde[*index])
+        .collect();
+    gens.push(params.g_tilde);
+
+    gens
+}
+
+// Challenge of a proof that is not combined with other proofs
+fn challenge(challenge_bytes: &[u8]) -> Fr {
+    let mut transcript = Transcript::new(TRANSCRIPT_PROTOCOL);
+    transcript.append_message(b"pok", challenge_bytes);
+
+    transcript.challenge_fr(b"challenge")
+}
+
+fn challenge_bytes(
+    mut bytes: Vec<u8>,
+    commitment: &G2,
+    signature: &PsPokOfSignatureProof,
+) -> Vec<u8> {
+    commitment.serialize(&mut bytes, false).unwrap();
+    signature.sigma_1.serialize(&mut bytes, false).unwrap();
+    signature.sigma_2.serialize(&mut bytes, false).unwrap();
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blind_signature::PsBlindSignature;
+    use crate::keys::{PsParams, PsSigningKey};
+    use crate::pok::PsPok;
+    use crate::prepared::PreparedPsPublicKey;
+    use crate::serde::Serializable;
+    use crypto_common::hash_to_fr;
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::{Fr, G1};
+    use pairing_plus::CurveProjective;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_ps_pok() {
+        let mut rng = thread_rng();
+
+        // Setup
+        let params = PsParams::generate(&mut rng);
+        let signing_key = PsSigningKey::generate(5, &params, &mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        let messages: Vec<Fr> = (0..5).map(|_| Fr::random(&mut rng)).collect();
+        let signature = PsBlindSignature::new(
+            G1::zero(),
+            &messages,
+            &signing_key,
+            &public_key,
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+
+        // 1) Reveal messages 1 and 3
+        let pok = PsPok::builder(&signature, &messages, &public_key, &params)
+            .reveal(&[3, 1])
+            .prove(&mut rng)
+            .unwrap();
+
+        assert_eq!(vec![1, 3], pok.revealed_indices);
+
+        let revealed_messages = vec![(1, messages[1]), (3, messages[3])];
+
+        assert!(pok
+            .verify(&revealed_messages, &public_key, &params)
+            .unwrap());
+
+        // Serialize de serialize
+        let pok_serialized = pok.serialize().unwrap();
+        let pok_deserialized = PsPok::deserialize(&pok_serialized).unwrap();
+
+        assert_eq!(pok, pok_deserialized);
+
+        // Bad revealed message
+        let bad_messages = vec![(1, messages[1]), (3, Fr::random(&mut rng))];
+
+        assert!(!pok.verify(&bad_messages, &public_key, &params).unwrap());
+
+        // Missing revealed message
+        assert!(!pok
+            .verify(&revealed_messages[..1], &public_key, &params)
+            .unwrap());
+
+        // Other signing key
+        let other_public_key =
+            PsSigningKey::generate(5, &params, &mut rng).derive_public_key(&params);
+
+        assert!(!pok
+            .verify(&revealed_messages, &other_public_key, &params)
+            .unwrap());
+
+        // 2) Link a hidden message response with a shared blinding factor
+        let blinding_factor = Fr::random(&mut rng);
+
+        let committed = PsPok::builder(&signature, &messages, &public_key, &params)
+            .reveal(&[1])
+            .blinding_factor(2, blinding_factor)
+            .commit(&mut rng)
+            .unwrap();
+
+        let challenge = hash_to_fr(committed.challenge_bytes());
+        let pok = committed.generate_proof(&challenge).unwrap();
+
+        let mut expected_response = challenge;
+        expected_response.mul_assign(&messages[2]);
+        expected_response.negate();
+        expected_response.add_assign(&blinding_factor);
+
+        assert_eq!(Some(expected_response), pok.hidden_response(2));
+        assert_eq!(None, pok.hidden_response(1));
+
+        assert!(pok
+            .verify_with_challenge(&[(1, messages[1])], &public_key, &params, &challenge)
+            .unwrap());
+
+        // Prepared key of the verifier
+        let prepared_key = PreparedPsPublicKey::new(&public_key, &params);
+
+        assert!(pok
+            .verify_with_challenge_prepared(&[(1, messages[1])], &prepared_key, &challenge)
+            .unwrap());
+        assert!(!pok
+            .verify_with_challenge_prepared(&[(1, messages[2])], &prepared_key, &challenge)
+            .unwrap());
+
+        let other_prepared_key = PreparedPsPublicKey::new(&other_public_key, &params);
+
+        assert!(!pok
+            .verify_with_challenge_prepared(&[(1, messages[1])], &other_prepared_key, &challenge)
+            .unwrap());
+
+        // Blinding factor of a revealed message
+        let result = PsPok::builder(&signature, &messages, &public_key, &params)
+            .reveal(&[1])
+            .blinding_factor(1, blinding_factor)
+            .commit(&mut rng);
+
+        assert!(result.is_err());
+
+        // Trailing bytes
+        let mut bad_bytes = pok_serialized.clone();
+        bad_bytes.push(0);
+
+        assert!(PsPok::deserialize(&bad_bytes).is_err());
+    }
+}
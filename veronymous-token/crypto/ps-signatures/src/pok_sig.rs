@@ -1,4 +1,5 @@
 use crate::keys::{PsParams, PsPublicKey};
+use crate::prepared::PreparedPsPublicKey;
 use crate::signature::PsSignature;
 use ff_zeroize::Field;
 use pairing_plus::bls12_381::{Bls12, Fr, G1, G2};
@@ -53,16 +54,44 @@ impl PsPokOfSignatureProof {
         params: &PsParams,
         payload_commitment: impl Into<G2>,
     ) -> bool {
-        // Pairing 1 = e(sigma_1, X_tilde + (Y_prime ^ m_0..m_i))
-        let mut pairing_1_point_2 = payload_commitment.into();
-        pairing_1_point_2.add_assign(&public_key.x_cap_tilde);
+        if self.sigma_1.is_zero() {
+            return false;
+        }
+
+        // X_tilde + (Y_prime ^ m_0..m_i)
+        let mut x_y_m = payload_commitment.into();
+        x_y_m.add_assign(&public_key.x_cap_tilde);
+
+        // e(sigma_1, x_y_m) * e(-sigma_2, g_tilde) == 1 with a single final exponentiation
+        let mut negated_sigma_2 = self.sigma_2;
+        negated_sigma_2.negate();
+
+        let product = Bls12::pairing_product(self.sigma_1, x_y_m, negated_sigma_2, params.g_tilde);
+
+        product == <Bls12 as Engine>::Fqk::one()
+    }
 
-        let pairing_1 = Bls12::pairing(self.sigma_1, pairing_1_point_2);
+    // Same as verify with the cached g_tilde lines of the verifier's key
+    pub fn verify_prepared(
+        &self,
+        prepared_key: &PreparedPsPublicKey,
+        payload_commitment: impl Into<G2>,
+    ) -> bool {
+        if self.sigma_1.is_zero() {
+            return false;
+        }
+
+        let mut x_y_m = payload_commitment.into();
+        x_y_m.add_assign(&prepared_key.public_key.x_cap_tilde);
 
-        // Pairing 2 = e(sigma_2, g_tilde)
-        let pairing_2 = Bls12::pairing(self.sigma_2, params.g_tilde);
+        let mut negated_sigma_2 = self.sigma_2;
+        negated_sigma_2.negate();
 
-        pairing_1.eq(&pairing_2)
+        prepared_key.pairing_product_is_one(
+            self.sigma_1.into_affine(),
+            x_y_m.into_affine(),
+            negated_sigma_2.into_affine(),
+        )
     }
 }
 
@@ -0,0 +1,111 @@
+/*
+* Public key prepared for a verifier checking many proofs under the same key.
+* Caches the prepared pairing lines of g_tilde and fixed base tables of g_tilde and Y_tilde_i.
+* X_tilde is added to the payload commitment of each proof, its lines cannot be cached.
+*/
+
+use crate::keys::{PsParams, PsPublicKey};
+use crypto_common::FixedBaseTable;
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Bls12, Fr, G1Affine, G2Affine, G2Prepared, G2};
+use pairing_plus::{CurveAffine, CurveProjective, Engine};
+
+#[derive(Clone, Debug)]
+pub struct PreparedPsPublicKey {
+    pub public_key: PsPublicKey,
+
+    pub params: PsParams,
+
+    g_tilde_prepared: G2Prepared,
+
+    g_tilde_table: FixedBaseTable<G2>,
+
+    y_cap_tilde_tables: Vec<FixedBaseTable<G2>>,
+}
+
+impl PreparedPsPublicKey {
+    pub fn new(public_key: &PsPublicKey, params: &PsParams) -> Self {
+        Self {
+            public_key: public_key.clone(),
+            params: params.clone(),
+            g_tilde_prepared: params.g_tilde.into_affine().prepare(),
+            g_tilde_table: FixedBaseTable::new(&params.g_tilde),
+            y_cap_tilde_tables: public_key
+                .y_cap_tilde
+                .iter()
+                .map(FixedBaseTable::new)
+                .collect(),
+        }
+    }
+
+    // g_tilde ^ scalar
+    pub fn mul_g_tilde(&self, scalar: &Fr) -> G2 {
+        self.g_tilde_table.mul(scalar)
+    }
+
+    // Y_tilde_index ^ scalar
+    pub fn mul_y_cap_tilde(&self, index: usize, scalar: &Fr) -> Option<G2> {
+        self.y_cap_tilde_tables
+            .get(index)
+            .map(|table| table.mul(scalar))
+    }
+
+    // e(point_1, point_2) * e(point_3, g_tilde) == 1
+    pub fn pairing_product_is_one(
+        &self,
+        point_1: G1Affine,
+        point_2: G2Affine,
+        point_3: G1Affine,
+    ) -> bool {
+        let product = Bls12::miller_loop(&[
+            (&point_1.prepare(), &point_2.prepare()),
+            (&point_3.prepare(), &self.g_tilde_prepared),
+        ]);
+
+        match Bls12::final_exponentiation(&product) {
+            Some(product) => product == <Bls12 as Engine>::Fqk::one(),
+            None => false,
+        }
+    }
+}
+
+// The cached values are derived from the key and the parameters
+impl PartialEq for PreparedPsPublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.public_key == other.public_key && self.params == other.params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keys::{PsParams, PsSigningKey};
+    use crate::prepared::PreparedPsPublicKey;
+    use crypto_common::rand_non_zero_fr;
+    use pairing_plus::CurveProjective;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_prepared_public_key() {
+        let mut rng = thread_rng();
+
+        let params = PsParams::generate(&mut rng);
+        let signing_key = PsSigningKey::generate(3, &params, &mut rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        let prepared = PreparedPsPublicKey::new(&public_key, &params);
+
+        let scalar = rand_non_zero_fr(&mut rng);
+
+        let mut expected = params.g_tilde;
+        expected.mul_assign(scalar);
+        assert_eq!(prepared.mul_g_tilde(&scalar), expected);
+
+        for (index, y_cap_tilde) in public_key.y_cap_tilde.iter().enumerate() {
+            let mut expected = *y_cap_tilde;
+            expected.mul_assign(scalar);
+            assert_eq!(prepared.mul_y_cap_tilde(index, &scalar), Some(expected));
+        }
+
+        assert_eq!(prepared.mul_y_cap_tilde(3, &scalar), None);
+    }
+}
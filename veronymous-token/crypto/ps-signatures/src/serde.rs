@@ -1,7 +1,8 @@
 use crate::error::PsSignatureError;
-use crate::error::PsSignatureError::DeserializationError;
+use crate::error::PsSignatureError::{DeserializationError, IdentityPointError, SubgroupError};
 use pairing_plus::bls12_381::{Fr, G1, G2};
 use pairing_plus::serdes::SerDes;
+use pairing_plus::{CurveProjective, SubgroupCheck};
 use std::io::Cursor;
 
 pub trait Serializable {
@@ -12,24 +13,38 @@ pub trait Serializable {
         Self: Sized;
 }
 
+// Rejects the identity and the points outside of the prime order subgroup
 pub fn read_g1_point(bytes: &mut Cursor<&[u8]>) -> Result<G1, PsSignatureError> {
-    match G1::deserialize(bytes, true) {
-        Ok(point) => Ok(point),
-        Err(err) => Err(DeserializationError(format!(
-            "Could not decode G1 point. {:?}",
-            err
-        ))),
-    }
+    let point = match G1::deserialize(bytes, true) {
+        Ok(point) => point,
+        Err(err) => {
+            return Err(DeserializationError(format!(
+                "Could not decode G1 point. {:?}",
+                err
+            )))
+        }
+    };
+
+    check_point(&point, "G1 point")?;
+
+    Ok(point)
 }
 
+// Rejects the identity and the points outside of the prime order subgroup
 pub fn read_g2_point(bytes: &mut Cursor<&[u8]>) -> Result<G2, PsSignatureError> {
-    match G2::deserialize(bytes, true) {
-        Ok(point) => Ok(point),
-        Err(err) => Err(DeserializationError(format!(
-            "Could not decode G2 point. {:?}",
-            err
-        ))),
-    }
+    let point = match G2::deserialize(bytes, true) {
+        Ok(point) => point,
+        Err(err) => {
+            return Err(DeserializationError(format!(
+                "Could not decode G2 point. {:?}",
+                err
+            )))
+        }
+    };
+
+    check_point(&point, "G2 point")?;
+
+    Ok(point)
 }
 
 pub fn read_fr(bytes: &mut Cursor<&[u8]>) -> Result<Fr, PsSignatureError> {
@@ -41,3 +56,21 @@ pub fn read_fr(bytes: &mut Cursor<&[u8]>) -> Result<Fr, PsSignatureError> {
         ))),
     }
 }
+
+pub fn check_point<C: CurveProjective>(point: &C, name: &str) -> Result<(), PsSignatureError>
+where
+    C::Affine: SubgroupCheck,
+{
+    if point.is_zero() {
+        return Err(IdentityPointError(format!("{} is the identity.", name)));
+    }
+
+    if !point.into_affine().in_subgroup() {
+        return Err(SubgroupError(format!(
+            "{} is not in the prime order subgroup.",
+            name
+        )));
+    }
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use crate::error::PsSignatureError;
 use crate::keys::{PsParams, PsPublicKey};
-use crypto_common::multi_scalar_mul_const_time;
+use crypto_common::multi_scalar_mul_var_time;
+use ff_zeroize::Field;
 use pairing_plus::bls12_381::Bls12;
 use pairing_plus::bls12_381::{Fr, G1};
 use pairing_plus::{CurveProjective, Engine};
@@ -21,24 +22,22 @@ impl PsSignature {
         // 1) Check the initial parameters
         Self::check_verification_params(messages, public_key)?;
 
-        // 2) sigma_1 != 1
-        // TODO: eq() might not work as expected
-        if self.sigma_1.eq(&G1::one()) {
+        // 2) sigma_1 is not the identity
+        if self.sigma_1.is_zero() {
             return Ok(false);
         }
 
         // 3) (sigma_1, x * y_0..y_i ^ m)
-        let mut x_y_m = multi_scalar_mul_const_time(&public_key.y_cap_tilde, messages);
+        let mut x_y_m = multi_scalar_mul_var_time(&public_key.y_cap_tilde, messages);
         x_y_m.add_assign(&public_key.x_cap_tilde);
 
-        // 4) Pairing 1 = e(sigma_1, y_m)
-        let pairing_1 = Bls12::pairing(self.sigma_1, x_y_m);
+        // 4) e(sigma_1, x_y_m) * e(-sigma_2, g_tilde) == 1 with a single final exponentiation
+        let mut negated_sigma_2 = self.sigma_2;
+        negated_sigma_2.negate();
 
-        // 5) Pairing 2 = e(sigma_2, g_tilde)
-        let pairing_2 = Bls12::pairing(self.sigma_2, params.g_tilde);
+        let product = Bls12::pairing_product(self.sigma_1, x_y_m, negated_sigma_2, params.g_tilde);
 
-        // 6) pairing_1 == pairing_2
-        Ok(pairing_1.eq(&pairing_2))
+        Ok(product == <Bls12 as Engine>::Fqk::one())
     }
 
     fn check_verification_params(
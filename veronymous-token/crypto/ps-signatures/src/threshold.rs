@@ -0,0 +1,1071 @@
+/*
+* Threshold ps signatures (Coconut).
+* The dealer shares x and y_i with degree (threshold - 1) polynomials between the signers.
+* The user commits to the hidden messages in commitment = g ^ r * prod(Y_j ^ m_j), derives
+* h = H(commitment) and commits to each hidden message in c_j = g ^ o_j * h ^ m_j.
+* Signer i returns sigma_2 = h ^ x_i * prod(c_j ^ y_ij) * prod(h ^ (y_ij * m_j)) for the revealed
+* messages, the user removes prod(Y_ij ^ o_j) and aggregates threshold partial signatures with
+* the lagrange coefficients into a ps signature (h, h ^ (x + sum(y_j * m_j))).
+*/
+
+use crate::error::PsSignatureError;
+use crate::error::PsSignatureError::{
+    DeserializationError, InvalidArgumentError, ProofError, SerializationError, SigningError,
+};
+use crate::keys::{PsParams, PsPublicKey, PsSigningKey};
+use crate::serde::{read_fr, read_g1_point, Serializable};
+use crate::signature::PsSignature;
+use byteorder::{BigEndian, ReadBytesExt};
+use commitments::pedersen_commitment::PedersenCommitmentCommitting;
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitting};
+use crypto_common::transcript::Transcript;
+use crypto_common::{
+    fr_from_u64, multi_scalar_mul_const_time, multi_scalar_mul_var_time, rand_non_zero_fr,
+};
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Fr, G1, G2};
+use pairing_plus::hash_to_curve::HashToCurve;
+use pairing_plus::hash_to_field::ExpandMsgXmd;
+use pairing_plus::serdes::SerDes;
+use pairing_plus::CurveProjective;
+use rand::CryptoRng;
+use std::io::Cursor;
+
+const DST: &[u8] = b"BLS12381G1_XMD:BLAKE2B_THRESHOLD_SIGNATURE_BASE:1_0_0";
+
+const TRANSCRIPT_PROTOCOL: &[u8] = b"ps_threshold_signature_request_v1";
+
+// Signing key share of the signer at index (>= 1)
+#[derive(Clone, Debug)]
+pub struct PsKeyShare {
+    pub index: u64,
+
+    pub signing_key: PsSigningKey,
+}
+
+impl PsKeyShare {
+    // Verification key of the share. The user needs its Y to unblind the partial signatures.
+    pub fn derive_public_key(&self, params: &PsParams) -> PsPublicKey {
+        self.signing_key.derive_public_key(params)
+    }
+
+    /*
+     * Verify the request proof and sign the hidden messages with the revealed ones.
+     * The public key is the aggregated key the request was made for.
+     */
+    pub fn sign_request(
+        &self,
+        request: &ThresholdSignatureRequest,
+        revealed_messages: &[(usize, Fr)],
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<PartialBlindSignature, PsSignatureError> {
+        if self.signing_key.y.len() != public_key.y_cap.len() {
+            return Err(InvalidArgumentError(format!(
+                "Key share must have {} y.",
+                public_key.y_cap.len()
+            )));
+        }
+
+        // 1) Hidden and revealed messages cover every message once
+        let mut indices: Vec<usize> = request
+            .hidden_indices
+            .iter()
+            .cloned()
+            .chain(revealed_messages.iter().map(|(index, _)| *index))
+            .collect();
+        indices.sort_unstable();
+
+        if indices != (0..public_key.y_cap.len()).collect::<Vec<usize>>() {
+            return Err(InvalidArgumentError(format!(
+                "Hidden and revealed messages must cover the {} messages.",
+                public_key.y_cap.len()
+            )));
+        }
+
+        // 2) Verify the hidden messages proof
+        if !request.verify(public_key, params)? {
+            return Err(SigningError(
+                "Hidden messages proof verification failed.".to_string(),
+            ));
+        }
+
+        // 3) sigma_2 = h ^ (x_i + sum(y_ij * m_j)) * prod(c_j ^ y_ij)
+        let h = request.base()?;
+
+        let mut exponent = self.signing_key.x;
+        for (index, message) in revealed_messages {
+            let mut y_m = self.signing_key.y[*index];
+            y_m.mul_assign(message);
+            exponent.add_assign(&y_m);
+        }
+
+        let mut bases = vec![h];
+        let mut scalars = vec![exponent];
+        for (index, message_commitment) in request
+            .hidden_indices
+            .iter()
+            .zip(&request.message_commitments)
+        {
+            bases.push(*message_commitment);
+            scalars.push(self.signing_key.y[*index]);
+        }
+
+        Ok(PartialBlindSignature {
+            index: self.index,
+            signature: PsSignature {
+                sigma_1: h,
+                sigma_2: multi_scalar_mul_const_time(&bases, &scalars),
+            },
+        })
+    }
+}
+
+impl Serializable for PsKeyShare {
+    // |index|signing key|
+    fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
+        let mut bytes = self.index.to_be_bytes().to_vec();
+        bytes.append(&mut self.signing_key.serialize()?);
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let index = read_index(&mut cursor)?;
+        let signing_key = PsSigningKey::deserialize(&bytes[cursor.position() as usize..])?;
+
+        Ok(Self { index, signing_key })
+    }
+}
+
+/*
+* Trusted dealer key generation. Any threshold of the share_count shares can sign.
+* Returns the aggregated public key and the key shares. The master key is not kept.
+*/
+pub fn generate_key_shares<R: CryptoRng + rand::RngCore>(
+    threshold: usize,
+    share_count: usize,
+    message_count: usize,
+    params: &PsParams,
+    rng: &mut R,
+) -> Result<(PsPublicKey, Vec<PsKeyShare>), PsSignatureError> {
+    if threshold == 0 || threshold > share_count {
+        return Err(InvalidArgumentError(format!(
+            "Threshold must be between 1 and {}.",
+            share_count
+        )));
+    }
+
+    // 1) Secret sharing polynomials for x and each y
+    let master_key = PsSigningKey::generate(message_count, params, rng);
+
+    let x_polynomial = random_polynomial(master_key.x, threshold, rng);
+    let y_polynomials: Vec<Vec<Fr>> = master_key
+        .y
+        .iter()
+        .map(|y| random_polynomial(*y, threshold, rng))
+        .collect();
+
+    // 2) Shares at 1..=share_count
+    let shares = (1..=share_count as u64)
+        .map(|index| {
+            let x = evaluate_polynomial(&x_polynomial, index);
+
+            let mut x_cap = params.g;
+            x_cap.mul_assign(x);
+
+            PsKeyShare {
+                index,
+                signing_key: PsSigningKey {
+                    x,
+                    y: y_polynomials
+                        .iter()
+                        .map(|polynomial| evaluate_polynomial(polynomial, index))
+                        .collect(),
+                    x_cap,
+                },
+            }
+        })
+        .collect();
+
+    Ok((master_key.derive_public_key(params), shares))
+}
+
+// Aggregated public key of threshold share public keys
+pub fn aggregate_public_keys(
+    public_keys: &[(u64, PsPublicKey)],
+) -> Result<PsPublicKey, PsSignatureError> {
+    let indices: Vec<u64> = public_keys.iter().map(|(index, _)| *index).collect();
+    let coefficients = lagrange_coefficients(&indices)?;
+
+    let message_count = public_keys[0].1.y_cap.len();
+    if public_keys
+        .iter()
+        .any(|(_, public_key)| public_key.y_cap.len() != message_count)
+    {
+        return Err(InvalidArgumentError(
+            "Public keys must have the same number of messages.".to_string(),
+        ));
+    }
+
+    let x_cap_tilde: Vec<G2> = public_keys
+        .iter()
+        .map(|(_, public_key)| public_key.x_cap_tilde)
+        .collect();
+
+    let y_cap = (0..message_count)
+        .map(|i| {
+            let bases: Vec<G1> = public_keys
+                .iter()
+                .map(|(_, public_key)| public_key.y_cap[i])
+                .collect();
+
+            multi_scalar_mul_var_time(&bases, &coefficients)
+        })
+        .collect();
+
+    let y_cap_tilde = (0..message_count)
+        .map(|i| {
+            let bases: Vec<G2> = public_keys
+                .iter()
+                .map(|(_, public_key)| public_key.y_cap_tilde[i])
+                .collect();
+
+            multi_scalar_mul_var_time(&bases, &coefficients)
+        })
+        .collect();
+
+    Ok(PsPublicKey {
+        y_cap,
+        x_cap_tilde: multi_scalar_mul_var_time(&x_cap_tilde, &coefficients),
+        y_cap_tilde,
+    })
+}
+
+// Aggregated signature of threshold unblinded partial signatures (index, signature)
+pub fn aggregate_signatures(
+    signatures: &[(u64, PsSignature)],
+) -> Result<PsSignature, PsSignatureError> {
+    let indices: Vec<u64> = signatures.iter().map(|(index, _)| *index).collect();
+    let coefficients = lagrange_coefficients(&indices)?;
+
+    let sigma_1 = signatures[0].1.sigma_1;
+    if signatures
+        .iter()
+        .any(|(_, signature)| signature.sigma_1 != sigma_1)
+    {
+        return Err(InvalidArgumentError(
+            "Partial signatures must be on the same base.".to_string(),
+        ));
+    }
+
+    let sigma_2: Vec<G1> = signatures
+        .iter()
+        .map(|(_, signature)| signature.sigma_2)
+        .collect();
+
+    Ok(PsSignature {
+        sigma_1,
+        sigma_2: multi_scalar_mul_var_time(&sigma_2, &coefficients),
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdSignatureRequest {
+    // Sorted indices of the hidden messages
+    pub hidden_indices: Vec<usize>,
+
+    // g ^ r * prod(Y_j ^ m_j)
+    pub commitment: G1,
+
+    // g ^ o_j * h ^ m_j for each hidden message
+    pub message_commitments: Vec<G1>,
+
+    pub randomness_commitment: G1,
+
+    pub message_randomness_commitments: Vec<G1>,
+
+    pub message_responses: Vec<Fr>,
+
+    pub blinding_response: Fr,
+
+    pub opening_responses: Vec<Fr>,
+}
+
+impl ThresholdSignatureRequest {
+    // Returns the request and the (index, opening) of each hidden message to unblind the signatures
+    pub fn new<R: CryptoRng + rand::RngCore>(
+        hidden_messages: &[(usize, Fr)],
+        public_key: &PsPublicKey,
+        params: &PsParams,
+        rng: &mut R,
+    ) -> Result<(Self, Vec<(usize, Fr)>), PsSignatureError> {
+        let mut hidden_messages = hidden_messages.to_vec();
+        hidden_messages.sort_by_key(|(index, _)| *index);
+
+        let hidden_indices: Vec<usize> = hidden_messages.iter().map(|(index, _)| *index).collect();
+        check_hidden_indices(&hidden_indices, public_key)?;
+
+        let messages: Vec<Fr> = hidden_messages
+            .iter()
+            .map(|(_, message)| *message)
+            .collect();
+
+        // 1) Hidden messages commitment
+        let blinding = rand_non_zero_fr(rng);
+
+        let gens = commitment_gens(&hidden_indices, public_key, params);
+
+        let mut secrets = messages.clone();
+        secrets.push(blinding);
+
+        let commitment =
+            PedersenCommitmentCommitting::new(Some(gens.clone()), Some(secrets.clone()))
+                .map_err(|e| ProofError(format!("Could not create commitment. {:?}", e)))?
+                .finish()
+                .0;
+
+        // 2) Message commitments on h = H(commitment)
+        let h = hash_to_base(&commitment)?;
+
+        let openings: Vec<Fr> = messages.iter().map(|_| rand_non_zero_fr(rng)).collect();
+
+        let message_commitments: Vec<G1> = messages
+            .iter()
+            .zip(&openings)
+            .map(|(message, opening)| {
+                multi_scalar_mul_const_time([params.g, h], [*opening, *message])
+            })
+            .collect();
+
+        // 3) Proof of knowledge with shared message blinding factors
+        let message_blinding_factors: Vec<Fr> =
+            messages.iter().map(|_| rand_non_zero_fr(rng)).collect();
+
+        let mut committing = ProverCommitting::new();
+        for (gen, blinding_factor) in gens.iter().zip(&message_blinding_factors) {
+            committing.commit(*gen, Some(*blinding_factor));
+        }
+        committing.commit(params.g, None);
+
+        let committed = committing.finish();
+
+        let message_committed: Vec<_> = message_blinding_factors
+            .iter()
+            .map(|blinding_factor| {
+                let mut committing = ProverCommitting::new();
+                committing.commit(params.g, None);
+                committing.commit(h, Some(*blinding_factor));
+
+                committing.finish()
+            })
+            .collect();
+
+        let mut challenge_bytes = committed.challenge_bytes();
+        commitment.serialize(&mut challenge_bytes, false).unwrap();
+        for (committed, message_commitment) in message_committed.iter().zip(&message_commitments) {
+            challenge_bytes.append(&mut committed.challenge_bytes());
+            message_commitment
+                .serialize(&mut challenge_bytes, false)
+                .unwrap();
+        }
+
+        let challenge = request_challenge(&challenge_bytes);
+
+        let mut proof = committed
+            .generate_proof(&challenge, &secrets)
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        let blinding_response = proof.responses.pop().unwrap();
+
+        let mut message_randomness_commitments = Vec::with_capacity(messages.len());
+        let mut opening_responses = Vec::with_capacity(messages.len());
+        for ((committed, message), opening) in
+            message_committed.into_iter().zip(&messages).zip(&openings)
+        {
+            let proof = committed
+                .generate_proof(&challenge, &[*opening, *message])
+                .map_err(|e| {
+                    ProofError(format!("Could not generate proof of knowledge. {:?}", e))
+                })?;
+
+            message_randomness_commitments.push(proof.commitment);
+            opening_responses.push(proof.responses[0]);
+        }
+
+        let request = Self {
+            hidden_indices: hidden_indices.clone(),
+            commitment,
+            message_commitments,
+            randomness_commitment: proof.commitment,
+            message_randomness_commitments,
+            message_responses: proof.responses,
+            blinding_response,
+            opening_responses,
+        };
+
+        Ok((request, hidden_indices.into_iter().zip(openings).collect()))
+    }
+
+    pub fn verify(
+        &self,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<bool, PsSignatureError> {
+        check_hidden_indices(&self.hidden_indices, public_key)?;
+
+        let hidden_count = self.hidden_indices.len();
+        if self.message_commitments.len() != hidden_count
+            || self.message_randomness_commitments.len() != hidden_count
+            || self.message_responses.len() != hidden_count
+            || self.opening_responses.len() != hidden_count
+        {
+            return Ok(false);
+        }
+
+        let h = self.base()?;
+        let gens = commitment_gens(&self.hidden_indices, public_key, params);
+        let message_gens = [params.g, h];
+
+        let mut responses = self.message_responses.clone();
+        responses.push(self.blinding_response);
+
+        let proof = CommitmentProof::new(self.randomness_commitment, responses);
+        let message_proofs: Vec<CommitmentProof<G1>> = self
+            .message_randomness_commitments
+            .iter()
+            .zip(self.opening_responses.iter().zip(&self.message_responses))
+            .map(
+                |(randomness_commitment, (opening_response, message_response))| {
+                    CommitmentProof::new(
+                        *randomness_commitment,
+                        vec![*opening_response, *message_response],
+                    )
+                },
+            )
+            .collect();
+
+        // Get the challenge
+        let mut challenge_bytes = proof.challenge_bytes(&gens);
+        self.commitment
+            .serialize(&mut challenge_bytes, false)
+            .unwrap();
+        for (message_proof, message_commitment) in
+            message_proofs.iter().zip(&self.message_commitments)
+        {
+            challenge_bytes.append(&mut message_proof.challenge_bytes(&message_gens));
+            message_commitment
+                .serialize(&mut challenge_bytes, false)
+                .unwrap();
+        }
+
+        let challenge = request_challenge(&challenge_bytes);
+
+        if !proof
+            .verify(&gens, &self.commitment, &challenge)
+            .map_err(|e| ProofError(format!("Could not verify proof of knowledge. {:?}", e)))?
+        {
+            return Ok(false);
+        }
+
+        for (message_proof, message_commitment) in
+            message_proofs.iter().zip(&self.message_commitments)
+        {
+            if !message_proof
+                .verify(&message_gens, message_commitment, &challenge)
+                .map_err(|e| ProofError(format!("Could not verify proof of knowledge. {:?}", e)))?
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // Signature base h = H(commitment)
+    pub fn base(&self) -> Result<G1, PsSignatureError> {
+        hash_to_base(&self.commitment)
+    }
+}
+
+impl Serializable for ThresholdSignatureRequest {
+    // |hidden count|hidden indices|commitment|randomness commitment|(message commitment, randomness commitment)..|
+    // |message responses|blinding response|opening responses|
+    fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
+        if self.hidden_indices.len() > u8::MAX as usize {
+            return Err(SerializationError(format!(
+                "Cannot serialize more than {} hidden messages.",
+                u8::MAX
+            )));
+        }
+
+        let mut bytes = Vec::new();
+
+        bytes.push(self.hidden_indices.len() as u8);
+        for index in &self.hidden_indices {
+            if *index > u8::MAX as usize {
+                return Err(SerializationError(format!(
+                    "Invalid hidden message index ({}).",
+                    index
+                )));
+            }
+            bytes.push(*index as u8);
+        }
+
+        let mut points = vec![&self.commitment, &self.randomness_commitment];
+        for (message_commitment, randomness_commitment) in self
+            .message_commitments
+            .iter()
+            .zip(&self.message_randomness_commitments)
+        {
+            points.push(message_commitment);
+            points.push(randomness_commitment);
+        }
+
+        for point in points {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        for response in self
+            .message_responses
+            .iter()
+            .chain([&self.blinding_response])
+            .chain(&self.opening_responses)
+        {
+            response.serialize(&mut bytes, true).map_err(|e| {
+                SerializationError(format!("Could not serialize response. {:?}", e))
+            })?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let hidden_count = cursor
+            .read_u8()
+            .map_err(|e| DeserializationError(format!("Could not read hidden count. {:?}", e)))?
+            as usize;
+
+        let mut hidden_indices = Vec::with_capacity(hidden_count);
+        for _ in 0..hidden_count {
+            hidden_indices.push(cursor.read_u8().map_err(|e| {
+                DeserializationError(format!("Could not read hidden index. {:?}", e))
+            })? as usize);
+        }
+
+        let commitment = read_g1_point(&mut cursor)?;
+        let randomness_commitment = read_g1_point(&mut cursor)?;
+
+        let mut message_commitments = Vec::with_capacity(hidden_count);
+        let mut message_randomness_commitments = Vec::with_capacity(hidden_count);
+        for _ in 0..hidden_count {
+            message_commitments.push(read_g1_point(&mut cursor)?);
+            message_randomness_commitments.push(read_g1_point(&mut cursor)?);
+        }
+
+        let message_responses = (0..hidden_count)
+            .map(|_| read_fr(&mut cursor))
+            .collect::<Result<Vec<Fr>, _>>()?;
+        let blinding_response = read_fr(&mut cursor)?;
+        let opening_responses = (0..hidden_count)
+            .map(|_| read_fr(&mut cursor))
+            .collect::<Result<Vec<Fr>, _>>()?;
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized threshold signature request has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            hidden_indices,
+            commitment,
+            message_commitments,
+            randomness_commitment,
+            message_randomness_commitments,
+            message_responses,
+            blinding_response,
+            opening_responses,
+        })
+    }
+}
+
+// Blinded signature of one signer
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialBlindSignature {
+    pub index: u64,
+
+    pub signature: PsSignature,
+}
+
+impl PartialBlindSignature {
+    // signature = (h, sigma_2 / prod(Y_ij ^ o_j)) with the share public key of the signer
+    pub fn unblind(
+        &self,
+        openings: &[(usize, Fr)],
+        share_public_key: &PsPublicKey,
+    ) -> Result<PsSignature, PsSignatureError> {
+        if let Some((index, _)) = openings
+            .iter()
+            .find(|(index, _)| *index >= share_public_key.y_cap.len())
+        {
+            return Err(InvalidArgumentError(format!(
+                "Opening index ({}) is out of bounds.",
+                index
+            )));
+        }
+
+        let bases: Vec<G1> = openings
+            .iter()
+            .map(|(index, _)| share_public_key.y_cap[*index])
+            .collect();
+        let scalars: Vec<Fr> = openings.iter().map(|(_, opening)| *opening).collect();
+
+        let mut sigma_2 = self.signature.sigma_2;
+        sigma_2.sub_assign(&multi_scalar_mul_const_time(&bases, &scalars));
+
+        Ok(PsSignature {
+            sigma_1: self.signature.sigma_1,
+            sigma_2,
+        })
+    }
+}
+
+impl Serializable for PartialBlindSignature {
+    // |index|sigma_1|sigma_2|
+    fn serialize(&self) -> Result<Vec<u8>, PsSignatureError> {
+        let mut bytes = self.index.to_be_bytes().to_vec();
+
+        for point in [&self.signature.sigma_1, &self.signature.sigma_2] {
+            point
+                .serialize(&mut bytes, true)
+                .map_err(|e| SerializationError(format!("Could not serialize point. {:?}", e)))?;
+        }
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, PsSignatureError>
+    where
+        Self: Sized,
+    {
+        let mut cursor = Cursor::new(bytes);
+
+        let index = read_index(&mut cursor)?;
+        let sigma_1 = read_g1_point(&mut cursor)?;
+        let sigma_2 = read_g1_point(&mut cursor)?;
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized partial signature has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            index,
+            signature: PsSignature { sigma_1, sigma_2 },
+        })
+    }
+}
+
+// Lagrange coefficients at 0 for the distinct non zero indices
+pub fn lagrange_coefficients(indices: &[u64]) -> Result<Vec<Fr>, PsSignatureError> {
+    if indices.is_empty() {
+        return Err(InvalidArgumentError(
+            "At least one share is required.".to_string(),
+        ));
+    }
+
+    if indices.contains(&0) {
+        return Err(InvalidArgumentError(
+            "Share index must be at least 1.".to_string(),
+        ));
+    }
+
+    let mut sorted_indices = indices.to_vec();
+    sorted_indices.sort_unstable();
+    sorted_indices.dedup();
+
+    if sorted_indices.len() != indices.len() {
+        return Err(InvalidArgumentError(
+            "Share indices must be unique.".to_string(),
+        ));
+    }
+
+    indices
+        .iter()
+        .map(|i| {
+            // prod(j / (j - i)) for j != i
+            let mut numerator = Fr::one();
+            let mut denominator = Fr::one();
+
+            for j in indices.iter().filter(|j| *j != i) {
+                numerator.mul_assign(&fr_from_u64(*j));
+
+                let mut difference = fr_from_u64(*j);
+                difference.sub_assign(&fr_from_u64(*i));
+                denominator.mul_assign(&difference);
+            }
+
+            // Indices are distinct so the denominator is not 0
+            numerator.mul_assign(&denominator.inverse().unwrap());
+
+            Ok(numerator)
+        })
+        .collect()
+}
+
+// Polynomial of the degree (threshold - 1) with the secret at 0
+fn random_polynomial<R: CryptoRng + rand::RngCore>(
+    secret: Fr,
+    threshold: usize,
+    rng: &mut R,
+) -> Vec<Fr> {
+    let mut coefficients = vec![secret];
+    coefficients.extend((1..threshold).map(|_| Fr::random(&mut *rng)));
+
+    coefficients
+}
+
+fn evaluate_polynomial(coefficients: &[Fr], x: u64) -> Fr {
+    let x = fr_from_u64(x);
+
+    coefficients
+        .iter()
+        .rev()
+        .fold(Fr::zero(), |mut result, coefficient| {
+            result.mul_assign(&x);
+            result.add_assign(coefficient);
+            result
+        })
+}
+
+fn request_challenge(challenge_bytes: &[u8]) -> Fr {
+    let mut transcript = Transcript::new(TRANSCRIPT_PROTOCOL);
+    transcript.append_message(b"request", challenge_bytes);
+
+    transcript.challenge_fr(b"challenge")
+}
+
+fn hash_to_base(commitment: &G1) -> Result<G1, PsSignatureError> {
+    let mut bytes = Vec::new();
+    commitment
+        .serialize(&mut bytes, true)
+        .map_err(|e| SerializationError(format!("Could not serialize commitment. {:?}", e)))?;
+
+    Ok(<G1 as HashToCurve<ExpandMsgXmd<blake2::Blake2b>>>::hash_to_curve(bytes, DST))
+}
+
+// Hidden indices must be sorted, unique and in the key
+fn check_hidden_indices(
+    hidden_indices: &[usize],
+    public_key: &PsPublicKey,
+) -> Result<(), PsSignatureError> {
+    if hidden_indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(InvalidArgumentError(
+            "Hidden message indices must be unique.".to_string(),
+        ));
+    }
+
+    if let Some(index) = hidden_indices
+        .iter()
+        .find(|index| **index >= public_key.y_cap.len())
+    {
+        return Err(InvalidArgumentError(format!(
+            "Hidden message index ({}) is out of bounds.",
+            index
+        )));
+    }
+
+    Ok(())
+}
+
+// [Y_j for the hidden indices, g]
+fn commitment_gens(
+    hidden_indices: &[usize],
+    public_key: &PsPublicKey,
+    params: &PsParams,
+) -> Vec<G1> {
+    let mut gens: Vec<G1> = hidden_indices
+        .iter()
+        .map(|index| public_key.y_cap[*index])
+        .collect();
+    gens.push(params.g);
+
+    gens
+}
+
+fn read_index(cursor: &mut Cursor<&[u8]>) -> Result<u64, PsSignatureError> {
+    cursor
+        .read_u64::<BigEndian>()
+        .map_err(|e| DeserializationError(format!("Could not read share index. {:?}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keys::{PsParams, PsPublicKey};
+    use crate::serde::Serializable;
+    use crate::signature::PsSignature;
+    use crate::threshold::{
+        aggregate_public_keys, aggregate_signatures, generate_key_shares, lagrange_coefficients,
+        PartialBlindSignature, PsKeyShare, ThresholdSignatureRequest,
+    };
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::Fr;
+    use rand::rngs::ThreadRng;
+    use rand::thread_rng;
+
+    // Blind issuance with the signers at the positions, returns the unblinded partial signatures
+    fn issue(
+        signers: &[usize],
+        shares: &[PsKeyShare],
+        messages: &[Fr],
+        public_key: &PsPublicKey,
+        params: &PsParams,
+        rng: &mut ThreadRng,
+    ) -> Vec<(u64, PsSignature)> {
+        let (request, openings) =
+            ThresholdSignatureRequest::new(&[(0, messages[0])], public_key, params, rng).unwrap();
+
+        let revealed_messages: Vec<(usize, Fr)> =
+            messages.iter().cloned().enumerate().skip(1).collect();
+
+        signers
+            .iter()
+            .map(|i| {
+                let partial = shares[*i]
+                    .sign_request(&request, &revealed_messages, public_key, params)
+                    .unwrap();
+
+                let signature = partial
+                    .unblind(&openings, &shares[*i].derive_public_key(params))
+                    .unwrap();
+
+                (partial.index, signature)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_threshold_signature() {
+        let mut rng = thread_rng();
+
+        // Setup: 3 of 5 signers
+        let params = PsParams::generate(&mut rng);
+        let (public_key, shares) = generate_key_shares(3, 5, 3, &params, &mut rng).unwrap();
+
+        let share_public_keys: Vec<_> = shares
+            .iter()
+            .map(|share| share.derive_public_key(&params))
+            .collect();
+
+        let messages = vec![
+            Fr::random(&mut rng),
+            Fr::random(&mut rng),
+            Fr::random(&mut rng),
+        ];
+
+        // 1) Request with the messages 0 and 2 hidden
+        let (request, openings) = ThresholdSignatureRequest::new(
+            &[(0, messages[0]), (2, messages[2])],
+            &public_key,
+            &params,
+            &mut rng,
+        )
+        .unwrap();
+
+        assert!(request.verify(&public_key, &params).unwrap());
+
+        let request_serialized = request.serialize().unwrap();
+        let request_deserialized =
+            ThresholdSignatureRequest::deserialize(&request_serialized).unwrap();
+
+        assert_eq!(request, request_deserialized);
+
+        // 2) Partial signatures of the signers 1, 3 and 4
+        let mut signatures = Vec::new();
+        for i in [0, 2, 3] {
+            let partial = shares[i]
+                .sign_request(&request, &[(1, messages[1])], &public_key, &params)
+                .unwrap();
+
+            let partial_serialized = partial.serialize().unwrap();
+            let partial = PartialBlindSignature::deserialize(&partial_serialized).unwrap();
+
+            let signature = partial.unblind(&openings, &share_public_keys[i]).unwrap();
+
+            // Partial signatures verify under the share key
+            assert!(signature
+                .verify(&messages, &share_public_keys[i], &params)
+                .unwrap());
+
+            signatures.push((partial.index, signature));
+        }
+
+        // 3) Aggregate
+        let signature = aggregate_signatures(&signatures).unwrap();
+
+        assert!(signature.verify(&messages, &public_key, &params).unwrap());
+
+        // Any threshold of the share keys aggregates to the public key
+        let aggregated_public_key = aggregate_public_keys(&[
+            (shares[1].index, share_public_keys[1].clone()),
+            (shares[2].index, share_public_keys[2].clone()),
+            (shares[4].index, share_public_keys[4].clone()),
+        ])
+        .unwrap();
+
+        assert_eq!(public_key, aggregated_public_key);
+
+        // Below the threshold
+        let signature = aggregate_signatures(&signatures[..2]).unwrap();
+
+        assert!(!signature.verify(&messages, &public_key, &params).unwrap());
+
+        // Bad proof
+        let mut bad_request = request.clone();
+        bad_request.opening_responses[0] = Fr::random(&mut rng);
+
+        assert!(!bad_request.verify(&public_key, &params).unwrap());
+        assert!(shares[0]
+            .sign_request(&bad_request, &[(1, messages[1])], &public_key, &params)
+            .is_err());
+
+        // Threshold above the number of shares
+        assert!(generate_key_shares(6, 5, 3, &params, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_threshold_share_subsets() {
+        let mut rng = thread_rng();
+
+        // 3 of 5 signers
+        let params = PsParams::generate(&mut rng);
+        let (public_key, shares) = generate_key_shares(3, 5, 2, &params, &mut rng).unwrap();
+
+        let messages = vec![Fr::random(&mut rng), Fr::random(&mut rng)];
+
+        // Any threshold or more of the signers
+        for signers in [
+            vec![0, 1, 2],
+            vec![2, 3, 4],
+            vec![0, 2, 4],
+            vec![4, 1, 3],
+            vec![0, 1, 2, 3],
+            vec![0, 1, 2, 3, 4],
+        ] {
+            let signatures = issue(&signers, &shares, &messages, &public_key, &params, &mut rng);
+            let signature = aggregate_signatures(&signatures).unwrap();
+
+            assert!(signature.verify(&messages, &public_key, &params).unwrap());
+        }
+
+        // Fewer than the threshold
+        for signers in [vec![0], vec![1, 3], vec![4, 2]] {
+            let signatures = issue(&signers, &shares, &messages, &public_key, &params, &mut rng);
+            let signature = aggregate_signatures(&signatures).unwrap();
+
+            assert!(!signature.verify(&messages, &public_key, &params).unwrap());
+
+            let share_public_keys: Vec<_> = signers
+                .iter()
+                .map(|i| (shares[*i].index, shares[*i].derive_public_key(&params)))
+                .collect();
+
+            assert_ne!(
+                public_key,
+                aggregate_public_keys(&share_public_keys).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_threshold_share_indices() {
+        let mut rng = thread_rng();
+
+        let params = PsParams::generate(&mut rng);
+        let (public_key, shares) = generate_key_shares(2, 3, 1, &params, &mut rng).unwrap();
+
+        let messages = vec![Fr::random(&mut rng)];
+        let signatures = issue(&[0, 1], &shares, &messages, &public_key, &params, &mut rng);
+
+        // Duplicate index
+        assert!(lagrange_coefficients(&[1, 2, 1]).is_err());
+        assert!(aggregate_signatures(&[signatures[0].clone(), signatures[0].clone()]).is_err());
+
+        let share_public_key = shares[0].derive_public_key(&params);
+        assert!(aggregate_public_keys(&[
+            (1, share_public_key.clone()),
+            (1, share_public_key.clone())
+        ])
+        .is_err());
+
+        // Zero index
+        assert!(lagrange_coefficients(&[0, 1]).is_err());
+        assert!(
+            aggregate_signatures(&[(0, signatures[0].1.clone()), signatures[1].clone()]).is_err()
+        );
+        assert!(aggregate_public_keys(&[(0, share_public_key)]).is_err());
+
+        // No share
+        assert!(lagrange_coefficients(&[]).is_err());
+        assert!(aggregate_signatures(&[]).is_err());
+        assert!(aggregate_public_keys(&[]).is_err());
+    }
+
+    #[test]
+    fn test_aggregated_key_blind_signature() {
+        let mut rng = thread_rng();
+
+        // The verifier only knows the share public keys of 2 of 3 signers
+        let params = PsParams::generate(&mut rng);
+        let (_, shares) = generate_key_shares(2, 3, 3, &params, &mut rng).unwrap();
+
+        let aggregated_public_key = aggregate_public_keys(&[
+            (shares[0].index, shares[0].derive_public_key(&params)),
+            (shares[2].index, shares[2].derive_public_key(&params)),
+        ])
+        .unwrap();
+
+        let messages = vec![
+            Fr::random(&mut rng),
+            Fr::random(&mut rng),
+            Fr::random(&mut rng),
+        ];
+
+        // Blind issuance by the other signers
+        let signatures = issue(
+            &[1, 2],
+            &shares,
+            &messages,
+            &aggregated_public_key,
+            &params,
+            &mut rng,
+        );
+        let signature = aggregate_signatures(&signatures).unwrap();
+
+        assert!(signature
+            .verify(&messages, &aggregated_public_key, &params)
+            .unwrap());
+
+        // Other messages
+        let mut other_messages = messages.clone();
+        other_messages[0] = Fr::random(&mut rng);
+
+        assert!(!signature
+            .verify(&other_messages, &aggregated_public_key, &params)
+            .unwrap());
+    }
+}
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ps_signatures::keys::PsParams;
+use veronymous_token_fuzz::ps_round_trip;
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Some(params) = ps_round_trip::<PsParams>(bytes) {
+        let _ = params.validate();
+    }
+});
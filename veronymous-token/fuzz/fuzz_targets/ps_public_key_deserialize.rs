@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ps_signatures::keys::PsPublicKey;
+use veronymous_token_fuzz::{issuer, ps_round_trip};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Some(public_key) = ps_round_trip::<PsPublicKey>(bytes) {
+        let _ = public_key.validate(&issuer().params);
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use ps_signatures::keys::PsSigningKey;
+use veronymous_token_fuzz::{issuer, ps_round_trip};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Some(signing_key) = ps_round_trip::<PsSigningKey>(bytes) {
+        let _ = signing_key.derive_public_key(&issuer().params);
+    }
+});
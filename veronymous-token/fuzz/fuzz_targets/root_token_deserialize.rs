@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use veronymous_token::root::RootVeronymousToken;
+use veronymous_token_fuzz::{issuer, round_trip};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Some(root_token) = round_trip::<RootVeronymousToken>(bytes) {
+        let issuer = issuer();
+
+        let _ = root_token.verify(&issuer.public_key, &issuer.params);
+    }
+});
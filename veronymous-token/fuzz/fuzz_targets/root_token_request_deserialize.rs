@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use veronymous_token::root_exchange::RootTokenRequest;
+use veronymous_token_fuzz::{issuer, round_trip};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Some(token_request) = round_trip::<RootTokenRequest>(bytes) {
+        let issuer = issuer();
+
+        let _ = token_request.verify(&issuer.public_key, &issuer.params);
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use ff_zeroize::Field;
+use libfuzzer_sys::fuzz_target;
+use pairing_plus::bls12_381::Fr;
+use veronymous_token::root_exchange::{complete_root_token, RootTokenResponse};
+use veronymous_token_fuzz::{issuer, round_trip};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Some(token_response) = round_trip::<RootTokenResponse>(bytes) {
+        let issuer = issuer();
+
+        let _ = complete_root_token(
+            &token_response,
+            &Fr::one(),
+            &Fr::one(),
+            &issuer.public_key,
+            &issuer.params,
+        );
+    }
+});
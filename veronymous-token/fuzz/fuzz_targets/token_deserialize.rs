@@ -0,0 +1,26 @@
+#![no_main]
+
+/*
+* Malformed tokens (identity or off-subgroup points, truncated proofs) must be rejected
+* with an error and never panic. Run with `cargo fuzz run token_deserialize`.
+*/
+
+use libfuzzer_sys::fuzz_target;
+use veronymous_token::token::VeronymousToken;
+use veronymous_token_fuzz::{issuer, round_trip, CONNECTION_LIMIT, DOMAIN, NOW};
+
+fuzz_target!(|bytes: &[u8]| {
+    if let Some(token) = round_trip::<VeronymousToken>(bytes) {
+        let issuer = issuer();
+
+        let _ = token.verify(
+            DOMAIN,
+            NOW,
+            CONNECTION_LIMIT,
+            None,
+            None,
+            &issuer.public_key,
+            &issuer.params,
+        );
+    }
+});
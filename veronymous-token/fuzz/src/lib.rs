@@ -0,0 +1,135 @@
+/*
+* Shared helpers of the fuzz targets.
+* Decoded values must re-encode to the exact same bytes and must not panic when verified.
+*/
+
+use ps_signatures::serde::Serializable as PsSerializable;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::OnceLock;
+use veronymous_token::issuer::TokenIssuer;
+use veronymous_token::serde::Serializable;
+
+pub const DOMAIN: &[u8] = b"fuzz_domain";
+
+pub const NOW: u64 = 1643629600;
+
+pub const CONNECTION_LIMIT: u64 = 3;
+
+// Fixed issuer the decoded values are verified against
+pub fn issuer() -> &'static TokenIssuer {
+    static ISSUER: OnceLock<TokenIssuer> = OnceLock::new();
+
+    ISSUER.get_or_init(|| {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        TokenIssuer::generate_with_attributes(2, &mut rng)
+    })
+}
+
+pub fn round_trip<T: Serializable>(bytes: &[u8]) -> Option<T> {
+    let value = T::deserialize(bytes).ok()?;
+
+    assert_eq!(value.serialize().unwrap(), bytes);
+
+    Some(value)
+}
+
+pub fn ps_round_trip<T: PsSerializable>(bytes: &[u8]) -> Option<T> {
+    let value = T::deserialize(bytes).ok()?;
+
+    assert_eq!(value.serialize().unwrap(), bytes);
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{issuer, ps_round_trip, round_trip, CONNECTION_LIMIT, DOMAIN, NOW};
+    use ff_zeroize::Field;
+    use pairing_plus::bls12_381::Fr;
+    use ps_signatures::keys::{PsParams, PsPublicKey, PsSigningKey};
+    use ps_signatures::serde::Serializable as PsSerializable;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use veronymous_token::root::RootVeronymousToken;
+    use veronymous_token::root_exchange::{
+        complete_root_token, create_root_token_request, issue_root_token, RootTokenRequest,
+        RootTokenResponse,
+    };
+    use veronymous_token::serde::Serializable;
+    use veronymous_token::token::VeronymousToken;
+
+    // Valid encodings round trip, the same bytes with a trailing or a missing byte are rejected
+    fn check_seed(seed: &[u8], round_trip: fn(&[u8]) -> bool) {
+        assert!(round_trip(seed));
+
+        let mut extended = seed.to_vec();
+        extended.push(0);
+        assert!(!round_trip(&extended));
+
+        assert!(!round_trip(&seed[..seed.len() - 1]));
+    }
+
+    #[test]
+    fn test_seeds() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let issuer = issuer();
+
+        let token_id = Fr::random(&mut rng);
+        let blinding = Fr::random(&mut rng);
+
+        let token_request =
+            create_root_token_request(&token_id, &blinding, &issuer.public_key, &issuer.params)
+                .unwrap();
+        let token_response = issue_root_token(
+            &token_request,
+            NOW + 1000,
+            &[1, 2],
+            &issuer.signing_key,
+            &issuer.public_key,
+            &issuer.params,
+            &mut rng,
+        )
+        .unwrap();
+        let root_token = complete_root_token(
+            &token_response,
+            &token_id,
+            &blinding,
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .unwrap();
+        let token = root_token
+            .derive_builder(DOMAIN, NOW, &issuer.public_key, &issuer.params)
+            .connection(1, CONNECTION_LIMIT)
+            .disclose(&[0])
+            .derive(&mut rng)
+            .unwrap();
+
+        check_seed(&token_request.serialize().unwrap(), |bytes| {
+            round_trip::<RootTokenRequest>(bytes).is_some()
+        });
+        check_seed(&token_response.serialize().unwrap(), |bytes| {
+            round_trip::<RootTokenResponse>(bytes).is_some()
+        });
+        check_seed(&root_token.serialize().unwrap(), |bytes| {
+            round_trip::<RootVeronymousToken>(bytes).is_some()
+        });
+        check_seed(&token.serialize().unwrap(), |bytes| {
+            round_trip::<VeronymousToken>(bytes).is_some()
+        });
+        check_seed(
+            &PsSerializable::serialize(&issuer.params).unwrap(),
+            |bytes| ps_round_trip::<PsParams>(bytes).is_some(),
+        );
+        check_seed(
+            &PsSerializable::serialize(&issuer.public_key).unwrap(),
+            |bytes| ps_round_trip::<PsPublicKey>(bytes).is_some(),
+        );
+        check_seed(
+            &PsSerializable::serialize(&issuer.signing_key).unwrap(),
+            |bytes| ps_round_trip::<PsSigningKey>(bytes).is_some(),
+        );
+    }
+}
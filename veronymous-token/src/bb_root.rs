@@ -0,0 +1,630 @@
+/*
+* Root tokens signed with a bb signature on a committed token id.
+* Alternative to the ps root tokens with a single signed message and smaller proofs.
+* No expiry, attributes or revocation: the root token is valid for the lifetime of the key.
+* The issuer only sees a commitment to the token id and the derived tokens only carry a
+* randomized proof of the signature: the issuer cannot link them to the root token it issued.
+*/
+
+use crate::error::VeronymousTokenError;
+use crate::error::VeronymousTokenError::{
+    DeserializationError, ProofError, SerializationError, SigningError, VerificationError,
+};
+use crate::serde::Serializable;
+use crate::token::{
+    compute_commitment_generator, compute_serial_number_generator, ConnectionIndexCommitted,
+    ProofConnectionIndex, ProofSerialNumber,
+};
+use crate::utils::{
+    read_fr, read_g1_point, read_g2_point, read_range_proof, write_element, write_range_proof,
+};
+use crate::{RootTokenId, SerialNumber, TokenBlinding};
+use bb_signatures::blind_signature::{BbBlindSignature, BbBlindSignatureRequest};
+use bb_signatures::keys::{BbParams, BbPublicKey, BbSigningKey};
+use bb_signatures::pok_blind_sig::{BbPokOfBlindSignatureCommitted, BbPokOfBlindSignatureProof};
+use bb_signatures::serde::Serializable as BbSerializable;
+use crypto_common::transcript::Transcript;
+use crypto_common::{fr_from_u64, rand_non_zero_fr};
+use pairing_plus::bls12_381::Fr;
+use rand::CryptoRng;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+const SERIALIZED_ROOT_TOKEN_SIZE: usize = 144;
+
+// Size of the token without the range proofs
+const SERIALIZED_TOKEN_BASE_SIZE: usize = 720;
+
+const SERIALIZED_POK_SIZE: usize = 368;
+
+// Versioned identifier of the bb token proof transcript
+pub const BB_TOKEN_PROTOCOL: &[u8] = b"veronymous_bb_token_v2";
+
+// Blind signature request on the token id
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbRootTokenRequest {
+    pub request: BbBlindSignatureRequest,
+}
+
+impl Serializable for BbRootTokenRequest {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        self.request
+            .serialize()
+            .map_err(|e| SerializationError(format!("Could not serialize token request. {:?}", e)))
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError> {
+        let request = BbBlindSignatureRequest::deserialize(bytes).map_err(|e| {
+            DeserializationError(format!("Could not deserialize token request. {:?}", e))
+        })?;
+
+        Ok(Self { request })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbRootTokenResponse {
+    pub signature: BbBlindSignature,
+}
+
+impl Serializable for BbRootTokenResponse {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        self.signature
+            .serialize()
+            .map_err(|e| SerializationError(format!("Could not serialize token response. {:?}", e)))
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError> {
+        let signature = BbBlindSignature::deserialize(bytes).map_err(|e| {
+            DeserializationError(format!("Could not deserialize token response. {:?}", e))
+        })?;
+
+        Ok(Self { signature })
+    }
+}
+
+// Commit to the token id with a proof of knowledge
+pub fn create_bb_root_token_request<R: CryptoRng + rand::RngCore>(
+    token_id: &RootTokenId,
+    blinding: &TokenBlinding,
+    params: &BbParams,
+    rng: &mut R,
+) -> Result<BbRootTokenRequest, VeronymousTokenError> {
+    let request = BbBlindSignatureRequest::new(
+        token_id,
+        blinding,
+        &compute_commitment_generator(),
+        params,
+        rng,
+    )
+    .map_err(|e| ProofError(format!("Could not create token request. {:?}", e)))?;
+
+    Ok(BbRootTokenRequest { request })
+}
+
+pub fn issue_bb_root_token<R: CryptoRng + rand::RngCore>(
+    token_request: &BbRootTokenRequest,
+    signing_key: &BbSigningKey,
+    params: &BbParams,
+    rng: &mut R,
+) -> Result<BbRootTokenResponse, VeronymousTokenError> {
+    let signature = BbBlindSignature::sign_request(
+        &token_request.request,
+        &compute_commitment_generator(),
+        signing_key,
+        params,
+        rng,
+    )
+    .map_err(|e| SigningError(format!("Could not sign the root token. {:?}", e)))?;
+
+    Ok(BbRootTokenResponse { signature })
+}
+
+pub fn complete_bb_root_token(
+    token_response: &BbRootTokenResponse,
+    token_id: &RootTokenId,
+    blinding: &TokenBlinding,
+    public_key: &BbPublicKey,
+    params: &BbParams,
+) -> Result<BbRootVeronymousToken, VeronymousTokenError> {
+    let root_token = BbRootVeronymousToken {
+        token_id: *token_id,
+        blinding: *blinding,
+        signature: token_response.signature.clone(),
+    };
+
+    if !root_token.verify(public_key, params) {
+        return Err(VeronymousTokenError::InvalidToken(
+            "Signature is invalid".to_string(),
+        ));
+    }
+
+    Ok(root_token)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbRootVeronymousToken {
+    pub token_id: RootTokenId,
+
+    pub blinding: TokenBlinding,
+
+    pub signature: BbBlindSignature,
+}
+
+impl BbRootVeronymousToken {
+    pub fn verify(&self, public_key: &BbPublicKey, params: &BbParams) -> bool {
+        self.signature.verify(
+            &self.token_id,
+            &self.blinding,
+            &compute_commitment_generator(),
+            public_key,
+            params,
+        )
+    }
+
+    // Derive the veronymous token for the given connection index (0 <= index < limit)
+    #[allow(clippy::too_many_arguments)]
+    pub fn derive_token<R: CryptoRng + rand::RngCore>(
+        &self,
+        domain: &[u8],
+        timestamp: u64,
+        connection_index: u64,
+        connection_limit: u64,
+        public_key: &BbPublicKey,
+        params: &BbParams,
+        rng: &mut R,
+    ) -> Result<BbVeronymousToken, VeronymousTokenError> {
+        let token_id_blinding_factor = rand_non_zero_fr(rng);
+        let index_blinding_factor = rand_non_zero_fr(rng);
+        let commitment_generator = compute_commitment_generator();
+
+        // 1) Randomized signature proof
+        let root_committed = BbPokOfBlindSignatureCommitted::new(
+            &self.signature,
+            &self.token_id,
+            &self.blinding,
+            Some(token_id_blinding_factor),
+            &commitment_generator,
+            params,
+            rng,
+        )
+        .map_err(|e| ProofError(format!("Could not create signature proof. {:?}", e)))?;
+
+        // 2) Hidden connection index with the proof of 0 <= index < limit
+        let connection_index_committed = ConnectionIndexCommitted::new(
+            connection_index,
+            connection_limit,
+            &index_blinding_factor,
+            &params.g1,
+            &commitment_generator,
+            rng,
+        )?;
+
+        // 3) Derive the serial number
+        let serial_number_generator = compute_serial_number_generator(domain, timestamp);
+        let serial_number = ProofSerialNumber::new(
+            &self.token_id,
+            &fr_from_u64(connection_index),
+            &token_id_blinding_factor,
+            &index_blinding_factor,
+            &serial_number_generator,
+        )?;
+
+        // 4) Create the challenge
+        let mut transcript = bb_token_transcript(domain, timestamp, connection_limit, public_key)?;
+        transcript.append_message(b"root", &root_committed.challenge_bytes());
+        transcript.append_message(
+            b"serial_number",
+            &serial_number.challenge_bytes(&serial_number_generator)?,
+        );
+        transcript.append_message(
+            b"connection_index",
+            &connection_index_committed.challenge_bytes()?,
+        );
+
+        let challenge = transcript.challenge_fr(b"challenge");
+
+        // 5) Generate the proofs
+        let root = root_committed
+            .generate_proof(&challenge)
+            .map_err(|e| ProofError(format!("Could not generate signature proof. {:?}", e)))?;
+
+        let (connection_index, connection_index_response) =
+            connection_index_committed.generate_proof(&challenge)?;
+
+        Ok(BbVeronymousToken {
+            root,
+            serial_number,
+            connection_index,
+            connection_index_response,
+        })
+    }
+}
+
+impl Serializable for BbRootVeronymousToken {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_ROOT_TOKEN_SIZE);
+
+        write_element(&self.token_id, true, &mut bytes)?;
+        write_element(&self.blinding, true, &mut bytes)?;
+        write_element(&self.signature.signature, true, &mut bytes)?;
+        write_element(&self.signature.e, true, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
+    where
+        Self: Sized,
+    {
+        if bytes.len() != SERIALIZED_ROOT_TOKEN_SIZE {
+            return Err(DeserializationError(format!(
+                "Serialized root token must have {} bytes.",
+                SERIALIZED_ROOT_TOKEN_SIZE
+            )));
+        }
+
+        let mut cursor = Cursor::new(bytes);
+
+        Ok(Self {
+            token_id: read_fr(&mut cursor)?,
+            blinding: read_fr(&mut cursor)?,
+            signature: BbBlindSignature {
+                signature: read_g1_point(&mut cursor)?,
+                e: read_fr(&mut cursor)?,
+            },
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BbVeronymousToken {
+    // Randomized root token signature. Its message response links the serial number
+    pub root: BbPokOfBlindSignatureProof,
+
+    pub serial_number: ProofSerialNumber,
+
+    pub connection_index: ProofConnectionIndex,
+
+    // Links serial number and connection index together
+    pub connection_index_response: Fr,
+}
+
+impl BbVeronymousToken {
+    pub fn verify(
+        &self,
+        domain: &[u8],
+        timestamp: u64,
+        connection_limit: u64,
+        public_key: &BbPublicKey,
+        params: &BbParams,
+    ) -> Result<bool, VeronymousTokenError> {
+        if connection_limit == 0 {
+            return Err(VeronymousTokenError::InvalidArgumentError(
+                "Connection limit must be at least 1.".to_string(),
+            ));
+        }
+
+        let serial_number_generator = compute_serial_number_generator(domain, timestamp);
+        let commitment_generator = compute_commitment_generator();
+
+        // Get the challenge
+        let mut transcript = bb_token_transcript(domain, timestamp, connection_limit, public_key)?;
+        transcript.append_message(
+            b"root",
+            &self.root.challenge_bytes(&commitment_generator, params),
+        );
+        transcript.append_message(
+            b"serial_number",
+            &self
+                .serial_number
+                .challenge_bytes(&serial_number_generator)?,
+        );
+        transcript.append_message(
+            b"connection_index",
+            &self
+                .connection_index
+                .challenge_bytes(&params.g1, &commitment_generator)?,
+        );
+
+        let challenge = transcript.challenge_fr(b"challenge");
+
+        // Verify the root token signature
+        if !self
+            .root
+            .verify(&commitment_generator, public_key, params, &challenge)
+            .map_err(|e| VerificationError(format!("Could not verify signature pok. {:?}", e)))?
+        {
+            return Ok(false);
+        }
+
+        // Verify serial number: generator = serial_number ^ (token_id + connection_index)
+        if !self.serial_number.verify(
+            &self.root.message_response,
+            &self.connection_index_response,
+            &serial_number_generator,
+            &challenge,
+        )? {
+            return Ok(false);
+        }
+
+        // Verify the connection index commitment and connection index < connection limit
+        self.connection_index.verify(
+            &self.connection_index_response,
+            connection_limit,
+            &params.g1,
+            &commitment_generator,
+            &challenge,
+        )
+    }
+
+    pub fn serial_number(&self) -> Result<SerialNumber, VeronymousTokenError> {
+        let bytes = self.serial_number.serial_number_bytes()?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(&bytes);
+
+        Ok(hasher.finalize().into())
+    }
+}
+
+impl Serializable for BbVeronymousToken {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = Vec::with_capacity(SERIALIZED_TOKEN_BASE_SIZE);
+
+        bytes.append(&mut self.root.serialize().map_err(|e| {
+            SerializationError(format!("Could not serialize signature pok. {:?}", e))
+        })?);
+
+        write_element(&self.serial_number.serial_number, true, &mut bytes)?;
+        write_element(&self.serial_number.randomness_commitment, true, &mut bytes)?;
+
+        write_element(&self.connection_index.commitment, true, &mut bytes)?;
+        write_element(
+            &self.connection_index.randomness_commitment,
+            true,
+            &mut bytes,
+        )?;
+        write_element(&self.connection_index.blinding_response, true, &mut bytes)?;
+        write_element(&self.connection_index_response, true, &mut bytes)?;
+
+        write_range_proof(&self.connection_index.lower_bound, &mut bytes)?;
+        write_range_proof(&self.connection_index.upper_bound, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
+    where
+        Self: Sized,
+    {
+        if bytes.len() < SERIALIZED_TOKEN_BASE_SIZE {
+            return Err(DeserializationError(format!(
+                "Serialized token must have at least {} bytes",
+                SERIALIZED_TOKEN_BASE_SIZE
+            )));
+        }
+
+        let root = BbPokOfBlindSignatureProof::deserialize(&bytes[..SERIALIZED_POK_SIZE]).map_err(
+            |e| DeserializationError(format!("Could not deserialize signature pok. {:?}", e)),
+        )?;
+
+        let mut cursor = Cursor::new(bytes);
+        cursor.set_position(SERIALIZED_POK_SIZE as u64);
+
+        let serial_number = ProofSerialNumber {
+            serial_number: read_g2_point(&mut cursor)?,
+            randomness_commitment: read_g2_point(&mut cursor)?,
+        };
+
+        let commitment = read_g1_point(&mut cursor)?;
+        let randomness_commitment = read_g1_point(&mut cursor)?;
+        let blinding_response = read_fr(&mut cursor)?;
+        let connection_index_response = read_fr(&mut cursor)?;
+
+        let connection_index = ProofConnectionIndex {
+            commitment,
+            randomness_commitment,
+            blinding_response,
+            lower_bound: read_range_proof(&mut cursor)?,
+            upper_bound: read_range_proof(&mut cursor)?,
+        };
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized token has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            root,
+            serial_number,
+            connection_index,
+            connection_index_response,
+        })
+    }
+}
+
+fn bb_token_transcript(
+    domain: &[u8],
+    timestamp: u64,
+    connection_limit: u64,
+    public_key: &BbPublicKey,
+) -> Result<Transcript, VeronymousTokenError> {
+    let public_key_bytes = public_key
+        .serialize()
+        .map_err(|e| SerializationError(format!("Could not serialize public key. {:?}", e)))?;
+
+    let mut transcript = Transcript::new(BB_TOKEN_PROTOCOL);
+    transcript.append_message(b"domain", domain);
+    transcript.append_u64(b"timestamp", timestamp);
+    transcript.append_u64(b"connection_limit", connection_limit);
+    transcript.append_message(b"issuer_public_key", &public_key_bytes);
+
+    Ok(transcript)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bb_root::{
+        complete_bb_root_token, create_bb_root_token_request, issue_bb_root_token,
+        BbRootTokenRequest, BbRootTokenResponse, BbRootVeronymousToken, BbVeronymousToken,
+    };
+    use crate::issuer::BbTokenIssuer;
+    use crate::serde::Serializable;
+    use bb_signatures::keys::BbSigningKey;
+    use crypto_common::rand_non_zero_fr;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_bb_root_token() {
+        let mut rng = thread_rng();
+
+        let issuer = BbTokenIssuer::generate(&mut rng);
+        let (public_key, params) = (issuer.public_key, issuer.params);
+
+        // 1) Request a root token on a hidden token id
+        let token_id = rand_non_zero_fr(&mut rng);
+        let blinding = rand_non_zero_fr(&mut rng);
+
+        let request =
+            create_bb_root_token_request(&token_id, &blinding, &params, &mut rng).unwrap();
+        let request = BbRootTokenRequest::deserialize(&request.serialize().unwrap()).unwrap();
+
+        // 2) Issue
+        let response =
+            issue_bb_root_token(&request, &issuer.signing_key, &params, &mut rng).unwrap();
+        let response = BbRootTokenResponse::deserialize(&response.serialize().unwrap()).unwrap();
+
+        // 3) Complete
+        let root_token =
+            complete_bb_root_token(&response, &token_id, &blinding, &public_key, &params).unwrap();
+        assert!(root_token.verify(&public_key, &params));
+
+        let root_token_deserialized =
+            BbRootVeronymousToken::deserialize(&root_token.serialize().unwrap()).unwrap();
+        assert_eq!(root_token, root_token_deserialized);
+
+        // Wrong token id
+        let other_token_id = rand_non_zero_fr(&mut rng);
+        assert!(complete_bb_root_token(
+            &response,
+            &other_token_id,
+            &blinding,
+            &public_key,
+            &params
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_bb_veronymous_token() {
+        let mut rng = thread_rng();
+
+        let issuer = BbTokenIssuer::generate(&mut rng);
+        let (public_key, params) = (issuer.public_key, issuer.params);
+
+        let token_id = rand_non_zero_fr(&mut rng);
+        let blinding = rand_non_zero_fr(&mut rng);
+
+        let request =
+            create_bb_root_token_request(&token_id, &blinding, &params, &mut rng).unwrap();
+        let response =
+            issue_bb_root_token(&request, &issuer.signing_key, &params, &mut rng).unwrap();
+        let root_token =
+            complete_bb_root_token(&response, &token_id, &blinding, &public_key, &params).unwrap();
+
+        let domain = "test".as_bytes();
+        let now = 1643629600u64;
+        let connection_limit = 3u64;
+
+        let token_1 = root_token
+            .derive_token(
+                domain,
+                now,
+                0,
+                connection_limit,
+                &public_key,
+                &params,
+                &mut rng,
+            )
+            .unwrap();
+        let token_2 = root_token
+            .derive_token(
+                domain,
+                now,
+                0,
+                connection_limit,
+                &public_key,
+                &params,
+                &mut rng,
+            )
+            .unwrap();
+        let token_3 = root_token
+            .derive_token(
+                domain,
+                now,
+                2,
+                connection_limit,
+                &public_key,
+                &params,
+                &mut rng,
+            )
+            .unwrap();
+
+        // Same connection index gives the same serial number
+        assert_eq!(
+            token_1.serial_number().unwrap(),
+            token_2.serial_number().unwrap()
+        );
+        assert_ne!(
+            token_1.serial_number().unwrap(),
+            token_3.serial_number().unwrap()
+        );
+
+        // The signature proofs are randomized
+        assert_ne!(token_1.root.sigma_prime, token_2.root.sigma_prime);
+        assert_ne!(token_1.root.d, token_2.root.d);
+
+        let token_deserialized =
+            BbVeronymousToken::deserialize(&token_3.serialize().unwrap()).unwrap();
+        assert_eq!(token_3, token_deserialized);
+
+        assert!(token_1
+            .verify(domain, now, connection_limit, &public_key, &params)
+            .unwrap());
+        assert!(token_deserialized
+            .verify(domain, now, connection_limit, &public_key, &params)
+            .unwrap());
+
+        // Index above a lower connection limit
+        assert!(!token_3
+            .verify(domain, now, 2, &public_key, &params)
+            .unwrap());
+
+        // Other epoch
+        assert!(!token_1
+            .verify(domain, now + 1, connection_limit, &public_key, &params)
+            .unwrap());
+
+        // Other issuer
+        let other_public_key = BbSigningKey::generate(&mut rng).derive_public_key(&params);
+
+        assert!(!token_1
+            .verify(domain, now, connection_limit, &other_public_key, &params)
+            .unwrap());
+
+        // Connection index must be lower than the limit
+        assert!(root_token
+            .derive_token(
+                domain,
+                now,
+                connection_limit,
+                connection_limit,
+                &public_key,
+                &params,
+                &mut rng
+            )
+            .is_err());
+    }
+}
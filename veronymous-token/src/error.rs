@@ -20,6 +20,18 @@ pub enum VeronymousTokenError {
     #[error("Deserialization error. {0}")]
     DeserializationError(String),
 
+    #[error("Identity point. {0}")]
+    IdentityPointError(String),
+
+    #[error("Point not in the prime order subgroup. {0}")]
+    SubgroupError(String),
+
     #[error("Serialization error. {0}")]
     SerializationError(String),
+
+    #[error("Quota exceeded. {0}")]
+    QuotaExceeded(String),
+
+    #[error("DB error. {0}")]
+    DBError(String),
 }
@@ -0,0 +1,43 @@
+use crate::error::VeronymousTokenError;
+use crate::issuance::IssuanceQuotaDB;
+use std::collections::HashMap;
+
+// Quota counts of a single issuer instance
+#[derive(Clone, Debug, Default)]
+pub struct MemoryIssuanceQuotaDB {
+    // entry -> (count, expires at)
+    entries: HashMap<String, (u64, u64)>,
+}
+
+impl MemoryIssuanceQuotaDB {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IssuanceQuotaDB for MemoryIssuanceQuotaDB {
+    fn try_increment(
+        &mut self,
+        account_id: &str,
+        key_epoch: u64,
+        quota: u64,
+        expires_at: u64,
+    ) -> Result<bool, VeronymousTokenError> {
+        // Drop the counts of the past key epochs
+        self.entries
+            .retain(|_, (_, entry_expires_at)| *entry_expires_at > key_epoch);
+
+        let (count, _) = self
+            .entries
+            .entry(Self::create_quota_entry(account_id, key_epoch))
+            .or_insert((0, expires_at));
+
+        if *count >= quota {
+            return Ok(false);
+        }
+
+        *count += 1;
+
+        Ok(true)
+    }
+}
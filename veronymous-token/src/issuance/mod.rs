@@ -0,0 +1,198 @@
+/*
+* Issuance policies around the root token issuance.
+* Root tokens are unlinkable, the issuer has to bound the root tokens of an account
+* before signing them. Quotas are counted per key epoch, they reset with the issuer key.
+*/
+
+pub mod memory;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+use crate::error::VeronymousTokenError;
+use crate::issuer::TokenIssuer;
+use crate::root_exchange::{issue_root_token, RootTokenRequest, RootTokenResponse};
+use crate::token::{get_current_epoch, get_next_epoch};
+use rand::CryptoRng;
+
+// Decides if an account can get one more root token
+pub trait IssuancePolicy {
+    fn admit(&mut self, account_id: &str, now: u64) -> Result<(), VeronymousTokenError>;
+}
+
+// Storage of the issued root token counts
+pub trait IssuanceQuotaDB {
+    fn create_quota_entry(account_id: &str, key_epoch: u64) -> String {
+        format!("quota:{}:{}", key_epoch, account_id)
+    }
+
+    // Counts one root token for the account if it is below the quota.
+    // The count expires at the end of the key epoch.
+    fn try_increment(
+        &mut self,
+        account_id: &str,
+        key_epoch: u64,
+        quota: u64,
+        expires_at: u64,
+    ) -> Result<bool, VeronymousTokenError>;
+}
+
+// At most `tokens_per_epoch` root tokens per account and key epoch
+pub struct QuotaIssuancePolicy<DB: IssuanceQuotaDB> {
+    pub tokens_per_epoch: u64,
+
+    pub key_lifetime: u64,
+
+    db: DB,
+}
+
+impl<DB: IssuanceQuotaDB> QuotaIssuancePolicy<DB> {
+    pub fn new(db: DB, tokens_per_epoch: u64, key_lifetime: u64) -> Self {
+        Self {
+            tokens_per_epoch,
+            key_lifetime,
+            db,
+        }
+    }
+}
+
+impl<DB: IssuanceQuotaDB> IssuancePolicy for QuotaIssuancePolicy<DB> {
+    fn admit(&mut self, account_id: &str, now: u64) -> Result<(), VeronymousTokenError> {
+        let key_epoch = get_current_epoch(now, self.key_lifetime, 0);
+        let next_key_epoch = get_next_epoch(now, self.key_lifetime);
+
+        if !self
+            .db
+            .try_increment(account_id, key_epoch, self.tokens_per_epoch, next_key_epoch)?
+        {
+            return Err(VeronymousTokenError::QuotaExceeded(format!(
+                "Account received its {} root tokens for the key epoch {}. Next key epoch at {}.",
+                self.tokens_per_epoch, key_epoch, next_key_epoch
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/*
+* Issue a root token if the policy admits the account.
+* The request is verified and signed first, invalid requests do not count against the quota.
+* The signature is dropped if the account is not admitted.
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn issue_root_token_with_policy<P: IssuancePolicy, R: CryptoRng + rand::RngCore>(
+    policy: &mut P,
+    account_id: &str,
+    now: u64,
+    token_request: &RootTokenRequest,
+    expiry: u64,
+    attributes: &[u64],
+    issuer: &TokenIssuer,
+    rng: &mut R,
+) -> Result<RootTokenResponse, VeronymousTokenError> {
+    // 1) Issue the root token
+    let token_response = issue_root_token(
+        token_request,
+        expiry,
+        attributes,
+        &issuer.signing_key,
+        &issuer.public_key,
+        &issuer.params,
+        rng,
+    )?;
+
+    // 2) Count it against the account
+    policy.admit(account_id, now)?;
+
+    Ok(token_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::VeronymousTokenError;
+    use crate::issuance::memory::MemoryIssuanceQuotaDB;
+    use crate::issuance::{issue_root_token_with_policy, QuotaIssuancePolicy};
+    use crate::issuer::TokenIssuer;
+    use crate::root_exchange::create_root_token_request;
+    use crypto_common::rand_non_zero_fr;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_quota_issuance_policy() {
+        let mut rng = thread_rng();
+
+        let issuer = TokenIssuer::generate(&mut rng);
+
+        let key_lifetime = 30 * 24 * 60 * 60;
+        let now = 1643629600u64;
+        let key_epoch = now - now % key_lifetime;
+        let expiry = key_epoch + 2 * key_lifetime;
+
+        let mut policy = QuotaIssuancePolicy::new(MemoryIssuanceQuotaDB::new(), 2, key_lifetime);
+
+        let token_request = create_root_token_request(
+            &rand_non_zero_fr(&mut rng),
+            &rand_non_zero_fr(&mut rng),
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .unwrap();
+
+        let mut issue = |account_id: &str, now: u64| {
+            issue_root_token_with_policy(
+                &mut policy,
+                account_id,
+                now,
+                &token_request,
+                expiry,
+                &[],
+                &issuer,
+                &mut rng,
+            )
+        };
+
+        // Quota of 2 root tokens
+        assert!(issue("account_1", now).is_ok());
+        assert!(issue("account_1", now + 1).is_ok());
+        assert!(matches!(
+            issue("account_1", now + 2),
+            Err(VeronymousTokenError::QuotaExceeded(_))
+        ));
+
+        // Other account
+        assert!(issue("account_2", now).is_ok());
+
+        // Next key epoch
+        assert!(issue("account_1", key_epoch + key_lifetime).is_ok());
+
+        // Invalid requests do not count
+        let mut other_policy =
+            QuotaIssuancePolicy::new(MemoryIssuanceQuotaDB::new(), 1, key_lifetime);
+        let other_issuer = TokenIssuer::generate(&mut rng);
+
+        assert!(matches!(
+            issue_root_token_with_policy(
+                &mut other_policy,
+                "account_1",
+                now,
+                &token_request,
+                expiry,
+                &[],
+                &other_issuer,
+                &mut rng,
+            ),
+            Err(VeronymousTokenError::VerificationError(_))
+        ));
+        assert!(issue_root_token_with_policy(
+            &mut other_policy,
+            "account_1",
+            now,
+            &token_request,
+            expiry,
+            &[],
+            &issuer,
+            &mut rng,
+        )
+        .is_ok());
+    }
+}
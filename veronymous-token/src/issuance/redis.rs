@@ -0,0 +1,67 @@
+use crate::error::VeronymousTokenError;
+use crate::issuance::IssuanceQuotaDB;
+use redis::{Connection, Script};
+
+// Increment and expiry in a single step, shared by the issuer instances
+const TRY_INCREMENT_SCRIPT: &str = r"
+local count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('EXPIREAT', KEYS[1], ARGV[2])
+end
+if count > tonumber(ARGV[1]) then
+    redis.call('DECR', KEYS[1])
+    return 0
+end
+return 1
+";
+
+pub struct RedisIssuanceQuotaDB {
+    connection: Connection,
+
+    script: Script,
+}
+
+impl RedisIssuanceQuotaDB {
+    pub fn create(address: &str) -> Result<Self, VeronymousTokenError> {
+        let client = redis::Client::open(address).map_err(|err| {
+            VeronymousTokenError::DBError(format!("Could not connect to redis. {:?}", err))
+        })?;
+
+        let connection = client.get_connection().map_err(|err| {
+            VeronymousTokenError::DBError(format!("Could not connect to redis. {:?}", err))
+        })?;
+
+        Ok(Self::new(connection))
+    }
+
+    pub fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            script: Script::new(TRY_INCREMENT_SCRIPT),
+        }
+    }
+}
+
+impl IssuanceQuotaDB for RedisIssuanceQuotaDB {
+    fn try_increment(
+        &mut self,
+        account_id: &str,
+        key_epoch: u64,
+        quota: u64,
+        expires_at: u64,
+    ) -> Result<bool, VeronymousTokenError> {
+        let quota_entry = Self::create_quota_entry(account_id, key_epoch);
+
+        let admitted: bool = self
+            .script
+            .key(&quota_entry)
+            .arg(quota)
+            .arg(expires_at)
+            .invoke(&mut self.connection)
+            .map_err(|e| {
+                VeronymousTokenError::DBError(format!("Could not update the quota. {:?}", e))
+            })?;
+
+        Ok(admitted)
+    }
+}
@@ -1,4 +1,7 @@
-use ps_signatures::keys::{PsParams, PsPublicKey, PsSigningKey};
+use crate::error::VeronymousTokenError;
+use bb_signatures::keys::{BbParams, BbPublicKey, BbSigningKey};
+use ps_signatures::keys::{PsParams, PsPublicKey, PsSigningKey, DEFAULT_PARAMS_SEED};
+use ps_signatures::threshold::{generate_key_shares, PsKeyShare};
 use rand::CryptoRng;
 
 #[derive(Clone, Debug)]
@@ -20,9 +23,19 @@ impl TokenIssuer {
     }
 
     pub fn generate<R: CryptoRng + rand::RngCore>(rng: &mut R) -> Self {
-        let params = PsParams::generate(rng);
+        Self::generate_with_attributes(0, rng)
+    }
+
+    pub fn generate_with_attributes<R: CryptoRng + rand::RngCore>(
+        num_attributes: usize,
+        rng: &mut R,
+    ) -> Self {
+        // Parameters shared by the issuers
+        let params = PsParams::from_seed(DEFAULT_PARAMS_SEED)
+            .expect("The default params seed has a valid length.");
 
-        let signing_key = PsSigningKey::generate(1, &params, rng);
+        // Messages: token id (hidden), expiry, attributes
+        let signing_key = PsSigningKey::generate(2 + num_attributes, &params, rng);
 
         let public_key = signing_key.derive_public_key(&params);
 
@@ -33,3 +46,92 @@ impl TokenIssuer {
         }
     }
 }
+
+// Issuer of the bb signed root tokens
+#[derive(Clone, Debug)]
+pub struct BbTokenIssuer {
+    pub signing_key: BbSigningKey,
+
+    pub public_key: BbPublicKey,
+
+    pub params: BbParams,
+}
+
+impl BbTokenIssuer {
+    pub fn new(signing_key: BbSigningKey, public_key: BbPublicKey, params: BbParams) -> Self {
+        Self {
+            signing_key,
+            public_key,
+            params,
+        }
+    }
+
+    pub fn generate<R: CryptoRng + rand::RngCore>(rng: &mut R) -> Self {
+        let params = BbParams::generate(rng);
+        let signing_key = BbSigningKey::generate(rng);
+        let public_key = signing_key.derive_public_key(&params);
+
+        Self {
+            signing_key,
+            public_key,
+            params,
+        }
+    }
+}
+
+// Issuer holding a share of the signing key. Any threshold of the issuers can issue root tokens.
+#[derive(Clone, Debug)]
+pub struct ThresholdTokenIssuer {
+    pub key_share: PsKeyShare,
+
+    // Verification key of the share, used by the clients to unblind the partial signatures
+    pub share_public_key: PsPublicKey,
+
+    // Aggregated public key the root tokens verify under
+    pub public_key: PsPublicKey,
+
+    pub params: PsParams,
+}
+
+impl ThresholdTokenIssuer {
+    pub fn new(key_share: PsKeyShare, public_key: PsPublicKey, params: PsParams) -> Self {
+        let share_public_key = key_share.derive_public_key(&params);
+
+        Self {
+            key_share,
+            share_public_key,
+            public_key,
+            params,
+        }
+    }
+
+    // Generates the issuers with a trusted dealer
+    pub fn generate<R: CryptoRng + rand::RngCore>(
+        threshold: usize,
+        issuer_count: usize,
+        num_attributes: usize,
+        rng: &mut R,
+    ) -> Result<Vec<Self>, VeronymousTokenError> {
+        let params = PsParams::from_seed(DEFAULT_PARAMS_SEED).map_err(|e| {
+            VeronymousTokenError::InvalidArgumentError(format!(
+                "Could not derive the params. {:?}",
+                e
+            ))
+        })?;
+
+        // Messages: token id (hidden), expiry, attributes
+        let (public_key, key_shares) =
+            generate_key_shares(threshold, issuer_count, 2 + num_attributes, &params, rng)
+                .map_err(|e| {
+                    VeronymousTokenError::InvalidArgumentError(format!(
+                        "Could not generate the key shares. {:?}",
+                        e
+                    ))
+                })?;
+
+        Ok(key_shares
+            .into_iter()
+            .map(|key_share| Self::new(key_share, public_key.clone(), params.clone()))
+            .collect())
+    }
+}
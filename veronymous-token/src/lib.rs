@@ -3,11 +3,16 @@ extern crate ps_signatures;
 * TODO: Review visibility
 */
 
+pub mod bb_root;
 pub mod error;
+pub mod issuance;
 pub mod issuer;
+pub mod predicate;
+pub mod revocation;
 pub mod root;
 pub mod root_exchange;
 pub mod serde;
+pub mod threshold_exchange;
 pub mod token;
 mod utils;
 
@@ -16,3 +21,4 @@ use pairing_plus::bls12_381::Fr;
 pub type RootTokenId = Fr;
 pub type TokenBlinding = Fr;
 pub type SerialNumber = [u8; 32];
+pub type IssuerKeyId = [u8; 32];
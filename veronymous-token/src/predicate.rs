@@ -0,0 +1,269 @@
+/*
+* Predicate proofs on hidden root token attributes.
+* The attribute is committed in G1 (g ^ attribute * h ^ blinding), linked to the
+* hidden root with the attribute response and proven to satisfy the predicate.
+*/
+
+use crate::error::VeronymousTokenError;
+use crate::error::VeronymousTokenError::{ProofError, VerificationError};
+use crate::utils::write_element;
+use commitments::pedersen_commitment::PedersenCommitmentCommitting;
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitted, ProverCommitting};
+use commitments::pok_set_membership::{SetMembershipCommitted, SetMembershipProof};
+use crypto_common::{fr_from_u64, rand_non_zero_fr};
+use pairing_plus::bls12_381::{Fr, G1};
+use ps_signatures::keys::PsParams;
+use rand::CryptoRng;
+
+pub(crate) const EQUAL_PREDICATE_TAG: u8 = 0;
+pub(crate) const MEMBER_PREDICATE_TAG: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributePredicate {
+    // attribute == value
+    Equal(u64),
+
+    // attribute in {values}
+    Member(Vec<u64>),
+}
+
+impl AttributePredicate {
+    pub fn is_satisfied(&self, value: u64) -> bool {
+        match self {
+            AttributePredicate::Equal(expected) => *expected == value,
+            AttributePredicate::Member(values) => values.contains(&value),
+        }
+    }
+
+    // Values the hidden attribute can take
+    pub fn set(&self) -> Vec<Fr> {
+        match self {
+            AttributePredicate::Equal(value) => vec![fr_from_u64(*value)],
+            AttributePredicate::Member(values) => {
+                values.iter().map(|value| fr_from_u64(*value)).collect()
+            }
+        }
+    }
+
+    fn challenge_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        match self {
+            AttributePredicate::Equal(value) => {
+                bytes.push(EQUAL_PREDICATE_TAG);
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+            AttributePredicate::Member(values) => {
+                bytes.push(MEMBER_PREDICATE_TAG);
+                bytes.extend_from_slice(&(values.len() as u64).to_be_bytes());
+                for value in values {
+                    bytes.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+        }
+
+        bytes
+    }
+}
+
+pub struct AttributePredicateCommitted {
+    index: usize,
+
+    predicate: AttributePredicate,
+
+    value: Fr,
+
+    commitment: G1,
+
+    blinding: Fr,
+
+    link: ProverCommitted<G1>,
+
+    membership: SetMembershipCommitted<G1>,
+}
+
+impl AttributePredicateCommitted {
+    // The value blinding factor must be the one used for the attribute in the root proof
+    pub fn new<R: CryptoRng + rand::RngCore>(
+        index: usize,
+        predicate: &AttributePredicate,
+        value: u64,
+        value_blinding_factor: Fr,
+        commitment_generator: &G1,
+        params: &PsParams,
+        rng: &mut R,
+    ) -> Result<Self, VeronymousTokenError> {
+        if !predicate.is_satisfied(value) {
+            return Err(VeronymousTokenError::InvalidArgumentError(format!(
+                "Attribute {} does not satisfy the predicate.",
+                index
+            )));
+        }
+
+        let value = fr_from_u64(value);
+        let blinding = rand_non_zero_fr(rng);
+
+        // 1) Attribute commitment
+        let commitment = PedersenCommitmentCommitting::new(
+            Some(vec![params.g, *commitment_generator]),
+            Some(vec![value, blinding]),
+        )
+        .map_err(|e| ProofError(format!("Could not create commitment. {:?}", e)))?
+        .finish();
+
+        // 2) Link the commitment to the root
+        let mut link_committing = ProverCommitting::new();
+        link_committing.commit(params.g, Some(value_blinding_factor));
+        link_committing.commit(*commitment_generator, Some(rand_non_zero_fr(rng)));
+
+        let link = link_committing.finish();
+
+        // 3) Predicate proof
+        let membership = SetMembershipCommitted::new(
+            &params.g,
+            commitment_generator,
+            &commitment.0,
+            &predicate.set(),
+            &value,
+            rng,
+        )
+        .map_err(|e| ProofError(format!("Could not create predicate proof. {:?}", e)))?;
+
+        Ok(Self {
+            index,
+            predicate: predicate.clone(),
+            value,
+            commitment: commitment.0,
+            blinding,
+            link,
+            membership,
+        })
+    }
+
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&(self.index as u64).to_be_bytes());
+        bytes.append(&mut self.predicate.challenge_bytes());
+        bytes.append(&mut self.link.challenge_bytes());
+        write_element(&self.commitment, false, &mut bytes)?;
+        bytes.append(&mut self.membership.challenge_bytes());
+
+        Ok(bytes)
+    }
+
+    pub fn generate_proof(
+        self,
+        challenge: &Fr,
+    ) -> Result<ProofAttributePredicate, VeronymousTokenError> {
+        let mut link = self
+            .link
+            .generate_proof(challenge, &[self.value, self.blinding])
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        Ok(ProofAttributePredicate {
+            index: self.index,
+            predicate: self.predicate,
+            commitment: self.commitment,
+            randomness_commitment: link.commitment,
+            blinding_response: link.responses.remove(1),
+            membership: self.membership.generate_proof(challenge, &self.blinding),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofAttributePredicate {
+    // Index of the hidden attribute
+    pub index: usize,
+
+    pub predicate: AttributePredicate,
+
+    pub commitment: G1,
+
+    pub randomness_commitment: G1,
+
+    pub blinding_response: Fr,
+
+    pub membership: SetMembershipProof<G1>,
+}
+
+impl ProofAttributePredicate {
+    pub fn challenge_bytes(
+        &self,
+        commitment_generator: &G1,
+        params: &PsParams,
+    ) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(&(self.index as u64).to_be_bytes());
+        bytes.append(&mut self.predicate.challenge_bytes());
+        write_element(&params.g, false, &mut bytes)?;
+        write_element(commitment_generator, false, &mut bytes)?;
+        write_element(&self.randomness_commitment, false, &mut bytes)?;
+        write_element(&self.commitment, false, &mut bytes)?;
+        bytes.append(
+            &mut self
+                .membership
+                .challenge_bytes(&params.g, commitment_generator),
+        );
+
+        Ok(bytes)
+    }
+
+    // The attribute response is the hidden attribute response of the root proof
+    pub fn verify(
+        &self,
+        attribute_response: &Fr,
+        commitment_generator: &G1,
+        params: &PsParams,
+        challenge: &Fr,
+    ) -> Result<bool, VeronymousTokenError> {
+        let gens = [params.g, *commitment_generator];
+
+        // Verify the link with the root
+        let link = CommitmentProof::new(
+            self.randomness_commitment,
+            vec![*attribute_response, self.blinding_response],
+        );
+
+        if !link
+            .verify(&gens, &self.commitment, challenge)
+            .map_err(|e| VerificationError(format!("Could not verify predicate pok. {:?}", e)))?
+        {
+            return Ok(false);
+        }
+
+        // Verify the predicate
+        let set = self.predicate.set();
+        if self.membership.commitments.len() != set.len() {
+            return Ok(false);
+        }
+
+        self.membership
+            .verify(
+                &params.g,
+                commitment_generator,
+                &self.commitment,
+                &set,
+                challenge,
+            )
+            .map_err(|e| VerificationError(format!("Could not verify predicate proof. {:?}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::predicate::AttributePredicate;
+
+    #[test]
+    fn test_predicate_is_satisfied() {
+        assert!(AttributePredicate::Equal(3).is_satisfied(3));
+        assert!(!AttributePredicate::Equal(3).is_satisfied(4));
+
+        let predicate = AttributePredicate::Member(vec![1, 2]);
+        assert!(predicate.is_satisfied(2));
+        assert!(!predicate.is_satisfied(3));
+    }
+}
@@ -0,0 +1,316 @@
+/*
+* Root token revocation.
+* The issuer signs a random revocation handle as a root token attribute and accumulates
+* the handles of the revoked root tokens. Derived tokens prove that their hidden handle
+* is not in the accumulator without revealing it.
+*
+* Non-membership witness: C ^ (y + alpha) * g1 ^ d == V with d != 0
+* Blinded with a random r: C' = C ^ r, D = g1 ^ (d * r)
+* Proof of knowledge of (y, r, d * r): C' ^ alpha == V ^ r / (D * C' ^ y)
+*/
+
+use crate::error::VeronymousTokenError;
+use crate::error::VeronymousTokenError::{DeserializationError, SerializationError};
+use crate::serde::Serializable;
+use crate::utils::{read_g1_point, read_g2_point, read_u8, write_element};
+use bb_signatures::accumulator::{Accumulator, NonMembershipWitness};
+use bb_signatures::keys::{BbParams, BbPublicKey};
+use crypto_common::{multi_scalar_mul_const_time, multi_scalar_mul_var_time, rand_non_zero_fr};
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Bls12, Fr, G1};
+use pairing_plus::{CurveProjective, Engine};
+use rand::CryptoRng;
+use std::io::Cursor;
+
+const SERIALIZED_REVOCATION_INFO_SIZE: usize = 289;
+
+// Random revocation handle to sign as a root token attribute
+pub fn generate_revocation_handle<R: CryptoRng + rand::RngCore>(rng: &mut R) -> u64 {
+    rng.next_u64()
+}
+
+// What the verifier needs to check that a token is not revoked
+#[derive(Clone, Debug, PartialEq)]
+pub struct RevocationInfo {
+    // Root token attribute holding the revocation handle
+    pub attribute_index: usize,
+
+    // Revoked handles
+    pub accumulator: Accumulator,
+
+    pub public_key: BbPublicKey,
+
+    pub params: BbParams,
+}
+
+impl Serializable for RevocationInfo {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        if self.attribute_index > u8::MAX as usize {
+            return Err(SerializationError(format!(
+                "Revocation handle attribute index cannot be more than {}.",
+                u8::MAX
+            )));
+        }
+
+        let mut bytes = Vec::with_capacity(SERIALIZED_REVOCATION_INFO_SIZE);
+
+        bytes.push(self.attribute_index as u8);
+        write_element(&self.accumulator.0, true, &mut bytes)?;
+        write_element(&self.params.g1, true, &mut bytes)?;
+        write_element(&self.params.g2, true, &mut bytes)?;
+        write_element(&self.public_key.0, true, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
+    where
+        Self: Sized,
+    {
+        if bytes.len() != SERIALIZED_REVOCATION_INFO_SIZE {
+            return Err(DeserializationError(format!(
+                "Serialized revocation info must have {} bytes.",
+                SERIALIZED_REVOCATION_INFO_SIZE
+            )));
+        }
+
+        let mut cursor = Cursor::new(bytes);
+
+        let attribute_index = read_u8(&mut cursor)? as usize;
+        let accumulator = Accumulator(read_g1_point(&mut cursor)?);
+        let params = BbParams {
+            g1: read_g1_point(&mut cursor)?,
+            g2: read_g2_point(&mut cursor)?,
+        };
+        let public_key = BbPublicKey(read_g2_point(&mut cursor)?);
+
+        Ok(Self {
+            attribute_index,
+            accumulator,
+            public_key,
+            params,
+        })
+    }
+}
+
+pub struct NonRevocationCommitted {
+    accumulator: G1,
+
+    blinded_witness: G1,
+
+    d_commitment: G1,
+
+    randomness_commitment: G1,
+
+    d_randomness_commitment: G1,
+
+    r: Fr,
+
+    d_r: Fr,
+
+    r_blinding_factor: Fr,
+
+    d_r_blinding_factor: Fr,
+}
+
+impl NonRevocationCommitted {
+    // The handle blinding factor must be the one used for the handle in the root proof
+    pub fn new<R: CryptoRng + rand::RngCore>(
+        witness: &NonMembershipWitness,
+        handle_blinding_factor: &Fr,
+        revocation_info: &RevocationInfo,
+        rng: &mut R,
+    ) -> Result<Self, VeronymousTokenError> {
+        if witness.d.is_zero() {
+            return Err(VeronymousTokenError::InvalidToken(
+                "Root token is revoked.".to_string(),
+            ));
+        }
+
+        let g1 = revocation_info.params.g1;
+        let accumulator = revocation_info.accumulator.0;
+
+        // 1) Blind the witness
+        let r = rand_non_zero_fr(rng);
+
+        let mut blinded_witness = witness.c;
+        blinded_witness.mul_assign(r);
+
+        let mut d_r = witness.d;
+        d_r.mul_assign(&r);
+
+        let mut d_commitment = g1;
+        d_commitment.mul_assign(d_r);
+
+        // 2) Commit to the randomness: V ^ rho_r / (g1 ^ rho_dr * C' ^ rho_y)
+        let r_blinding_factor = rand_non_zero_fr(rng);
+        let d_r_blinding_factor = rand_non_zero_fr(rng);
+
+        let mut negated_d_r_blinding_factor = d_r_blinding_factor;
+        negated_d_r_blinding_factor.negate();
+        let mut negated_handle_blinding_factor = *handle_blinding_factor;
+        negated_handle_blinding_factor.negate();
+
+        let randomness_commitment = multi_scalar_mul_const_time(
+            [accumulator, g1, blinded_witness],
+            [
+                r_blinding_factor,
+                negated_d_r_blinding_factor,
+                negated_handle_blinding_factor,
+            ],
+        );
+
+        let mut d_randomness_commitment = g1;
+        d_randomness_commitment.mul_assign(d_r_blinding_factor);
+
+        Ok(Self {
+            accumulator,
+            blinded_witness,
+            d_commitment,
+            randomness_commitment,
+            d_randomness_commitment,
+            r,
+            d_r,
+            r_blinding_factor,
+            d_r_blinding_factor,
+        })
+    }
+
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        challenge_bytes(
+            &self.accumulator,
+            &self.blinded_witness,
+            &self.d_commitment,
+            &self.randomness_commitment,
+            &self.d_randomness_commitment,
+        )
+    }
+
+    pub fn generate_proof(self, challenge: &Fr) -> ProofNonRevocation {
+        ProofNonRevocation {
+            blinded_witness: self.blinded_witness,
+            d_commitment: self.d_commitment,
+            randomness_commitment: self.randomness_commitment,
+            d_randomness_commitment: self.d_randomness_commitment,
+            r_response: response(&self.r_blinding_factor, challenge, &self.r),
+            d_response: response(&self.d_r_blinding_factor, challenge, &self.d_r),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofNonRevocation {
+    pub blinded_witness: G1,
+
+    pub d_commitment: G1,
+
+    pub randomness_commitment: G1,
+
+    pub d_randomness_commitment: G1,
+
+    pub r_response: Fr,
+
+    pub d_response: Fr,
+}
+
+impl ProofNonRevocation {
+    pub fn challenge_bytes(
+        &self,
+        revocation_info: &RevocationInfo,
+    ) -> Result<Vec<u8>, VeronymousTokenError> {
+        challenge_bytes(
+            &revocation_info.accumulator.0,
+            &self.blinded_witness,
+            &self.d_commitment,
+            &self.randomness_commitment,
+            &self.d_randomness_commitment,
+        )
+    }
+
+    // The handle response is the hidden handle attribute response of the root proof
+    pub fn verify(
+        &self,
+        handle_response: &Fr,
+        revocation_info: &RevocationInfo,
+        challenge: &Fr,
+    ) -> bool {
+        let g1 = revocation_info.params.g1;
+
+        // d != 0
+        if self.d_commitment.is_zero() {
+            return false;
+        }
+
+        // g1 ^ z_dr * D ^ c == R_D
+        let mut calculated_commitment =
+            multi_scalar_mul_var_time([g1, self.d_commitment], [self.d_response, *challenge]);
+        calculated_commitment.sub_assign(&self.d_randomness_commitment);
+
+        if !calculated_commitment.is_zero() {
+            return false;
+        }
+
+        // e(R / (V ^ z_r / (g1 ^ z_dr * C' ^ z_y)), g2) * e(C' ^ -c, public_key) == 1
+        let mut negated_d_response = self.d_response;
+        negated_d_response.negate();
+        let mut negated_handle_response = *handle_response;
+        negated_handle_response.negate();
+
+        let calculated_commitment = multi_scalar_mul_var_time(
+            [revocation_info.accumulator.0, g1, self.blinded_witness],
+            [self.r_response, negated_d_response, negated_handle_response],
+        );
+
+        let mut point_1 = self.randomness_commitment;
+        point_1.sub_assign(&calculated_commitment);
+
+        let mut negated_challenge = *challenge;
+        negated_challenge.negate();
+
+        let mut point_2 = self.blinded_witness;
+        point_2.mul_assign(negated_challenge);
+
+        let product = Bls12::pairing_product(
+            point_1,
+            revocation_info.params.g2,
+            point_2,
+            revocation_info.public_key.0,
+        );
+
+        product == <Bls12 as Engine>::Fqk::one()
+    }
+}
+
+// blinding_factor - challenge * secret
+fn response(blinding_factor: &Fr, challenge: &Fr, secret: &Fr) -> Fr {
+    let mut c = *challenge;
+    c.mul_assign(secret);
+
+    let mut response = *blinding_factor;
+    response.sub_assign(&c);
+
+    response
+}
+
+fn challenge_bytes(
+    accumulator: &G1,
+    blinded_witness: &G1,
+    d_commitment: &G1,
+    randomness_commitment: &G1,
+    d_randomness_commitment: &G1,
+) -> Result<Vec<u8>, VeronymousTokenError> {
+    let mut bytes = Vec::new();
+
+    for point in [
+        accumulator,
+        blinded_witness,
+        d_commitment,
+        randomness_commitment,
+        d_randomness_commitment,
+    ] {
+        write_element(point, false, &mut bytes)?;
+    }
+
+    Ok(bytes)
+}
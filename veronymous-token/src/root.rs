@@ -1,19 +1,25 @@
 use crate::error::VeronymousTokenError;
 use crate::error::VeronymousTokenError::{DeserializationError, ProofError};
+use crate::predicate::{AttributePredicate, AttributePredicateCommitted};
+use crate::revocation::{NonRevocationCommitted, RevocationInfo};
 use crate::serde::Serializable;
 use crate::token::{
-    compute_serial_number_generator, ProofRootToken, ProofSerialNumber, VeronymousToken,
+    compute_commitment_generator, compute_serial_number_generator, token_transcript,
+    ConnectionIndexCommitted, ProofExpiry, ProofSerialNumber, TokenAttribute, VeronymousToken,
+    EXPIRY_RANGE_BITS,
+};
+use crate::utils::{
+    read_attribute_values, read_fr, read_g1_point, read_u64, write_attribute_values, write_element,
 };
-use crate::utils::{read_fr, read_g1_point};
 use base64;
+use bb_signatures::accumulator::NonMembershipWitness;
 use commitments::pedersen_commitment::PedersenCommitmentCommitting;
 use commitments::pok_pedersen_commitment::ProverCommitting;
-use crypto_common::{hash_to_fr, rand_non_zero_fr};
-use pairing_plus::bls12_381::{Fr, G2};
-use pairing_plus::serdes::SerDes;
-use pairing_plus::CurveProjective;
+use commitments::pok_range::RangeProofCommitted;
+use crypto_common::{fr_from_u64, rand_non_zero_fr};
+use pairing_plus::bls12_381::Fr;
 use ps_signatures::keys::{PsParams, PsPublicKey};
-use ps_signatures::pok_sig::PsPokOfSignatureProof;
+use ps_signatures::pok::PsPok;
 use ps_signatures::signature::PsSignature;
 use rand::CryptoRng;
 use serde::de::{Error, Visitor};
@@ -21,12 +27,19 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::Formatter;
 use std::io::Cursor;
 
-const SERIALIZED_ROOT_TOKEN_SIZE: usize = 128;
+// Size of the root token without the attributes
+const SERIALIZED_ROOT_TOKEN_BASE_SIZE: usize = 137;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct RootVeronymousToken {
     pub token_id: Fr,
 
+    // Unix timestamp (seconds) of the last epoch the token can be used for
+    pub expiry: u64,
+
+    // Issuer attributes (tier, region, ...)
+    pub attributes: Vec<u64>,
+
     pub signature: PsSignature,
 }
 
@@ -39,7 +52,7 @@ impl RootVeronymousToken {
         // Verify the signature
         let signature_valid = self
             .signature
-            .verify(&[self.token_id], &public_key, &params)
+            .verify(&self.messages(), &public_key, &params)
             .map_err(|e| {
                 VeronymousTokenError::VerificationError(format!(
                     "Could not verify token_issuer signature. {:?}",
@@ -51,130 +64,413 @@ impl RootVeronymousToken {
     }
 
     // TODO: Find Epoch instead of timestamp
-    // TODO: Expiration
-    // Derive a veronymous token_issuer
-    pub fn derive_token<R: CryptoRng + rand::RngCore>(
-        &self,
-        domain: &[u8],
+    // Derive a veronymous token_issuer for the domain and epoch.
+    // Connection index, disclosed attributes, predicates, revocation and context are set on the builder.
+    pub fn derive_builder<'a>(
+        &'a self,
+        domain: &'a [u8],
         timestamp: u64,
-        public_key: &PsPublicKey,
-        params: &PsParams,
+        public_key: &'a PsPublicKey,
+        params: &'a PsParams,
+    ) -> DeriveTokenBuilder<'a> {
+        DeriveTokenBuilder {
+            root_token: self,
+            domain,
+            timestamp,
+            connection_index: 0,
+            connection_limit: 1,
+            disclosed_attributes: Vec::new(),
+            predicates: Vec::new(),
+            revocation: None,
+            context: None,
+            public_key,
+            params,
+        }
+    }
+
+    fn derive<R: CryptoRng + rand::RngCore>(
+        &self,
+        builder: DeriveTokenBuilder,
         rng: &mut R,
     ) -> Result<VeronymousToken, VeronymousTokenError> {
-        if public_key.y_cap.len() < 1 {
+        let DeriveTokenBuilder {
+            domain,
+            timestamp,
+            connection_index,
+            connection_limit,
+            disclosed_attributes,
+            predicates,
+            revocation,
+            context,
+            public_key,
+            params,
+            ..
+        } = builder;
+        let disclosed_attributes = disclosed_attributes.as_slice();
+        let predicates = predicates.as_slice();
+
+        if public_key.y_cap_tilde.len() != 2 + self.attributes.len() {
             return Err(VeronymousTokenError::InvalidArgumentError(format!(
-                "Public key must have at least 1 Y."
+                "Public key must have {} Y.",
+                2 + self.attributes.len()
             )));
         }
 
-        // 1) Hidden root
-        let blinding_t = rand_non_zero_fr(rng);
+        if let Some(index) = disclosed_attributes
+            .iter()
+            .find(|index| **index >= self.attributes.len())
+        {
+            return Err(VeronymousTokenError::InvalidArgumentError(format!(
+                "Disclosed attribute index ({}) is out of bounds.",
+                index
+            )));
+        }
+
+        let hidden_attributes: Vec<usize> = (0..self.attributes.len())
+            .filter(|index| !disclosed_attributes.contains(index))
+            .collect();
+
+        if predicates.len() > u8::MAX as usize {
+            return Err(VeronymousTokenError::InvalidArgumentError(format!(
+                "Cannot prove more than {} predicates.",
+                u8::MAX
+            )));
+        }
 
-        // hidden_root = (g ^ token_id)(g ^ blinding_t)
-        let root_commitment = PedersenCommitmentCommitting::new(
-            Some(vec![public_key.y_cap_tilde[0], params.g_tilde]),
-            Some(vec![self.token_id.clone(), blinding_t.clone()]),
+        if let Some((index, _)) = predicates
+            .iter()
+            .find(|(index, _)| !hidden_attributes.contains(index))
+        {
+            return Err(VeronymousTokenError::InvalidArgumentError(format!(
+                "Predicate attribute ({}) must be hidden.",
+                index
+            )));
+        }
+
+        if let Some((revocation_info, _)) = revocation {
+            if !hidden_attributes.contains(&revocation_info.attribute_index) {
+                return Err(VeronymousTokenError::InvalidArgumentError(format!(
+                    "Revocation handle attribute ({}) must be hidden.",
+                    revocation_info.attribute_index
+                )));
+            }
+        }
+
+        if timestamp > self.expiry || (self.expiry - timestamp) >> EXPIRY_RANGE_BITS != 0 {
+            return Err(VeronymousTokenError::InvalidToken(format!(
+                "Root token expiry ({}) is not valid for epoch {}.",
+                self.expiry, timestamp
+            )));
+        }
+
+        let expiry = fr_from_u64(self.expiry);
+
+        // 1) Hidden messages blinding factors shared with the other proofs
+        let root_blinding_factor = rand_non_zero_fr(rng);
+        let expiry_blinding_factor = rand_non_zero_fr(rng);
+        let attribute_blinding_factors: Vec<Fr> = hidden_attributes
+            .iter()
+            .map(|_| rand_non_zero_fr(rng))
+            .collect();
+
+        // 2) Signature proof of knowledge with the disclosed attributes revealed
+        let disclosed_messages: Vec<usize> =
+            disclosed_attributes.iter().map(|index| 2 + index).collect();
+
+        let messages = self.messages();
+
+        let mut root_builder = PsPok::builder(&self.signature, &messages, public_key, params)
+            .reveal(&disclosed_messages)
+            .blinding_factor(0, root_blinding_factor)
+            .blinding_factor(1, expiry_blinding_factor);
+        for (index, blinding_factor) in hidden_attributes.iter().zip(&attribute_blinding_factors) {
+            root_builder = root_builder.blinding_factor(2 + index, *blinding_factor);
+        }
+
+        let root_committed = root_builder
+            .commit(rng)
+            .map_err(|e| ProofError(format!("Could not create root proof. {:?}", e)))?;
+
+        // 3) Hidden connection index with the proof of 0 <= index < limit
+        let commitment_generator = compute_commitment_generator();
+        let index_blinding_factor = rand_non_zero_fr(rng);
+
+        let connection_index_committed = ConnectionIndexCommitted::new(
+            connection_index,
+            connection_limit,
+            &index_blinding_factor,
+            &params.g,
+            &commitment_generator,
+            rng,
+        )?;
+
+        // 4) Hidden expiry (g ^ expiry)(h ^ blinding_e) and proof of epoch <= expiry
+        let blinding_e = rand_non_zero_fr(rng);
+
+        let expiry_commitment = PedersenCommitmentCommitting::new(
+            Some(vec![params.g, commitment_generator]),
+            Some(vec![expiry, blinding_e]),
         )
         .map_err(|e| {
             VeronymousTokenError::ProofError(format!("Could not create commitment. {:?}", e))
         })?
         .finish();
 
-        // 2) Signature proof
-        let root_signature =
-            PsPokOfSignatureProof::new(&self.signature, Some(blinding_t.clone()), rng);
+        let expiry_range = RangeProofCommitted::new(
+            &params.g,
+            &commitment_generator,
+            self.expiry - timestamp,
+            &blinding_e,
+            EXPIRY_RANGE_BITS,
+            rng,
+        )
+        .map_err(|e| ProofError(format!("Could not create range proof. {:?}", e)))?;
+
+        // 5) Predicates and non revocation proofs
+        // Predicates share the hidden attribute blinding factor with the root proof
+        let mut predicate_committed = Vec::with_capacity(predicates.len());
+        for (index, predicate) in predicates {
+            let position = hidden_attributes
+                .iter()
+                .position(|hidden| hidden == index)
+                .unwrap();
+
+            predicate_committed.push(AttributePredicateCommitted::new(
+                *index,
+                predicate,
+                self.attributes[*index],
+                attribute_blinding_factors[position],
+                &commitment_generator,
+                params,
+                rng,
+            )?);
+        }
 
-        // 3) Derive the serial number
-        let serial_number_generator = compute_serial_number_generator(domain, timestamp);
-        let serial_number = self.derive_serial_number(&serial_number_generator);
+        // The non revocation proof shares the revocation handle blinding factor with the root proof
+        let revocation_committed = match revocation {
+            Some((revocation_info, witness)) => {
+                let position = hidden_attributes
+                    .iter()
+                    .position(|hidden| *hidden == revocation_info.attribute_index)
+                    .unwrap();
+
+                Some(NonRevocationCommitted::new(
+                    witness,
+                    &attribute_blinding_factors[position],
+                    revocation_info,
+                    rng,
+                )?)
+            }
+            None => None,
+        };
 
-        // 4) Create the proof of knowledge
-        let root_blinding_factor = rand_non_zero_fr(rng);
+        let mut expiry_prover_committing = ProverCommitting::new();
+        expiry_prover_committing.commit(params.g, Some(expiry_blinding_factor));
+        expiry_prover_committing.commit(commitment_generator, Some(rand_non_zero_fr(rng)));
+
+        let expiry_prover_committed = expiry_prover_committing.finish();
 
-        let mut prover_committing = ProverCommitting::new();
-        prover_committing.commit(
-            public_key.y_cap_tilde[0],
-            Some(root_blinding_factor.clone()),
+        // 6) Derive the serial number
+        let serial_number_generator = compute_serial_number_generator(domain, timestamp);
+        let proof_serial_number = ProofSerialNumber::new(
+            &self.token_id,
+            &fr_from_u64(connection_index),
+            &root_blinding_factor,
+            &index_blinding_factor,
+            &serial_number_generator,
+        )?;
+
+        // Create the challenge
+        let disclosed_values: Vec<(usize, u64)> = self
+            .attributes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| disclosed_attributes.contains(index))
+            .map(|(index, value)| (index, *value))
+            .collect();
+
+        let mut transcript = token_transcript(
+            domain,
+            timestamp,
+            connection_limit,
+            &disclosed_values,
+            context,
+            public_key,
+        )?;
+        transcript.append_message(b"root", &root_committed.challenge_bytes());
+        transcript.append_message(
+            b"serial_number",
+            &proof_serial_number.challenge_bytes(&serial_number_generator)?,
         );
-        prover_committing.commit(params.g_tilde, Some(blinding_t));
-
-        let prover_committed = prover_committing.finish();
-
-        // Serial number randomness commitment
-        let mut serial_number_commitment = serial_number_generator;
-        serial_number_commitment.mul_assign(root_blinding_factor);
-
-        // Create the challenge hash(|g1|g2|randomness_commitment|g3^blinding_t|root_commitment)
-        let mut challenge_bytes = prover_committed.challenge_bytes();
-        root_commitment
-            .0
-            .serialize(&mut challenge_bytes, false)
-            .unwrap();
-        serial_number_generator
-            .serialize(&mut challenge_bytes, false)
-            .unwrap();
-        serial_number_commitment
-            .serialize(&mut challenge_bytes, false)
-            .unwrap();
-
-        let challenge = hash_to_fr(challenge_bytes);
-
-        // Generate the proof of knowledge
-        let mut pok = prover_committed
-            .generate_proof(&challenge, &[self.token_id.clone(), blinding_t])
-            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+        transcript.append_message(
+            b"connection_index",
+            &connection_index_committed.challenge_bytes()?,
+        );
+        transcript.append_message(b"expiry", &expiry_prover_committed.challenge_bytes());
 
-        let root_token_response = pok.responses.remove(0);
-        let blinding_response = pok.responses.remove(0);
+        let mut expiry_commitment_bytes = Vec::new();
+        write_element(&expiry_commitment.0, false, &mut expiry_commitment_bytes)?;
+        transcript.append_message(b"expiry_commitment", &expiry_commitment_bytes);
 
-        let proof_root_token = ProofRootToken {
-            root: root_commitment,
-            randomness_commitment: pok.commitment,
-            blinding_response,
-        };
+        transcript.append_message(b"expiry_range", &expiry_range.challenge_bytes());
+        for predicate in &predicate_committed {
+            transcript.append_message(b"predicate", &predicate.challenge_bytes()?);
+        }
+        if let Some(revocation_committed) = &revocation_committed {
+            transcript.append_message(b"revocation", &revocation_committed.challenge_bytes()?);
+        }
+
+        let challenge = transcript.challenge_fr(b"challenge");
 
-        let proof_serial_number = ProofSerialNumber {
-            serial_number,
-            randomness_commitment: serial_number_commitment,
+        // Generate the proofs of knowledge
+        let root = root_committed
+            .generate_proof(&challenge)
+            .map_err(|e| ProofError(format!("Could not generate root proof. {:?}", e)))?;
+
+        let (proof_connection_index, connection_index_response) =
+            connection_index_committed.generate_proof(&challenge)?;
+
+        let mut expiry_pok = expiry_prover_committed
+            .generate_proof(&challenge, &[expiry, blinding_e])
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        let attributes = self
+            .attributes
+            .iter()
+            .enumerate()
+            .map(
+                |(index, value)| match disclosed_attributes.contains(&index) {
+                    true => TokenAttribute::Disclosed(*value),
+                    false => TokenAttribute::Hidden,
+                },
+            )
+            .collect();
+
+        let proof_expiry = ProofExpiry {
+            commitment: expiry_commitment.0,
+            randomness_commitment: expiry_pok.commitment,
+            blinding_response: expiry_pok.responses.remove(1),
+            range: expiry_range.generate_proof(&challenge),
         };
 
         Ok(VeronymousToken {
-            root: proof_root_token,
-            root_signature,
+            root,
             serial_number: proof_serial_number,
-            root_token_response,
+            connection_index: proof_connection_index,
+            expiry: proof_expiry,
+            attributes,
+            predicates: predicate_committed
+                .into_iter()
+                .map(|predicate| predicate.generate_proof(&challenge))
+                .collect::<Result<Vec<_>, _>>()?,
+            revocation: revocation_committed
+                .map(|revocation| revocation.generate_proof(&challenge)),
+            connection_index_response,
         })
     }
 
-    // TODO: Put derive generator in common function
-    fn derive_serial_number(&self, generator: &G2) -> G2 {
-        // Serial number = hash_to_curve(domain, timestamp) ^ token_id
-        let mut serial_number = *generator;
-        serial_number.mul_assign(self.token_id.clone());
+    // Signed messages: token id, expiry, attributes
+    fn messages(&self) -> Vec<Fr> {
+        let mut messages = Vec::with_capacity(2 + self.attributes.len());
+        messages.push(self.token_id);
+        messages.push(fr_from_u64(self.expiry));
+        messages.extend(self.attributes.iter().map(|value| fr_from_u64(*value)));
 
-        serial_number
+        messages
+    }
+}
+
+pub struct DeriveTokenBuilder<'a> {
+    root_token: &'a RootVeronymousToken,
+
+    domain: &'a [u8],
+
+    timestamp: u64,
+
+    connection_index: u64,
+
+    connection_limit: u64,
+
+    disclosed_attributes: Vec<usize>,
+
+    predicates: Vec<(usize, AttributePredicate)>,
+
+    revocation: Option<(&'a RevocationInfo, &'a NonMembershipWitness)>,
+
+    context: Option<&'a [u8]>,
+
+    public_key: &'a PsPublicKey,
+
+    params: &'a PsParams,
+}
+
+impl<'a> DeriveTokenBuilder<'a> {
+    // Connection index of the epoch (0 <= index < limit), defaults to 0 of 1
+    pub fn connection(mut self, connection_index: u64, connection_limit: u64) -> Self {
+        self.connection_index = connection_index;
+        self.connection_limit = connection_limit;
+        self
+    }
+
+    // Attributes at the indices are revealed to the verifier
+    pub fn disclose(mut self, indices: &[usize]) -> Self {
+        self.disclosed_attributes.extend_from_slice(indices);
+        self
+    }
+
+    // Predicate proven on a hidden attribute
+    pub fn predicate(mut self, index: usize, predicate: AttributePredicate) -> Self {
+        self.predicates.push((index, predicate));
+        self
+    }
+
+    // Proves that the hidden revocation handle is not revoked
+    pub fn revocation(
+        mut self,
+        revocation_info: &'a RevocationInfo,
+        witness: &'a NonMembershipWitness,
+    ) -> Self {
+        self.revocation = Some((revocation_info, witness));
+        self
+    }
+
+    // Context (e.g. the client wireguard key) bound to the proof,
+    // the token only verifies with the same context
+    pub fn context(mut self, context: &'a [u8]) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    pub fn derive<R: CryptoRng + rand::RngCore>(
+        self,
+        rng: &mut R,
+    ) -> Result<VeronymousToken, VeronymousTokenError> {
+        self.root_token.derive(self, rng)
     }
 }
 
 impl Serializable for RootVeronymousToken {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
         // TODO: With capacity
-        let mut bytes = Vec::with_capacity(SERIALIZED_ROOT_TOKEN_SIZE);
-        self.token_id.serialize(&mut bytes, true).unwrap();
-        self.signature.sigma_1.serialize(&mut bytes, true).unwrap();
-        self.signature.sigma_2.serialize(&mut bytes, true).unwrap();
-
-        bytes
+        let mut bytes =
+            Vec::with_capacity(SERIALIZED_ROOT_TOKEN_BASE_SIZE + 8 * self.attributes.len());
+        write_element(&self.token_id, true, &mut bytes)?;
+        write_element(&self.signature.sigma_1, true, &mut bytes)?;
+        write_element(&self.signature.sigma_2, true, &mut bytes)?;
+        bytes.extend_from_slice(&self.expiry.to_be_bytes());
+        write_attribute_values(&self.attributes, &mut bytes)?;
+
+        Ok(bytes)
     }
 
     fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
     where
         Self: Sized,
     {
-        if bytes.len() != SERIALIZED_ROOT_TOKEN_SIZE {
+        if bytes.len() < SERIALIZED_ROOT_TOKEN_BASE_SIZE {
             return Err(DeserializationError(format!(
-                "Serialized token_issuer must have {} bytes.",
-                SERIALIZED_ROOT_TOKEN_SIZE
+                "Serialized token_issuer must have at least {} bytes.",
+                SERIALIZED_ROOT_TOKEN_BASE_SIZE
             )));
         }
 
@@ -185,9 +481,19 @@ impl Serializable for RootVeronymousToken {
             sigma_1: read_g1_point(&mut cursor)?,
             sigma_2: read_g1_point(&mut cursor)?,
         };
+        let expiry = read_u64(&mut cursor)?;
+        let attributes = read_attribute_values(&mut cursor)?;
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized token_issuer has trailing bytes.".to_string(),
+            ));
+        }
 
         Ok(Self {
             token_id,
+            expiry,
+            attributes,
             signature,
         })
     }
@@ -198,7 +504,8 @@ impl Serialize for RootVeronymousToken {
     where
         S: Serializer,
     {
-        let bytes = Serializable::serialize(self);
+        let bytes = Serializable::serialize(self)
+            .map_err(|e| <S::Error as serde::ser::Error>::custom(format!("{:?}", e)))?;
         let string = base64::encode(&bytes);
 
         serializer.serialize_str(string.as_str())
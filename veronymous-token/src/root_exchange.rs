@@ -1,32 +1,26 @@
 use crate::error::VeronymousTokenError;
-use crate::error::VeronymousTokenError::DeserializationError;
+use crate::error::VeronymousTokenError::{DeserializationError, SerializationError};
 use crate::root::RootVeronymousToken;
 use crate::serde::Serializable;
-use crate::utils::{read_fr, read_g1_point};
+use crate::utils::{
+    read_attribute_values, read_g1_point, read_u64, write_attribute_values, write_element,
+};
 use crate::{RootTokenId, TokenBlinding};
-use commitments::pedersen_commitment::PedersenCommitmentCommitting;
-use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitting};
-use crypto_common::hash_to_fr;
-use pairing_plus::bls12_381::{Fr, G1};
-use pairing_plus::serdes::SerDes;
-use ps_signatures::blind_signature::PsBlindSignature;
+use crypto_common::fr_from_u64;
+use ps_signatures::blind_signature::{BlindSignatureRequest, PsBlindSignature};
 use ps_signatures::keys::{PsParams, PsPublicKey, PsSigningKey};
+use ps_signatures::serde::Serializable as PsSerializable;
 use ps_signatures::signature::PsSignature;
 use rand::CryptoRng;
 use std::io::Cursor;
 
-const SERIALIZED_TOKEN_REQUEST_SIZE: usize = 160;
-const SERIALIZED_TOKEN_RESPONSE_SIZE: usize = 96;
+// Size of the response without the attributes
+const SERIALIZED_TOKEN_RESPONSE_BASE_SIZE: usize = 105;
 
+// Blind signature request on the token id (message 0)
 #[derive(Clone, Debug, PartialEq)]
 pub struct RootTokenRequest {
-    pub token_id_commitment: G1,
-
-    pub randomness_commitment: G1,
-
-    pub token_id_response: Fr,
-
-    pub blinding_factor_response: Fr,
+    pub request: BlindSignatureRequest,
 }
 
 impl RootTokenRequest {
@@ -41,94 +35,64 @@ impl RootTokenRequest {
             )));
         }
 
-        let proof = CommitmentProof {
-            commitment: self.randomness_commitment,
-            responses: vec![self.token_id_response, self.blinding_factor_response],
-        };
-
-        let gens = vec![public_key.y_cap[0], params.g];
-
-        // Get the challenge
-        let mut challenge_bytes = proof.challenge_bytes(&gens);
-        self.token_id_commitment
-            .serialize(&mut challenge_bytes, false)
-            .unwrap();
-
-        let challenge = hash_to_fr(&challenge_bytes);
-        let result = proof
-            .verify(&gens, &self.token_id_commitment, &challenge)
-            .map_err(|e| {
-                VeronymousTokenError::VerificationError(format!("Could not verify proof. {:?}", e))
-            })?;
+        // Only the token id is hidden
+        if self.request.hidden_indices != [0] {
+            return Ok(false);
+        }
 
-        Ok(result)
+        self.request.verify(public_key, params).map_err(|e| {
+            VeronymousTokenError::VerificationError(format!("Could not verify proof. {:?}", e))
+        })
     }
 }
 
 impl Serializable for RootTokenRequest {
-    fn serialize(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(SERIALIZED_TOKEN_REQUEST_SIZE);
-
-        self.token_id_commitment
-            .serialize(&mut bytes, true)
-            .unwrap();
-        self.randomness_commitment
-            .serialize(&mut bytes, true)
-            .unwrap();
-        self.token_id_response.serialize(&mut bytes, true).unwrap();
-        self.blinding_factor_response
-            .serialize(&mut bytes, true)
-            .unwrap();
-
-        bytes
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        self.request
+            .serialize()
+            .map_err(|e| SerializationError(format!("Could not serialize token request. {:?}", e)))
     }
 
     fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError> {
-        if bytes.len() != SERIALIZED_TOKEN_REQUEST_SIZE {
-            return Err(DeserializationError(format!(
-                "Serialized token_issuer request must have {} bytes",
-                SERIALIZED_TOKEN_REQUEST_SIZE
-            )));
-        }
-        let mut cursor = Cursor::new(bytes);
-
-        let token_id_commitment = read_g1_point(&mut cursor)?;
-        let randomness_commitment = read_g1_point(&mut cursor)?;
-        let token_id_response = read_fr(&mut cursor)?;
-        let blinding_factor_response = read_fr(&mut cursor)?;
+        let request = BlindSignatureRequest::deserialize(bytes).map_err(|e| {
+            DeserializationError(format!("Could not deserialize token request. {:?}", e))
+        })?;
 
-        Ok(Self {
-            token_id_commitment,
-            randomness_commitment,
-            token_id_response,
-            blinding_factor_response,
-        })
+        Ok(Self { request })
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct RootTokenResponse {
     pub signature: PsSignature,
+
+    // Expiry and attributes signed by the issuer
+    pub expiry: u64,
+
+    pub attributes: Vec<u64>,
 }
 
 impl Serializable for RootTokenResponse {
-    fn serialize(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(SERIALIZED_TOKEN_RESPONSE_SIZE);
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes =
+            Vec::with_capacity(SERIALIZED_TOKEN_RESPONSE_BASE_SIZE + 8 * self.attributes.len());
 
-        self.signature.sigma_1.serialize(&mut bytes, true).unwrap();
-        self.signature.sigma_2.serialize(&mut bytes, true).unwrap();
+        write_element(&self.signature.sigma_1, true, &mut bytes)?;
+        write_element(&self.signature.sigma_2, true, &mut bytes)?;
+        bytes.extend_from_slice(&self.expiry.to_be_bytes());
+        write_attribute_values(&self.attributes, &mut bytes)?;
 
-        bytes
+        Ok(bytes)
     }
 
     fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
     where
         Self: Sized,
     {
-        if bytes.len() != SERIALIZED_TOKEN_RESPONSE_SIZE {
+        if bytes.len() < SERIALIZED_TOKEN_RESPONSE_BASE_SIZE {
             return Err(DeserializationError(format!(
-                "Serialized token_issuer response must have {} bytes",
-                SERIALIZED_TOKEN_RESPONSE_SIZE
+                "Serialized token_issuer response must have at least {} bytes",
+                SERIALIZED_TOKEN_RESPONSE_BASE_SIZE
             )));
         }
 
@@ -136,9 +100,19 @@ impl Serializable for RootTokenResponse {
 
         let sigma_1 = read_g1_point(&mut cursor)?;
         let sigma_2 = read_g1_point(&mut cursor)?;
+        let expiry = read_u64(&mut cursor)?;
+        let attributes = read_attribute_values(&mut cursor)?;
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized token_issuer response has trailing bytes.".to_string(),
+            ));
+        }
 
         Ok(Self {
             signature: PsSignature { sigma_1, sigma_2 },
+            expiry,
+            attributes,
         })
     }
 }
@@ -155,57 +129,38 @@ pub fn create_root_token_request(
         )));
     }
 
-    // 1) Create the token_id commitment
-    let mut committing = PedersenCommitmentCommitting::new(None, None).unwrap();
-    committing.commit(public_key.y_cap[0], *token_id);
-    committing.commit(params.g, *blinding);
-
-    let commitment = committing.finish();
-
-    // 2) Create the commitment proof
-
-    // Commit to randomness
-    let mut prover_committing = ProverCommitting::new();
-
-    prover_committing.commit(public_key.y_cap[0], None);
-    prover_committing.commit(params.g, None);
-
-    let prover_committed = prover_committing.finish();
-
-    // Get the challenge bytes
-    let mut challenge_bytes = prover_committed.challenge_bytes();
-    commitment.0.serialize(&mut challenge_bytes, false).unwrap();
-
-    let challenge = hash_to_fr(&challenge_bytes);
-
-    // Generate the proof of knowledge
-    let mut proof = prover_committed
-        .generate_proof(&challenge, &[*token_id, *blinding])
+    // Commit to the token_id with a proof of knowledge
+    let request = BlindSignatureRequest::new(&[(0, *token_id)], blinding, public_key, params)
         .map_err(|e| {
-            VeronymousTokenError::ProofError(format!(
-                "Could not generate commitment proof. {:?}",
-                e
-            ))
+            VeronymousTokenError::ProofError(format!("Could not create token request. {:?}", e))
         })?;
 
-    let token_id_response = proof.responses.remove(0);
-    let blinding_factor_response = proof.responses.remove(0);
-
-    Ok(RootTokenRequest {
-        token_id_commitment: commitment.0,
-        randomness_commitment: proof.commitment,
-        token_id_response,
-        blinding_factor_response,
-    })
+    Ok(RootTokenRequest { request })
 }
 
 pub fn issue_root_token<R: CryptoRng + rand::RngCore>(
     token_request: &RootTokenRequest,
+    expiry: u64,
+    attributes: &[u64],
     signing_key: &PsSigningKey,
     public_key: &PsPublicKey,
     params: &PsParams,
     rng: &mut R,
 ) -> Result<RootTokenResponse, VeronymousTokenError> {
+    if public_key.y_cap.len() != 2 + attributes.len() {
+        return Err(VeronymousTokenError::InvalidArgumentError(format!(
+            "Public key must have {} Y.",
+            2 + attributes.len()
+        )));
+    }
+
+    if attributes.len() > u8::MAX as usize {
+        return Err(VeronymousTokenError::InvalidArgumentError(format!(
+            "Cannot have more than {} attributes.",
+            u8::MAX
+        )));
+    }
+
     // 1) Verify the token_issuer
     if !token_request.verify(&public_key, &params)? {
         return Err(VeronymousTokenError::VerificationError(format!(
@@ -213,10 +168,19 @@ pub fn issue_root_token<R: CryptoRng + rand::RngCore>(
         )));
     }
 
-    // 2) Sign the token_issuer
-    let blind_signature = PsBlindSignature::new(
-        token_request.token_id_commitment,
-        &[],
+    // 2) Sign the token_issuer, its expiry and attributes
+    let mut messages = Vec::with_capacity(1 + attributes.len());
+    messages.push((1, fr_from_u64(expiry)));
+    messages.extend(
+        attributes
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (2 + i, fr_from_u64(*value))),
+    );
+
+    let blind_signature = PsBlindSignature::sign_request(
+        &token_request.request,
+        &messages,
         &signing_key,
         &public_key,
         &params,
@@ -224,10 +188,13 @@ pub fn issue_root_token<R: CryptoRng + rand::RngCore>(
     )
     .map_err(|e| {
         VeronymousTokenError::SigningError(format!("Could not sign token_issuer. {:?}", e))
-    })?;
+    })?
+    .signature;
 
     Ok(RootTokenResponse {
         signature: blind_signature,
+        expiry,
+        attributes: attributes.to_vec(),
     })
 }
 
@@ -243,6 +210,8 @@ pub fn complete_root_token(
 
     let root_token = RootVeronymousToken {
         token_id: *token_id,
+        expiry: token_response.expiry,
+        attributes: token_response.attributes.clone(),
         signature,
     };
 
@@ -258,19 +227,65 @@ pub fn complete_root_token(
 
 #[cfg(test)]
 mod tests {
+    use crate::error::VeronymousTokenError::IdentityPointError;
     use crate::issuer::TokenIssuer;
+    use crate::predicate::AttributePredicate;
+    use crate::revocation::{generate_revocation_handle, RevocationInfo};
     use crate::root::RootVeronymousToken;
     use crate::root_exchange::{
         complete_root_token, create_root_token_request, issue_root_token, RootTokenRequest,
         RootTokenResponse,
     };
     use crate::serde::Serializable;
-    use crate::token::VeronymousToken;
-    use crypto_common::rand_non_zero_fr;
+    use crate::token::{TokenAttribute, VeronymousToken};
+    use bb_signatures::accumulator::Accumulator;
+    use bb_signatures::keys::{BbParams, BbSigningKey};
+    use crypto_common::{fr_from_u64, rand_non_zero_fr};
     use ff_zeroize::Field;
     use pairing_plus::bls12_381::Fr;
+    use ps_signatures::prepared::PreparedPsPublicKey;
+    use ps_signatures::serde::Serializable as PsSerializable;
+    use rand::rngs::ThreadRng;
     use rand::thread_rng;
 
+    const NOW: u64 = 1643629600;
+
+    const EXPIRY: u64 = NOW + 30 * 24 * 60 * 60;
+
+    // Root token of a new issuer without attributes
+    fn create_root_token(rng: &mut ThreadRng) -> (TokenIssuer, RootVeronymousToken) {
+        let issuer = TokenIssuer::generate(rng);
+
+        let token_id = rand_non_zero_fr(rng);
+        let blinding = rand_non_zero_fr(rng);
+
+        let token_request =
+            create_root_token_request(&token_id, &blinding, &issuer.public_key, &issuer.params)
+                .unwrap();
+
+        let token_response = issue_root_token(
+            &token_request,
+            EXPIRY,
+            &[],
+            &issuer.signing_key,
+            &issuer.public_key,
+            &issuer.params,
+            rng,
+        )
+        .unwrap();
+
+        let root_token = complete_root_token(
+            &token_response,
+            &token_id,
+            &blinding,
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .unwrap();
+
+        (issuer, root_token)
+    }
+
     #[test]
     fn test_root_token_exchange() {
         let mut rng = thread_rng();
@@ -286,7 +301,7 @@ mod tests {
                 .unwrap();
 
         // Serialize de serialize
-        let token_request_serialized = token_request.serialize();
+        let token_request_serialized = token_request.serialize().unwrap();
         let token_request_deserialized =
             RootTokenRequest::deserialize(&token_request_serialized).unwrap();
         assert_eq!(token_request, token_request_deserialized);
@@ -300,6 +315,8 @@ mod tests {
         // Issuer issues the token_issuer
         let token_response = issue_root_token(
             &token_request,
+            EXPIRY,
+            &[],
             &issuer.signing_key,
             &issuer.public_key,
             &issuer.params,
@@ -308,7 +325,7 @@ mod tests {
         .unwrap();
 
         // Serialize and deserialize
-        let token_response_serialized = token_response.serialize();
+        let token_response_serialized = token_response.serialize().unwrap();
         let token_response_deserialized =
             RootTokenResponse::deserialize(&token_response_serialized).unwrap();
         assert_eq!(token_response, token_response_deserialized);
@@ -322,21 +339,78 @@ mod tests {
         )
         .unwrap();
 
-        let root_token_serialized = root_token.serialize();
+        let root_token_serialized = root_token.serialize().unwrap();
         let root_token_deserialized =
             RootVeronymousToken::deserialize(&root_token_serialized).unwrap();
         assert_eq!(root_token, root_token_deserialized);
 
         // Veronymous token_issuer
         let domain = "test".as_bytes();
-        let now = 1643629600u64;
+
+        let veronymous_token = root_token
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .derive(&mut rng)
+            .unwrap();
+
+        let veronymous_token_deserialized =
+            VeronymousToken::deserialize(&veronymous_token.serialize().unwrap()).unwrap();
+        assert_eq!(veronymous_token, veronymous_token_deserialized);
+
+        let result = veronymous_token
+            .verify(
+                domain,
+                NOW,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params,
+            )
+            .unwrap();
+
+        assert!(result);
+
+        // Test bad id
+        root_token.token_id = Fr::random(&mut rng);
+
+        let veronymous_token = root_token
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .derive(&mut rng)
+            .unwrap();
+
+        let result = veronymous_token
+            .verify(
+                domain,
+                NOW,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params,
+            )
+            .unwrap();
+        assert!(!result)
+    }
+
+    #[test]
+    fn test_connection_index() {
+        let mut rng = thread_rng();
+
+        let (issuer, root_token) = create_root_token(&mut rng);
+
+        let domain = "test".as_bytes();
+        let connection_limit = 3u64;
 
         let veronymous_token_1 = root_token
-            .derive_token(domain, now, &issuer.public_key, &issuer.params, &mut rng)
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .connection(0, connection_limit)
+            .derive(&mut rng)
             .unwrap();
 
         let veronymous_token_2 = root_token
-            .derive_token(domain, now, &issuer.public_key, &issuer.params, &mut rng)
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .connection(0, connection_limit)
+            .derive(&mut rng)
             .unwrap();
 
         // Serial number for token_issuer 1 and 2 must be the same
@@ -346,26 +420,632 @@ mod tests {
         );
 
         let result = veronymous_token_1
-            .verify(domain, now, &issuer.public_key, &issuer.params)
+            .verify(
+                domain,
+                NOW,
+                connection_limit,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params,
+            )
             .unwrap();
 
         assert!(result);
 
-        // Test bad id
-        root_token.token_id = Fr::random(&mut rng);
+        // Another connection index gives another serial number
+        let veronymous_token_3 = root_token
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .connection(connection_limit - 1, connection_limit)
+            .derive(&mut rng)
+            .unwrap();
+
+        assert_ne!(
+            veronymous_token_1.serial_number.serial_number,
+            veronymous_token_3.serial_number.serial_number
+        );
+
+        let result = veronymous_token_3
+            .verify(
+                domain,
+                NOW,
+                connection_limit,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params,
+            )
+            .unwrap();
+
+        assert!(result);
+
+        // Index above a lower connection limit
+        let result = veronymous_token_3
+            .verify(
+                domain,
+                NOW,
+                2,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params,
+            )
+            .unwrap();
+
+        assert!(!result);
+
+        // Connection index must be lower than the limit
+        assert!(root_token
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .connection(connection_limit, connection_limit)
+            .derive(&mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_token_context() {
+        let mut rng = thread_rng();
+
+        let (issuer, root_token) = create_root_token(&mut rng);
+
+        let domain = "test".as_bytes();
+
+        // Token bound to a wireguard key
+        let wg_key = [1u8; 32];
+        let other_wg_key = [2u8; 32];
+
+        let bound_token = root_token
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .context(&wg_key)
+            .derive(&mut rng)
+            .unwrap();
+
+        for (context, expected) in [
+            (Some(&wg_key[..]), true),
+            (Some(&other_wg_key[..]), false),
+            (None, false),
+        ] {
+            let result = bound_token
+                .verify(
+                    domain,
+                    NOW,
+                    1,
+                    None,
+                    context,
+                    &issuer.public_key,
+                    &issuer.params,
+                )
+                .unwrap();
+
+            assert_eq!(expected, result);
+        }
+
+        // Unbound token replayed with a wireguard key
+        let unbound_token = root_token
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .derive(&mut rng)
+            .unwrap();
+
+        let result = unbound_token
+            .verify(
+                domain,
+                NOW,
+                1,
+                None,
+                Some(&wg_key),
+                &issuer.public_key,
+                &issuer.params,
+            )
+            .unwrap();
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_root_token_expiry() {
+        let mut rng = thread_rng();
+
+        let (issuer, root_token) = create_root_token(&mut rng);
 
+        let domain = "test".as_bytes();
+
+        // Root token expired
+        assert!(root_token
+            .derive_builder(domain, EXPIRY + 1, &issuer.public_key, &issuer.params)
+            .derive(&mut rng)
+            .is_err());
+
+        // Root token can be used up to the expiry
         let veronymous_token = root_token
-            .derive_token(domain, now, &issuer.public_key, &issuer.params, &mut rng)
+            .derive_builder(domain, EXPIRY, &issuer.public_key, &issuer.params)
+            .derive(&mut rng)
             .unwrap();
 
-        let veronymous_token_serialized = veronymous_token.serialize();
+        let result = veronymous_token
+            .verify(
+                domain,
+                EXPIRY,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params,
+            )
+            .unwrap();
+        assert!(result);
+
+        // Test bad expiry
+        let mut bad_root_token = root_token.clone();
+        bad_root_token.expiry = EXPIRY + 1;
+
+        let veronymous_token = bad_root_token
+            .derive_builder(domain, NOW, &issuer.public_key, &issuer.params)
+            .derive(&mut rng)
+            .unwrap();
+
+        let result = veronymous_token
+            .verify(
+                domain,
+                NOW,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params,
+            )
+            .unwrap();
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_identity_serial_number() {
+        let mut rng = thread_rng();
+
+        let (issuer, root_token) = create_root_token(&mut rng);
+
+        let veronymous_token = root_token
+            .derive_builder("test".as_bytes(), NOW, &issuer.public_key, &issuer.params)
+            .derive(&mut rng)
+            .unwrap();
+
+        let veronymous_token_serialized = veronymous_token.serialize().unwrap();
+
+        // Serial number set to the identity
+        let offset = veronymous_token.root.serialize().unwrap().len();
+        let mut bad_token_serialized = veronymous_token_serialized.clone();
+        bad_token_serialized[offset] = 0xc0;
+        bad_token_serialized[offset + 1..offset + 96].fill(0);
+
+        assert!(matches!(
+            VeronymousToken::deserialize(&bad_token_serialized),
+            Err(IdentityPointError(_))
+        ));
+    }
+
+    #[test]
+    fn test_selective_disclosure() {
+        let mut rng = thread_rng();
+
+        // Tier, region, bandwidth class
+        let attributes = vec![2u64, 44, 1];
+        let issuer = TokenIssuer::generate_with_attributes(attributes.len(), &mut rng);
+
+        let token_id = rand_non_zero_fr(&mut rng);
+        let blinding = rand_non_zero_fr(&mut rng);
+
+        let token_request =
+            create_root_token_request(&token_id, &blinding, &issuer.public_key, &issuer.params)
+                .unwrap();
+
+        let now = 1643629600u64;
+        let expiry = now + 30 * 24 * 60 * 60;
+
+        // Wrong number of attributes for the issuer key
+        assert!(issue_root_token(
+            &token_request,
+            expiry,
+            &attributes[1..],
+            &issuer.signing_key,
+            &issuer.public_key,
+            &issuer.params,
+            &mut rng,
+        )
+        .is_err());
+
+        let token_response = issue_root_token(
+            &token_request,
+            expiry,
+            &attributes,
+            &issuer.signing_key,
+            &issuer.public_key,
+            &issuer.params,
+            &mut rng,
+        )
+        .unwrap();
+
+        let token_response_deserialized =
+            RootTokenResponse::deserialize(&token_response.serialize().unwrap()).unwrap();
+        assert_eq!(token_response, token_response_deserialized);
+
+        let root_token = complete_root_token(
+            &token_response,
+            &token_id,
+            &blinding,
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .unwrap();
+        assert_eq!(attributes, root_token.attributes);
+
+        let root_token_deserialized =
+            RootVeronymousToken::deserialize(&root_token.serialize().unwrap()).unwrap();
+        assert_eq!(root_token, root_token_deserialized);
+
+        // Disclose the tier and bandwidth class, hide the region
+        let domain = "test".as_bytes();
+
+        let veronymous_token = root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .disclose(&[0, 2])
+            .derive(&mut rng)
+            .unwrap();
+
+        assert_eq!(Some(2), veronymous_token.disclosed_attribute(0));
+        assert_eq!(None, veronymous_token.disclosed_attribute(1));
+        assert_eq!(Some(1), veronymous_token.disclosed_attribute(2));
+
         let veronymous_token_deserialized =
-            VeronymousToken::deserialize(&veronymous_token_serialized).unwrap();
+            VeronymousToken::deserialize(&veronymous_token.serialize().unwrap()).unwrap();
         assert_eq!(veronymous_token, veronymous_token_deserialized);
 
-        let result = veronymous_token
-            .verify(domain, now, &issuer.public_key, &issuer.params)
+        assert!(veronymous_token
+            .verify(
+                domain,
+                now,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        // Prepared key of the verifier
+        let prepared_key = PreparedPsPublicKey::new(&issuer.public_key, &issuer.params);
+
+        assert!(veronymous_token
+            .verify_prepared(domain, now, 1, None, None, &prepared_key)
+            .unwrap());
+
+        // Disclosed attribute was changed
+        let mut bad_token = veronymous_token.clone();
+        bad_token.attributes[0] = TokenAttribute::Disclosed(3);
+
+        assert!(!bad_token
+            .verify(
+                domain,
+                now,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+        assert!(!bad_token
+            .verify_prepared(domain, now, 1, None, None, &prepared_key)
+            .unwrap());
+
+        // Missing attribute
+        let mut bad_token = veronymous_token.clone();
+        bad_token.attributes.pop();
+
+        assert!(!bad_token
+            .verify(
+                domain,
+                now,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        // Unknown attribute index
+        assert!(root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .disclose(&[3])
+            .derive(&mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_attribute_predicates() {
+        let mut rng = thread_rng();
+
+        // Tier, region
+        let attributes = vec![2u64, 44];
+        let issuer = TokenIssuer::generate_with_attributes(attributes.len(), &mut rng);
+
+        let token_id = rand_non_zero_fr(&mut rng);
+        let blinding = rand_non_zero_fr(&mut rng);
+
+        let token_request =
+            create_root_token_request(&token_id, &blinding, &issuer.public_key, &issuer.params)
+                .unwrap();
+
+        let now = 1643629600u64;
+        let expiry = now + 30 * 24 * 60 * 60;
+
+        let token_response = issue_root_token(
+            &token_request,
+            expiry,
+            &attributes,
+            &issuer.signing_key,
+            &issuer.public_key,
+            &issuer.params,
+            &mut rng,
+        )
+        .unwrap();
+
+        let root_token = complete_root_token(
+            &token_response,
+            &token_id,
+            &blinding,
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .unwrap();
+
+        // tier in {premium, business} and region == eu
+        let domain = "test".as_bytes();
+        let tier_predicate = AttributePredicate::Member(vec![1, 2]);
+        let region_predicate = AttributePredicate::Equal(44);
+
+        let veronymous_token = root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .predicate(0, tier_predicate.clone())
+            .predicate(1, region_predicate.clone())
+            .derive(&mut rng)
             .unwrap();
-        assert!(!result)
+
+        let veronymous_token_deserialized =
+            VeronymousToken::deserialize(&veronymous_token.serialize().unwrap()).unwrap();
+        assert_eq!(veronymous_token, veronymous_token_deserialized);
+
+        assert!(veronymous_token
+            .verify(
+                domain,
+                now,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        assert_eq!(None, veronymous_token.disclosed_attribute(0));
+        assert!(veronymous_token.proves_predicate(0, &tier_predicate));
+        assert!(veronymous_token.proves_predicate(1, &region_predicate));
+        assert!(!veronymous_token.proves_predicate(1, &AttributePredicate::Equal(45)));
+
+        // Predicate was changed
+        let mut bad_token = veronymous_token.clone();
+        bad_token.predicates[1].predicate = AttributePredicate::Equal(45);
+
+        assert!(!bad_token
+            .verify(
+                domain,
+                now,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        // Predicate moved to another attribute
+        let mut bad_token = veronymous_token.clone();
+        bad_token.predicates[1].index = 0;
+
+        assert!(!bad_token
+            .verify(
+                domain,
+                now,
+                1,
+                None,
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        // Unsatisfied predicate
+        assert!(root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .predicate(1, AttributePredicate::Equal(45))
+            .derive(&mut rng)
+            .is_err());
+
+        // Predicate on a disclosed attribute
+        assert!(root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .disclose(&[0])
+            .predicate(0, tier_predicate)
+            .derive(&mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_revocation() {
+        let mut rng = thread_rng();
+
+        // Revocation handle
+        let handle = generate_revocation_handle(&mut rng);
+        let attributes = vec![handle];
+        let issuer = TokenIssuer::generate_with_attributes(attributes.len(), &mut rng);
+
+        let token_id = rand_non_zero_fr(&mut rng);
+        let blinding = rand_non_zero_fr(&mut rng);
+
+        let token_request =
+            create_root_token_request(&token_id, &blinding, &issuer.public_key, &issuer.params)
+                .unwrap();
+
+        let now = 1643629600u64;
+        let expiry = now + 30 * 24 * 60 * 60;
+
+        let token_response = issue_root_token(
+            &token_request,
+            expiry,
+            &attributes,
+            &issuer.signing_key,
+            &issuer.public_key,
+            &issuer.params,
+            &mut rng,
+        )
+        .unwrap();
+
+        let root_token = complete_root_token(
+            &token_response,
+            &token_id,
+            &blinding,
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .unwrap();
+
+        // Revoke other root tokens
+        let bb_params = BbParams::generate(&mut rng);
+        let bb_signing_key = BbSigningKey::generate(&mut rng);
+        let bb_public_key = bb_signing_key.derive_public_key(&bb_params);
+
+        let mut revoked: Vec<Fr> = (0..3)
+            .map(|_| fr_from_u64(generate_revocation_handle(&mut rng)))
+            .collect();
+
+        let mut accumulator = Accumulator::new(&bb_params);
+        for handle in &revoked {
+            accumulator.add(handle, &bb_signing_key);
+        }
+
+        let revocation_info = RevocationInfo {
+            attribute_index: 0,
+            accumulator,
+            public_key: bb_public_key,
+            params: bb_params,
+        };
+
+        let revocation_info_deserialized =
+            RevocationInfo::deserialize(&revocation_info.serialize().unwrap()).unwrap();
+        assert_eq!(revocation_info, revocation_info_deserialized);
+
+        // Attribute index must fit in a byte
+        let mut invalid_revocation_info = revocation_info.clone();
+        invalid_revocation_info.attribute_index = 256;
+        assert!(invalid_revocation_info.serialize().is_err());
+
+        let witness = revocation_info
+            .accumulator
+            .non_membership_witness(
+                &fr_from_u64(handle),
+                &revoked,
+                &bb_signing_key,
+                &revocation_info.params,
+            )
+            .unwrap();
+
+        let domain = "test".as_bytes();
+
+        let veronymous_token = root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .revocation(&revocation_info, &witness)
+            .derive(&mut rng)
+            .unwrap();
+
+        let veronymous_token_deserialized =
+            VeronymousToken::deserialize(&veronymous_token.serialize().unwrap()).unwrap();
+        assert_eq!(veronymous_token, veronymous_token_deserialized);
+
+        assert!(veronymous_token
+            .verify(
+                domain,
+                now,
+                1,
+                Some(&revocation_info_deserialized),
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        // Missing non revocation proof
+        let mut bad_token = veronymous_token.clone();
+        bad_token.revocation = None;
+
+        assert!(!bad_token
+            .verify(
+                domain,
+                now,
+                1,
+                Some(&revocation_info),
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        // The handle cannot be disclosed
+        assert!(root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .disclose(&[0])
+            .revocation(&revocation_info, &witness)
+            .derive(&mut rng)
+            .is_err());
+
+        // Revoke the root token
+        let mut revoked_info = revocation_info.clone();
+        let handle = fr_from_u64(handle);
+        revoked_info.accumulator.add(&handle, &bb_signing_key);
+        revoked.push(handle);
+
+        assert!(revoked_info
+            .accumulator
+            .non_membership_witness(&handle, &revoked, &bb_signing_key, &revoked_info.params)
+            .is_none());
+
+        assert!(!veronymous_token
+            .verify(
+                domain,
+                now,
+                1,
+                Some(&revoked_info),
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
+
+        // The outdated witness does not verify against the new accumulator
+        let outdated_token = root_token
+            .derive_builder(domain, now, &issuer.public_key, &issuer.params)
+            .revocation(&revoked_info, &witness)
+            .derive(&mut rng)
+            .unwrap();
+
+        assert!(!outdated_token
+            .verify(
+                domain,
+                now,
+                1,
+                Some(&revoked_info),
+                None,
+                &issuer.public_key,
+                &issuer.params
+            )
+            .unwrap());
     }
 }
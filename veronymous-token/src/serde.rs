@@ -1,7 +1,7 @@
 use crate::error::VeronymousTokenError;
 
 pub trait Serializable {
-    fn serialize(&self) -> Vec<u8>;
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError>;
 
     fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
     where
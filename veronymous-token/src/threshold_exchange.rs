@@ -0,0 +1,339 @@
+/*
+* Root token exchange with threshold issuers.
+* The client sends the same request to the issuers, unblinds the partial root tokens of
+* a threshold of them and aggregates them into a root token under the aggregated public key.
+*/
+
+use crate::error::VeronymousTokenError;
+use crate::error::VeronymousTokenError::{DeserializationError, SerializationError};
+use crate::issuer::ThresholdTokenIssuer;
+use crate::root::RootVeronymousToken;
+use crate::serde::Serializable;
+use crate::utils::{read_attribute_values, read_u64, write_attribute_values};
+use crate::{RootTokenId, TokenBlinding};
+use crypto_common::fr_from_u64;
+use ps_signatures::keys::{PsParams, PsPublicKey};
+use ps_signatures::serde::Serializable as PsSerializable;
+use ps_signatures::threshold::{
+    aggregate_signatures, PartialBlindSignature, ThresholdSignatureRequest,
+};
+use rand::CryptoRng;
+use std::io::Cursor;
+
+// Size of the partial signature (index, sigma_1, sigma_2)
+const SERIALIZED_PARTIAL_SIGNATURE_SIZE: usize = 104;
+
+// Threshold blind signature request on the token id (message 0)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThresholdRootTokenRequest {
+    pub request: ThresholdSignatureRequest,
+}
+
+impl ThresholdRootTokenRequest {
+    pub fn verify(
+        &self,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+    ) -> Result<bool, VeronymousTokenError> {
+        // Only the token id is hidden
+        if self.request.hidden_indices != [0] {
+            return Ok(false);
+        }
+
+        self.request.verify(public_key, params).map_err(|e| {
+            VeronymousTokenError::VerificationError(format!("Could not verify proof. {:?}", e))
+        })
+    }
+}
+
+impl Serializable for ThresholdRootTokenRequest {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        self.request
+            .serialize()
+            .map_err(|e| SerializationError(format!("Could not serialize token request. {:?}", e)))
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError> {
+        let request = ThresholdSignatureRequest::deserialize(bytes).map_err(|e| {
+            DeserializationError(format!("Could not deserialize token request. {:?}", e))
+        })?;
+
+        Ok(Self { request })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PartialRootTokenResponse {
+    pub signature: PartialBlindSignature,
+
+    // Expiry and attributes signed by the issuer
+    pub expiry: u64,
+
+    pub attributes: Vec<u64>,
+}
+
+impl Serializable for PartialRootTokenResponse {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = self
+            .signature
+            .serialize()
+            .map_err(|e| SerializationError(format!("Could not serialize signature. {:?}", e)))?;
+        bytes.extend_from_slice(&self.expiry.to_be_bytes());
+        write_attribute_values(&self.attributes, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
+    where
+        Self: Sized,
+    {
+        if bytes.len() < SERIALIZED_PARTIAL_SIGNATURE_SIZE {
+            return Err(DeserializationError(format!(
+                "Serialized partial token response must have at least {} bytes",
+                SERIALIZED_PARTIAL_SIGNATURE_SIZE
+            )));
+        }
+
+        let signature =
+            PartialBlindSignature::deserialize(&bytes[..SERIALIZED_PARTIAL_SIGNATURE_SIZE])
+                .map_err(|e| {
+                    DeserializationError(format!("Could not deserialize signature. {:?}", e))
+                })?;
+
+        let mut cursor = Cursor::new(&bytes[SERIALIZED_PARTIAL_SIGNATURE_SIZE..]);
+
+        let expiry = read_u64(&mut cursor)?;
+        let attributes = read_attribute_values(&mut cursor)?;
+
+        if cursor.position() as usize != bytes.len() - SERIALIZED_PARTIAL_SIGNATURE_SIZE {
+            return Err(DeserializationError(
+                "Serialized partial token response has trailing bytes.".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            signature,
+            expiry,
+            attributes,
+        })
+    }
+}
+
+// Returns the request for the issuers and the token id opening to complete the root token
+pub fn create_threshold_root_token_request<R: CryptoRng + rand::RngCore>(
+    token_id: &RootTokenId,
+    public_key: &PsPublicKey,
+    params: &PsParams,
+    rng: &mut R,
+) -> Result<(ThresholdRootTokenRequest, TokenBlinding), VeronymousTokenError> {
+    let (request, mut openings) =
+        ThresholdSignatureRequest::new(&[(0, *token_id)], public_key, params, rng).map_err(
+            |e| {
+                VeronymousTokenError::ProofError(format!("Could not create token request. {:?}", e))
+            },
+        )?;
+
+    Ok((ThresholdRootTokenRequest { request }, openings.remove(0).1))
+}
+
+pub fn issue_partial_root_token(
+    token_request: &ThresholdRootTokenRequest,
+    expiry: u64,
+    attributes: &[u64],
+    issuer: &ThresholdTokenIssuer,
+) -> Result<PartialRootTokenResponse, VeronymousTokenError> {
+    if issuer.public_key.y_cap.len() != 2 + attributes.len() {
+        return Err(VeronymousTokenError::InvalidArgumentError(format!(
+            "Public key must have {} Y.",
+            2 + attributes.len()
+        )));
+    }
+
+    // 1) Verify the token_issuer
+    if !token_request.verify(&issuer.public_key, &issuer.params)? {
+        return Err(VeronymousTokenError::VerificationError(
+            "Token proof verification failed.".to_string(),
+        ));
+    }
+
+    // 2) Sign the token_issuer, its expiry and attributes with the key share
+    let mut messages = Vec::with_capacity(1 + attributes.len());
+    messages.push((1, fr_from_u64(expiry)));
+    messages.extend(
+        attributes
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (2 + i, fr_from_u64(*value))),
+    );
+
+    let signature = issuer
+        .key_share
+        .sign_request(
+            &token_request.request,
+            &messages,
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .map_err(|e| {
+            VeronymousTokenError::SigningError(format!("Could not sign token_issuer. {:?}", e))
+        })?;
+
+    Ok(PartialRootTokenResponse {
+        signature,
+        expiry,
+        attributes: attributes.to_vec(),
+    })
+}
+
+// Aggregates the partial root tokens with the share public key of their issuer
+pub fn complete_threshold_root_token(
+    token_responses: &[(PartialRootTokenResponse, PsPublicKey)],
+    token_id: &RootTokenId,
+    opening: &TokenBlinding,
+    public_key: &PsPublicKey,
+    params: &PsParams,
+) -> Result<RootVeronymousToken, VeronymousTokenError> {
+    let (first_response, _) = token_responses.first().ok_or_else(|| {
+        VeronymousTokenError::InvalidArgumentError("No partial root token.".to_string())
+    })?;
+
+    // 1) The issuers signed the same expiry and attributes
+    if token_responses.iter().any(|(response, _)| {
+        response.expiry != first_response.expiry || response.attributes != first_response.attributes
+    }) {
+        return Err(VeronymousTokenError::InvalidToken(
+            "Partial root tokens have different expiry or attributes.".to_string(),
+        ));
+    }
+
+    // 2) Unblind and aggregate the partial signatures
+    let signatures = token_responses
+        .iter()
+        .map(|(response, share_public_key)| {
+            let signature = response
+                .signature
+                .unblind(&[(0, *opening)], share_public_key)
+                .map_err(|e| {
+                    VeronymousTokenError::InvalidToken(format!(
+                        "Could not unblind partial signature. {:?}",
+                        e
+                    ))
+                })?;
+
+            Ok((response.signature.index, signature))
+        })
+        .collect::<Result<Vec<_>, VeronymousTokenError>>()?;
+
+    let signature = aggregate_signatures(&signatures).map_err(|e| {
+        VeronymousTokenError::InvalidToken(format!("Could not aggregate signatures. {:?}", e))
+    })?;
+
+    let root_token = RootVeronymousToken {
+        token_id: *token_id,
+        expiry: first_response.expiry,
+        attributes: first_response.attributes.clone(),
+        signature,
+    };
+
+    // 3) Verify the aggregated signature
+    if !root_token.verify(public_key, params)? {
+        return Err(VeronymousTokenError::InvalidToken(
+            "Signature is invalid".to_string(),
+        ));
+    }
+
+    Ok(root_token)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::issuer::ThresholdTokenIssuer;
+    use crate::serde::Serializable;
+    use crate::threshold_exchange::{
+        complete_threshold_root_token, create_threshold_root_token_request,
+        issue_partial_root_token, PartialRootTokenResponse, ThresholdRootTokenRequest,
+    };
+    use crypto_common::rand_non_zero_fr;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_threshold_root_token_exchange() {
+        let mut rng = thread_rng();
+
+        // 2 of 3 issuers
+        let issuers = ThresholdTokenIssuer::generate(2, 3, 1, &mut rng).unwrap();
+        let public_key = &issuers[0].public_key;
+        let params = &issuers[0].params;
+
+        let token_id = rand_non_zero_fr(&mut rng);
+
+        let (token_request, opening) =
+            create_threshold_root_token_request(&token_id, public_key, params, &mut rng).unwrap();
+
+        // Serialize de serialize
+        let token_request_serialized = token_request.serialize().unwrap();
+        let token_request =
+            ThresholdRootTokenRequest::deserialize(&token_request_serialized).unwrap();
+
+        // Issuers 1 and 3 issue the partial root tokens
+        let now = 1643629600u64;
+        let expiry = now + 30 * 24 * 60 * 60;
+
+        let token_responses: Vec<_> = [&issuers[0], &issuers[2]]
+            .iter()
+            .map(|issuer| {
+                let response =
+                    issue_partial_root_token(&token_request, expiry, &[7], issuer).unwrap();
+
+                let response_serialized = response.serialize().unwrap();
+                let response_deserialized =
+                    PartialRootTokenResponse::deserialize(&response_serialized).unwrap();
+                assert_eq!(response, response_deserialized);
+
+                (response, issuer.share_public_key.clone())
+            })
+            .collect();
+
+        let root_token = complete_threshold_root_token(
+            &token_responses,
+            &token_id,
+            &opening,
+            public_key,
+            params,
+        )
+        .unwrap();
+
+        assert!(root_token.verify(public_key, params).unwrap());
+
+        // Derive and verify a token under the aggregated public key
+        let domain = "test_domain".as_bytes();
+        let token = root_token
+            .derive_builder(domain, now, public_key, params)
+            .disclose(&[0])
+            .derive(&mut rng)
+            .unwrap();
+
+        assert!(token
+            .verify(domain, now, 1, None, None, public_key, params)
+            .unwrap());
+
+        // A single issuer is below the threshold
+        let result = complete_threshold_root_token(
+            &token_responses[..1],
+            &token_id,
+            &opening,
+            public_key,
+            params,
+        );
+        assert!(result.is_err());
+
+        // Issuers must sign the same attributes
+        let mut bad_responses = token_responses.clone();
+        bad_responses[1].0.attributes = vec![8];
+
+        let result =
+            complete_threshold_root_token(&bad_responses, &token_id, &opening, public_key, params);
+        assert!(result.is_err());
+    }
+}
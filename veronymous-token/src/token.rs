@@ -1,17 +1,32 @@
 use crate::error::VeronymousTokenError;
-use crate::error::VeronymousTokenError::{DeserializationError, SerializationError};
+use crate::error::VeronymousTokenError::{
+    DeserializationError, ProofError, SerializationError, VerificationError,
+};
+use crate::predicate::{AttributePredicate, ProofAttributePredicate};
+use crate::revocation::{ProofNonRevocation, RevocationInfo};
 use crate::serde::Serializable;
-use crate::utils::{read_fr, read_g1_point, read_g2_point};
-use crate::SerialNumber;
-use commitments::pedersen_commitment::PedersenCommitment;
-use commitments::pok_pedersen_commitment::CommitmentProof;
-use crypto_common::hash_to_fr;
-use pairing_plus::bls12_381::{Fr, G2};
+use crate::utils::{
+    read_attribute_predicate, read_fr, read_g1_point, read_g2_point, read_non_revocation_proof,
+    read_range_proof, read_u64, read_u8, write_attribute_predicate, write_element,
+    write_non_revocation_proof, write_range_proof,
+};
+use crate::{IssuerKeyId, SerialNumber};
+use commitments::pedersen_commitment::PedersenCommitmentCommitting;
+use commitments::pok_pedersen_commitment::{CommitmentProof, ProverCommitted, ProverCommitting};
+use commitments::pok_range::{bits_for_bound, RangeProof, RangeProofCommitted};
+use crypto_common::transcript::Transcript;
+use crypto_common::{fr_from_u64, multi_scalar_mul_const_time, rand_non_zero_fr};
+use ff_zeroize::Field;
+use pairing_plus::bls12_381::{Fr, G1, G2};
 use pairing_plus::hash_to_curve::HashToCurve;
 use pairing_plus::hash_to_field::ExpandMsgXmd;
 use pairing_plus::serdes::SerDes;
+use pairing_plus::CurveProjective;
 use ps_signatures::keys::{PsParams, PsPublicKey};
-use ps_signatures::pok_sig::PsPokOfSignatureProof;
+use ps_signatures::pok::{read_pok, PsPok};
+use ps_signatures::prepared::PreparedPsPublicKey;
+use ps_signatures::serde::Serializable as PsSerializable;
+use rand::CryptoRng;
 use sha2::Digest;
 use sha2::Sha256;
 use std::io::Cursor;
@@ -19,16 +34,20 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 const DST: &[u8] = b"BLS12381G2_XMD:BLAKE2B_SERIAL_NUMBER_GENERATOR:1_0_0";
 
-const SERIALIZED_TOKEN_SIZE: usize = 544;
+const COMMITMENT_GENERATOR_DST: &[u8] = b"BLS12381G1_XMD:BLAKE2B_COMMITMENT_GENERATOR:1_0_0";
+const COMMITMENT_GENERATOR_SEED: &[u8] = b"veronymous_commitment_generator";
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct ProofRootToken {
-    pub root: PedersenCommitment<G2>,
+// Versioned identifier of the token proof transcript
+pub const TOKEN_PROTOCOL: &[u8] = b"veronymous_token_v1";
 
-    pub randomness_commitment: G2,
+// Root tokens can be valid up to 2 ^ 32 seconds after the epoch
+pub const EXPIRY_RANGE_BITS: usize = 32;
 
-    pub blinding_response: Fr,
-}
+// Size of the token without the attributes, range proofs, predicates and non revocation proof
+const SERIALIZED_TOKEN_BASE_SIZE: usize = 869;
+
+const HIDDEN_ATTRIBUTE_TAG: u8 = 0;
+const DISCLOSED_ATTRIBUTE_TAG: u8 = 1;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ProofSerialNumber {
@@ -38,6 +57,37 @@ pub struct ProofSerialNumber {
 }
 
 impl ProofSerialNumber {
+    /*
+     * serial_number = generator ^ (1 / (token_id + index)), proven with the randomness commitment
+     * serial_number ^ (token_id_blinding + index_blinding) linked to the token id and index proofs.
+     */
+    pub fn new(
+        token_id: &Fr,
+        index: &Fr,
+        token_id_blinding_factor: &Fr,
+        index_blinding_factor: &Fr,
+        generator: &G2,
+    ) -> Result<Self, VeronymousTokenError> {
+        let mut exponent = *token_id;
+        exponent.add_assign(index);
+
+        let exponent = exponent.inverse().ok_or_else(|| {
+            ProofError("Could not derive the serial number for this connection index.".to_string())
+        })?;
+
+        let serial_number = multi_scalar_mul_const_time([*generator], [exponent]);
+
+        let mut blinding_factor = *token_id_blinding_factor;
+        blinding_factor.add_assign(index_blinding_factor);
+
+        let randomness_commitment = multi_scalar_mul_const_time([serial_number], [blinding_factor]);
+
+        Ok(Self {
+            serial_number,
+            randomness_commitment,
+        })
+    }
+
     pub fn serial_number_bytes(&self) -> Result<Vec<u8>, VeronymousTokenError> {
         let mut serial_number_bytes = Vec::with_capacity(96);
         self.serial_number
@@ -48,80 +98,516 @@ impl ProofSerialNumber {
 
         Ok(serial_number_bytes)
     }
+
+    pub fn challenge_bytes(&self, generator: &G2) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = Vec::new();
+
+        write_element(generator, false, &mut bytes)?;
+        write_element(&self.serial_number, false, &mut bytes)?;
+        write_element(&self.randomness_commitment, false, &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    // generator = serial_number ^ (token_id + connection_index)
+    pub fn verify(
+        &self,
+        token_id_response: &Fr,
+        index_response: &Fr,
+        generator: &G2,
+        challenge: &Fr,
+    ) -> Result<bool, VeronymousTokenError> {
+        CommitmentProof::new(
+            self.randomness_commitment,
+            vec![*token_id_response, *index_response],
+        )
+        .verify(
+            &[self.serial_number, self.serial_number],
+            generator,
+            challenge,
+        )
+        .map_err(|e| VerificationError(format!("Could not verify serial number pok. {:?}", e)))
+    }
 }
 
+/*
+* Connection index commitment (g ^ index * h ^ blinding) with the proof that
+* the index is lower than the issuer's connection limit.
+*/
 #[derive(Clone, Debug, PartialEq)]
-pub struct VeronymousToken {
-    // Hidden root token_issuer
-    pub root: ProofRootToken,
+pub struct ProofConnectionIndex {
+    pub commitment: G1,
+
+    pub randomness_commitment: G1,
+
+    pub blinding_response: Fr,
+
+    // index in [0, 2 ^ n)
+    pub lower_bound: RangeProof<G1>,
+
+    // (limit - 1 - index) in [0, 2 ^ n)
+    pub upper_bound: RangeProof<G1>,
+}
+
+impl ProofConnectionIndex {
+    // g ^ (limit - 1) / C
+    pub fn upper_bound_commitment(&self, connection_limit: u64, g: &G1) -> G1 {
+        let mut upper_bound_commitment = *g;
+        upper_bound_commitment.mul_assign(fr_from_u64(connection_limit - 1));
+        upper_bound_commitment.sub_assign(&self.commitment);
+
+        upper_bound_commitment
+    }
+
+    pub fn challenge_bytes(&self, g: &G1, h: &G1) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = Vec::new();
+
+        for point in [g, h, &self.randomness_commitment] {
+            write_element(point, false, &mut bytes)?;
+        }
+        write_element(&self.commitment, false, &mut bytes)?;
+        bytes.append(&mut self.lower_bound.challenge_bytes(g, h));
+        bytes.append(&mut self.upper_bound.challenge_bytes(g, h));
+
+        Ok(bytes)
+    }
+
+    // The index response links the commitment to the serial number
+    pub fn verify(
+        &self,
+        index_response: &Fr,
+        connection_limit: u64,
+        g: &G1,
+        h: &G1,
+        challenge: &Fr,
+    ) -> Result<bool, VeronymousTokenError> {
+        // Verify the connection index commitment
+        let pok = CommitmentProof::new(
+            self.randomness_commitment,
+            vec![*index_response, self.blinding_response],
+        );
+
+        if !pok
+            .verify(&[*g, *h], &self.commitment, challenge)
+            .map_err(|e| {
+                VerificationError(format!("Could not verify connection index pok. {:?}", e))
+            })?
+        {
+            return Ok(false);
+        }
+
+        // Verify connection index < connection limit
+        let bits = bits_for_bound(connection_limit);
+        let upper_bound_commitment = self.upper_bound_commitment(connection_limit, g);
+
+        for (range_proof, commitment) in [
+            (&self.lower_bound, &self.commitment),
+            (&self.upper_bound, &upper_bound_commitment),
+        ] {
+            if !range_proof
+                .verify(g, h, commitment, bits, challenge)
+                .map_err(|e| {
+                    VerificationError(format!(
+                        "Could not verify connection index range proof. {:?}",
+                        e
+                    ))
+                })?
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+// Prover side of the connection index proof
+pub struct ConnectionIndexCommitted {
+    index: Fr,
+
+    commitment: G1,
+
+    blinding: Fr,
+
+    committed: ProverCommitted<G1>,
+
+    lower_bound: RangeProofCommitted<G1>,
+
+    upper_bound: RangeProofCommitted<G1>,
+}
+
+impl ConnectionIndexCommitted {
+    // The index blinding factor must be the one used for the index in the serial number proof
+    pub fn new<R: CryptoRng + rand::RngCore>(
+        connection_index: u64,
+        connection_limit: u64,
+        index_blinding_factor: &Fr,
+        g: &G1,
+        h: &G1,
+        rng: &mut R,
+    ) -> Result<Self, VeronymousTokenError> {
+        if connection_index >= connection_limit {
+            return Err(VeronymousTokenError::InvalidArgumentError(format!(
+                "Connection index ({}) must be lower than the connection limit ({}).",
+                connection_index, connection_limit
+            )));
+        }
+
+        // 1) Hidden connection index (g ^ index)(h ^ blinding)
+        let index = fr_from_u64(connection_index);
+        let blinding = rand_non_zero_fr(rng);
+
+        let commitment =
+            PedersenCommitmentCommitting::new(Some(vec![*g, *h]), Some(vec![index, blinding]))
+                .map_err(|e| ProofError(format!("Could not create commitment. {:?}", e)))?
+                .finish();
+
+        // 2) Prove 0 <= index < limit
+        let bits = bits_for_bound(connection_limit);
+        let mut negated_blinding = blinding;
+        negated_blinding.negate();
+
+        let lower_bound = RangeProofCommitted::new(g, h, connection_index, &blinding, bits, rng)
+            .map_err(|e| ProofError(format!("Could not create range proof. {:?}", e)))?;
+
+        let upper_bound = RangeProofCommitted::new(
+            g,
+            h,
+            connection_limit - 1 - connection_index,
+            &negated_blinding,
+            bits,
+            rng,
+        )
+        .map_err(|e| ProofError(format!("Could not create range proof. {:?}", e)))?;
+
+        // 3) Commit to the randomness
+        let mut committing = ProverCommitting::new();
+        committing.commit(*g, Some(*index_blinding_factor));
+        committing.commit(*h, Some(rand_non_zero_fr(rng)));
 
-    // Issuer ps signature
-    pub root_signature: PsPokOfSignatureProof,
+        Ok(Self {
+            index,
+            commitment: commitment.0,
+            blinding,
+            committed: committing.finish(),
+            lower_bound,
+            upper_bound,
+        })
+    }
+
+    // Challenge bytes for fiat-shamir heuristic
+    pub fn challenge_bytes(&self) -> Result<Vec<u8>, VeronymousTokenError> {
+        let mut bytes = self.committed.challenge_bytes();
+
+        write_element(&self.commitment, false, &mut bytes)?;
+        bytes.append(&mut self.lower_bound.challenge_bytes());
+        bytes.append(&mut self.upper_bound.challenge_bytes());
+
+        Ok(bytes)
+    }
+
+    // Returns the proof and the index response
+    pub fn generate_proof(
+        self,
+        challenge: &Fr,
+    ) -> Result<(ProofConnectionIndex, Fr), VeronymousTokenError> {
+        let mut pok = self
+            .committed
+            .generate_proof(challenge, &[self.index, self.blinding])
+            .map_err(|e| ProofError(format!("Could not generate proof of knowledge. {:?}", e)))?;
+
+        let index_response = pok.responses.remove(0);
+
+        let proof = ProofConnectionIndex {
+            commitment: self.commitment,
+            randomness_commitment: pok.commitment,
+            blinding_response: pok.responses.remove(0),
+            lower_bound: self.lower_bound.generate_proof(challenge),
+            upper_bound: self.upper_bound.generate_proof(challenge),
+        };
+
+        Ok((proof, index_response))
+    }
+}
+
+/*
+* Expiry commitment (g ^ expiry * h ^ blinding) with the proof that
+* the root token expires at or after the epoch.
+*/
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofExpiry {
+    pub commitment: G1,
+
+    pub randomness_commitment: G1,
+
+    pub blinding_response: Fr,
+
+    // (expiry - epoch) in [0, 2 ^ EXPIRY_RANGE_BITS)
+    pub range: RangeProof<G1>,
+}
+
+impl ProofExpiry {
+    // C / g ^ epoch
+    pub fn range_commitment(&self, timestamp: u64, params: &PsParams) -> G1 {
+        let mut g_timestamp = params.g;
+        g_timestamp.mul_assign(fr_from_u64(timestamp));
+
+        let mut range_commitment = self.commitment;
+        range_commitment.sub_assign(&g_timestamp);
+
+        range_commitment
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenAttribute {
+    // Attribute value revealed to the verifier
+    Disclosed(u64),
+
+    // Attribute hidden in the root proof of knowledge
+    Hidden,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VeronymousToken {
+    // Issuer ps signature on the hidden token id, expiry and attributes
+    pub root: PsPok,
 
     // The serial number
     pub serial_number: ProofSerialNumber,
 
-    // Links serial number and root together
-    pub root_token_response: Fr,
+    // The hidden connection index
+    pub connection_index: ProofConnectionIndex,
+
+    // The hidden root token expiry
+    pub expiry: ProofExpiry,
+
+    // Issuer attributes, disclosed or hidden
+    pub attributes: Vec<TokenAttribute>,
+
+    // Predicates proven on hidden attributes
+    pub predicates: Vec<ProofAttributePredicate>,
+
+    // Proves that the hidden revocation handle is not revoked
+    pub revocation: Option<ProofNonRevocation>,
+
+    // Links serial number and connection index together
+    pub connection_index_response: Fr,
 }
 
 impl VeronymousToken {
+    // The context must be the one the token was derived with
+    #[allow(clippy::too_many_arguments)]
     pub fn verify(
         &self,
         domain: &[u8],
         timestamp: u64,
+        connection_limit: u64,
+        revocation_info: Option<&RevocationInfo>,
+        context: Option<&[u8]>,
         public_key: &PsPublicKey,
         params: &PsParams,
     ) -> Result<bool, VeronymousTokenError> {
-        if public_key.y_cap.len() < 1 {
-            return Err(VeronymousTokenError::InvalidArgumentError(format!(
-                "Public key must have at least 1 Y."
-            )));
+        self.verify_token(
+            domain,
+            timestamp,
+            connection_limit,
+            revocation_info,
+            context,
+            public_key,
+            params,
+            None,
+        )
+    }
+
+    // Faster verification for the verifiers of many tokens under the same key
+    pub fn verify_prepared(
+        &self,
+        domain: &[u8],
+        timestamp: u64,
+        connection_limit: u64,
+        revocation_info: Option<&RevocationInfo>,
+        context: Option<&[u8]>,
+        prepared_key: &PreparedPsPublicKey,
+    ) -> Result<bool, VeronymousTokenError> {
+        self.verify_token(
+            domain,
+            timestamp,
+            connection_limit,
+            revocation_info,
+            context,
+            &prepared_key.public_key,
+            &prepared_key.params,
+            Some(prepared_key),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn verify_token(
+        &self,
+        domain: &[u8],
+        timestamp: u64,
+        connection_limit: u64,
+        revocation_info: Option<&RevocationInfo>,
+        context: Option<&[u8]>,
+        public_key: &PsPublicKey,
+        params: &PsParams,
+        prepared_key: Option<&PreparedPsPublicKey>,
+    ) -> Result<bool, VeronymousTokenError> {
+        if public_key.y_cap.len() < 2 {
+            return Err(VeronymousTokenError::InvalidArgumentError(
+                "Public key must have at least 2 Y.".to_string(),
+            ));
         }
 
-        let token_proof = CommitmentProof::new(
-            self.root.randomness_commitment,
-            vec![
-                self.root_token_response.clone(),
-                self.root.blinding_response.clone(),
-            ],
-        );
+        // Every signed attribute must be disclosed or hidden
+        if public_key.y_cap_tilde.len() != 2 + self.attributes.len() {
+            return Ok(false);
+        }
 
-        let serial_number_proof = CommitmentProof::new(
-            self.serial_number.randomness_commitment,
-            vec![self.root_token_response.clone()],
+        if connection_limit == 0 {
+            return Err(VeronymousTokenError::InvalidArgumentError(
+                "Connection limit must be at least 1.".to_string(),
+            ));
+        }
+
+        // Disclosed attributes are revealed root messages
+        let revealed_attributes: Vec<(usize, Fr)> = self
+            .attributes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, attribute)| match attribute {
+                TokenAttribute::Disclosed(value) => Some((2 + index, fr_from_u64(*value))),
+                TokenAttribute::Hidden => None,
+            })
+            .collect();
+
+        // Token id and expiry responses link the root to the serial number and expiry
+        let (root_token_response, expiry_response) =
+            match (self.root.hidden_response(0), self.root.hidden_response(1)) {
+                (Some(root_token_response), Some(expiry_response)) => {
+                    (root_token_response, expiry_response)
+                }
+                _ => return Ok(false),
+            };
+
+        let expiry_proof = CommitmentProof::new(
+            self.expiry.randomness_commitment,
+            vec![expiry_response, self.expiry.blinding_response],
         );
 
         let serial_number_generator = compute_serial_number_generator(domain, timestamp);
+        let commitment_generator = compute_commitment_generator();
+        let commitment_gens = [params.g, commitment_generator];
 
         // Get the challenge
-        let mut challenge_bytes =
-            token_proof.challenge_bytes(&[public_key.y_cap_tilde[0], params.g_tilde]);
-        self.root
-            .root
-            .0
-            .serialize(&mut challenge_bytes, false)
-            .unwrap();
-        serial_number_generator
-            .serialize(&mut challenge_bytes, false)
-            .unwrap();
-        self.serial_number
-            .randomness_commitment
-            .serialize(&mut challenge_bytes, false)
-            .unwrap();
+        let disclosed_attributes: Vec<(usize, u64)> = self
+            .attributes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, attribute)| match attribute {
+                TokenAttribute::Disclosed(value) => Some((index, *value)),
+                TokenAttribute::Hidden => None,
+            })
+            .collect();
+
+        let mut transcript = token_transcript(
+            domain,
+            timestamp,
+            connection_limit,
+            &disclosed_attributes,
+            context,
+            public_key,
+        )?;
+        transcript.append_message(b"root", &self.root.challenge_bytes(public_key, params));
+        transcript.append_message(
+            b"serial_number",
+            &self
+                .serial_number
+                .challenge_bytes(&serial_number_generator)?,
+        );
+        transcript.append_message(
+            b"connection_index",
+            &self
+                .connection_index
+                .challenge_bytes(&params.g, &commitment_generator)?,
+        );
+        transcript.append_message(b"expiry", &expiry_proof.challenge_bytes(&commitment_gens));
+
+        let mut expiry_commitment_bytes = Vec::new();
+        write_element(&self.expiry.commitment, false, &mut expiry_commitment_bytes)?;
+        transcript.append_message(b"expiry_commitment", &expiry_commitment_bytes);
+
+        transcript.append_message(
+            b"expiry_range",
+            &self
+                .expiry
+                .range
+                .challenge_bytes(&params.g, &commitment_generator),
+        );
+        for predicate in &self.predicates {
+            transcript.append_message(
+                b"predicate",
+                &predicate.challenge_bytes(&commitment_generator, params)?,
+            );
+        }
+        if let (Some(proof), Some(revocation_info)) = (&self.revocation, revocation_info) {
+            transcript.append_message(b"revocation", &proof.challenge_bytes(revocation_info)?);
+        }
 
-        let challenge = hash_to_fr(challenge_bytes);
+        let challenge = transcript.challenge_fr(b"challenge");
 
-        // Verify root token_issuer
-        if !token_proof
-            .verify(
-                &[public_key.y_cap_tilde[0], params.g_tilde],
-                &self.root.root.0,
+        // Verify the root signature proof of knowledge
+        let root_result = match prepared_key {
+            Some(prepared_key) => self.root.verify_with_challenge_prepared(
+                &revealed_attributes,
+                prepared_key,
                 &challenge,
-            )
+            ),
+            None => self.root.verify_with_challenge(
+                &revealed_attributes,
+                public_key,
+                params,
+                &challenge,
+            ),
+        };
+
+        if !root_result.map_err(|e| {
+            VeronymousTokenError::VerificationError(format!(
+                "Could not verify token_issuer pok. {:?}",
+                e
+            ))
+        })? {
+            return Ok(false);
+        }
+
+        // Verify serial number: generator = serial_number ^ (token_id + connection_index)
+        if !self.serial_number.verify(
+            &root_token_response,
+            &self.connection_index_response,
+            &serial_number_generator,
+            &challenge,
+        )? {
+            return Ok(false);
+        }
+
+        // Verify the connection index commitment and connection index < connection limit
+        if !self.connection_index.verify(
+            &self.connection_index_response,
+            connection_limit,
+            &params.g,
+            &commitment_generator,
+            &challenge,
+        )? {
+            return Ok(false);
+        }
+
+        // Verify the expiry commitment
+        if !expiry_proof
+            .verify(&commitment_gens, &self.expiry.commitment, &challenge)
             .map_err(|e| {
                 VeronymousTokenError::VerificationError(format!(
-                    "Could not verify token_issuer pok. {:?}",
+                    "Could not verify expiry pok. {:?}",
                     e
                 ))
             })?
@@ -129,16 +615,20 @@ impl VeronymousToken {
             return Ok(false);
         }
 
-        // Verify serial number
-        if !serial_number_proof
+        // Verify epoch <= expiry
+        if !self
+            .expiry
+            .range
             .verify(
-                &[serial_number_generator],
-                &self.serial_number.serial_number,
+                &params.g,
+                &commitment_generator,
+                &self.expiry.range_commitment(timestamp, params),
+                EXPIRY_RANGE_BITS,
                 &challenge,
             )
             .map_err(|e| {
                 VeronymousTokenError::VerificationError(format!(
-                    "Could not verify token_issuer pok. {:?}",
+                    "Could not verify expiry range proof. {:?}",
                     e
                 ))
             })?
@@ -146,17 +636,68 @@ impl VeronymousToken {
             return Ok(false);
         }
 
-        // Verify root signature
-        if !self
-            .root_signature
-            .verify(&public_key, &params, &self.root.root)
-        {
-            return Ok(false);
+        // Verify the predicates on the hidden attributes
+        for predicate in &self.predicates {
+            let attribute_response = match self.hidden_attribute_response(predicate.index) {
+                Some(response) => response,
+                None => return Ok(false),
+            };
+
+            if !predicate.verify(
+                &attribute_response,
+                &commitment_generator,
+                params,
+                &challenge,
+            )? {
+                return Ok(false);
+            }
+        }
+
+        // Verify the revocation handle is not revoked
+        if let Some(revocation_info) = revocation_info {
+            let proof = match &self.revocation {
+                Some(proof) => proof,
+                None => return Ok(false),
+            };
+
+            let handle_response =
+                match self.hidden_attribute_response(revocation_info.attribute_index) {
+                    Some(response) => response,
+                    None => return Ok(false),
+                };
+
+            if !proof.verify(&handle_response, revocation_info, &challenge) {
+                return Ok(false);
+            }
         }
 
         Ok(true)
     }
 
+    // Root proof response of the attribute if it is hidden
+    fn hidden_attribute_response(&self, index: usize) -> Option<Fr> {
+        match self.attributes.get(index) {
+            Some(TokenAttribute::Hidden) => self.root.hidden_response(2 + index),
+            _ => None,
+        }
+    }
+
+    // Value of the attribute if it was disclosed
+    pub fn disclosed_attribute(&self, index: usize) -> Option<u64> {
+        match self.attributes.get(index) {
+            Some(TokenAttribute::Disclosed(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    // Whether the token proves the predicate on the hidden attribute.
+    // NOTE: The token must be verified
+    pub fn proves_predicate(&self, index: usize, predicate: &AttributePredicate) -> bool {
+        self.predicates
+            .iter()
+            .any(|proof| proof.index == index && proof.predicate == *predicate)
+    }
+
     // TODO: Might want to name something else
     pub fn serial_number(&self) -> Result<SerialNumber, VeronymousTokenError> {
         let bytes = self.serial_number.serial_number_bytes()?;
@@ -169,81 +710,166 @@ impl VeronymousToken {
 }
 
 impl Serializable for VeronymousToken {
-    fn serialize(&self) -> Vec<u8> {
+    fn serialize(&self) -> Result<Vec<u8>, VeronymousTokenError> {
         // TODO: Vec with capacity
-        let mut bytes = Vec::with_capacity(SERIALIZED_TOKEN_SIZE);
-
-        self.root.root.0.serialize(&mut bytes, true).unwrap();
-        self.root
-            .randomness_commitment
-            .serialize(&mut bytes, true)
-            .unwrap();
-        self.root
-            .blinding_response
-            .serialize(&mut bytes, true)
-            .unwrap();
-
-        self.root_signature
-            .sigma_1
-            .serialize(&mut bytes, true)
-            .unwrap();
-        self.root_signature
-            .sigma_2
-            .serialize(&mut bytes, true)
-            .unwrap();
+        let mut bytes = Vec::with_capacity(SERIALIZED_TOKEN_BASE_SIZE);
 
-        self.serial_number
-            .serial_number
-            .serialize(&mut bytes, true)
-            .unwrap();
-        self.serial_number
-            .randomness_commitment
-            .serialize(&mut bytes, true)
-            .unwrap();
+        bytes.append(
+            &mut self.root.serialize().map_err(|e| {
+                SerializationError(format!("Could not serialize root proof. {:?}", e))
+            })?,
+        );
+
+        write_element(&self.serial_number.serial_number, true, &mut bytes)?;
+        write_element(&self.serial_number.randomness_commitment, true, &mut bytes)?;
 
-        self.root_token_response
-            .serialize(&mut bytes, true)
-            .unwrap();
+        write_element(&self.connection_index.commitment, true, &mut bytes)?;
+        write_element(
+            &self.connection_index.randomness_commitment,
+            true,
+            &mut bytes,
+        )?;
+        write_element(&self.connection_index.blinding_response, true, &mut bytes)?;
 
-        bytes
+        write_element(&self.expiry.commitment, true, &mut bytes)?;
+        write_element(&self.expiry.randomness_commitment, true, &mut bytes)?;
+        write_element(&self.expiry.blinding_response, true, &mut bytes)?;
+
+        write_element(&self.connection_index_response, true, &mut bytes)?;
+
+        if self.attributes.len() > u8::MAX as usize || self.predicates.len() > u8::MAX as usize {
+            return Err(SerializationError(format!(
+                "Cannot have more than {} attributes or predicates.",
+                u8::MAX
+            )));
+        }
+
+        bytes.push(self.attributes.len() as u8);
+        for attribute in &self.attributes {
+            match attribute {
+                TokenAttribute::Disclosed(value) => {
+                    bytes.push(DISCLOSED_ATTRIBUTE_TAG);
+                    bytes.extend_from_slice(&value.to_be_bytes());
+                }
+                TokenAttribute::Hidden => bytes.push(HIDDEN_ATTRIBUTE_TAG),
+            }
+        }
+
+        write_range_proof(&self.connection_index.lower_bound, &mut bytes)?;
+        write_range_proof(&self.connection_index.upper_bound, &mut bytes)?;
+        write_range_proof(&self.expiry.range, &mut bytes)?;
+
+        bytes.push(self.predicates.len() as u8);
+        for predicate in &self.predicates {
+            write_attribute_predicate(predicate, &mut bytes)?;
+        }
+
+        match &self.revocation {
+            Some(proof) => {
+                bytes.push(1);
+                write_non_revocation_proof(proof, &mut bytes)?;
+            }
+            None => bytes.push(0),
+        }
+
+        Ok(bytes)
     }
 
     fn deserialize(bytes: &[u8]) -> Result<Self, VeronymousTokenError>
     where
         Self: Sized,
     {
-        if bytes.len() != SERIALIZED_TOKEN_SIZE {
+        if bytes.len() < SERIALIZED_TOKEN_BASE_SIZE {
             return Err(DeserializationError(format!(
-                "Serialized token_issuer must have {} bytes",
-                SERIALIZED_TOKEN_SIZE
+                "Serialized token_issuer must have at least {} bytes",
+                SERIALIZED_TOKEN_BASE_SIZE
             )));
         }
 
         let mut cursor = Cursor::new(bytes);
 
-        let root = ProofRootToken {
-            root: PedersenCommitment(read_g2_point(&mut cursor)?),
+        let root = read_pok(&mut cursor)
+            .map_err(|e| DeserializationError(format!("Could not decode root proof. {:?}", e)))?;
+
+        let serial_number = ProofSerialNumber {
+            serial_number: read_g2_point(&mut cursor)?,
             randomness_commitment: read_g2_point(&mut cursor)?,
-            blinding_response: read_fr(&mut cursor)?,
         };
 
-        let root_signature = PsPokOfSignatureProof {
-            sigma_1: read_g1_point(&mut cursor)?,
-            sigma_2: read_g1_point(&mut cursor)?,
+        let commitment = read_g1_point(&mut cursor)?;
+        let randomness_commitment = read_g1_point(&mut cursor)?;
+        let blinding_response = read_fr(&mut cursor)?;
+
+        let expiry_commitment = read_g1_point(&mut cursor)?;
+        let expiry_randomness_commitment = read_g1_point(&mut cursor)?;
+        let expiry_blinding_response = read_fr(&mut cursor)?;
+
+        let connection_index_response = read_fr(&mut cursor)?;
+
+        let attribute_count = read_u8(&mut cursor)? as usize;
+        let mut attributes = Vec::with_capacity(attribute_count);
+        for _ in 0..attribute_count {
+            let attribute = match read_u8(&mut cursor)? {
+                DISCLOSED_ATTRIBUTE_TAG => TokenAttribute::Disclosed(read_u64(&mut cursor)?),
+                HIDDEN_ATTRIBUTE_TAG => TokenAttribute::Hidden,
+                tag => {
+                    return Err(DeserializationError(format!(
+                        "Unknown attribute tag ({}).",
+                        tag
+                    )))
+                }
+            };
+
+            attributes.push(attribute);
+        }
+
+        let connection_index = ProofConnectionIndex {
+            commitment,
+            randomness_commitment,
+            blinding_response,
+            lower_bound: read_range_proof(&mut cursor)?,
+            upper_bound: read_range_proof(&mut cursor)?,
         };
 
-        let serial_number = ProofSerialNumber {
-            serial_number: read_g2_point(&mut cursor)?,
-            randomness_commitment: read_g2_point(&mut cursor)?,
+        let expiry = ProofExpiry {
+            commitment: expiry_commitment,
+            randomness_commitment: expiry_randomness_commitment,
+            blinding_response: expiry_blinding_response,
+            range: read_range_proof(&mut cursor)?,
         };
 
-        let root_token_response = read_fr(&mut cursor)?;
+        let predicate_count = read_u8(&mut cursor)? as usize;
+        let mut predicates = Vec::with_capacity(predicate_count);
+        for _ in 0..predicate_count {
+            predicates.push(read_attribute_predicate(&mut cursor)?);
+        }
+
+        let revocation = match read_u8(&mut cursor)? {
+            0 => None,
+            1 => Some(read_non_revocation_proof(&mut cursor)?),
+            flag => {
+                return Err(DeserializationError(format!(
+                    "Invalid non revocation proof flag ({}).",
+                    flag
+                )))
+            }
+        };
+
+        if cursor.position() as usize != bytes.len() {
+            return Err(DeserializationError(
+                "Serialized token_issuer has trailing bytes.".to_string(),
+            ));
+        }
 
         Ok(Self {
             root,
-            root_signature,
             serial_number,
-            root_token_response,
+            connection_index,
+            expiry,
+            attributes,
+            predicates,
+            revocation,
+            connection_index_response,
         })
     }
 }
@@ -277,6 +903,50 @@ pub fn get_next_epoch(now: u64, epoch_length: u64) -> u64 {
     now + time_until_next_epoch
 }
 
+/*
+* Transcript of the token proofs, bound to the verification context:
+* domain, timestamp, connection limit, disclosed attributes and issuer key.
+*/
+pub(crate) fn token_transcript(
+    domain: &[u8],
+    timestamp: u64,
+    connection_limit: u64,
+    disclosed_attributes: &[(usize, u64)],
+    context: Option<&[u8]>,
+    public_key: &PsPublicKey,
+) -> Result<Transcript, VeronymousTokenError> {
+    let public_key_bytes = public_key
+        .serialize()
+        .map_err(|e| SerializationError(format!("Could not serialize public key. {:?}", e)))?;
+
+    let mut transcript = Transcript::new(TOKEN_PROTOCOL);
+    transcript.append_message(b"domain", domain);
+    transcript.append_u64(b"timestamp", timestamp);
+    transcript.append_u64(b"connection_limit", connection_limit);
+    for (index, value) in disclosed_attributes {
+        transcript.append_u64(b"disclosed_attribute_index", *index as u64);
+        transcript.append_u64(b"disclosed_attribute_value", *value);
+    }
+    if let Some(context) = context {
+        transcript.append_message(b"context", context);
+    }
+    transcript.append_message(b"issuer_public_key", &public_key_bytes);
+
+    Ok(transcript)
+}
+
+// Identifies the issuer key of a root token without sending the key
+pub fn issuer_key_id(public_key: &PsPublicKey) -> Result<IssuerKeyId, VeronymousTokenError> {
+    let public_key_bytes = public_key
+        .serialize()
+        .map_err(|e| SerializationError(format!("Could not serialize public key. {:?}", e)))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&public_key_bytes);
+
+    Ok(hasher.finalize().into())
+}
+
 pub fn compute_serial_number_generator(domain: &[u8], timestamp: u64) -> G2 {
     let timestamp_bytes = timestamp.to_be_bytes();
 
@@ -287,9 +957,19 @@ pub fn compute_serial_number_generator(domain: &[u8], timestamp: u64) -> G2 {
     <G2 as HashToCurve<ExpandMsgXmd<blake2::Blake2b>>>::hash_to_curve(input_bytes, DST)
 }
 
+// Second pedersen generator for the hidden attribute commitments (connection index, expiry).
+// Nobody knows its discrete log with respect to g.
+pub fn compute_commitment_generator() -> G1 {
+    <G1 as HashToCurve<ExpandMsgXmd<blake2::Blake2b>>>::hash_to_curve(
+        COMMITMENT_GENERATOR_SEED,
+        COMMITMENT_GENERATOR_DST,
+    )
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::token::{get_current_epoch, get_next_epoch};
+    use crate::token::{get_current_epoch, get_next_epoch, issuer_key_id};
+    use ps_signatures::keys::{PsParams, PsSigningKey};
 
     #[test]
     fn test_get_next_epoch() {
@@ -309,4 +989,23 @@ mod tests {
         let current_epoch = get_current_epoch(now, 10 * 60, 2 * 60);
         assert_eq!(1645911600, current_epoch);
     }
+
+    #[test]
+    fn test_issuer_key_id() {
+        let mut rng = rand::thread_rng();
+
+        let params = PsParams::generate(&mut rng);
+        let public_key = PsSigningKey::generate(3, &params, &mut rng).derive_public_key(&params);
+        let other_public_key =
+            PsSigningKey::generate(3, &params, &mut rng).derive_public_key(&params);
+
+        assert_eq!(
+            issuer_key_id(&public_key).unwrap(),
+            issuer_key_id(&public_key.clone()).unwrap()
+        );
+        assert_ne!(
+            issuer_key_id(&public_key).unwrap(),
+            issuer_key_id(&other_public_key).unwrap()
+        );
+    }
 }
@@ -1,27 +1,53 @@
 use crate::error::VeronymousTokenError;
-use crate::error::VeronymousTokenError::DeserializationError;
+use crate::error::VeronymousTokenError::{
+    DeserializationError, IdentityPointError, SerializationError, SubgroupError,
+};
+use crate::predicate::{
+    AttributePredicate, ProofAttributePredicate, EQUAL_PREDICATE_TAG, MEMBER_PREDICATE_TAG,
+};
+use crate::revocation::ProofNonRevocation;
+use commitments::pok_range::{RangeProof, MAX_RANGE_BITS};
+use commitments::pok_set_membership::SetMembershipProof;
 use pairing_plus::bls12_381::{Fr, G1, G2};
 use pairing_plus::serdes::SerDes;
-use std::io::Cursor;
+use pairing_plus::{CurveProjective, SubgroupCheck};
+use std::io::{Cursor, Read};
 
+// A range proof bit proves membership of {0, 1}
+const RANGE_PROOF_BRANCHES: usize = 2;
+
+// Rejects the identity and the points outside of the prime order subgroup
 pub fn read_g1_point(bytes: &mut Cursor<&[u8]>) -> Result<G1, VeronymousTokenError> {
-    match G1::deserialize(bytes, true) {
-        Ok(point) => Ok(point),
-        Err(err) => Err(DeserializationError(format!(
-            "Could not decode G1 point. {:?}",
-            err
-        ))),
-    }
+    let point = match G1::deserialize(bytes, true) {
+        Ok(point) => point,
+        Err(err) => {
+            return Err(DeserializationError(format!(
+                "Could not decode G1 point. {:?}",
+                err
+            )))
+        }
+    };
+
+    check_point(&point, "G1 point")?;
+
+    Ok(point)
 }
 
+// Rejects the identity and the points outside of the prime order subgroup
 pub fn read_g2_point(bytes: &mut Cursor<&[u8]>) -> Result<G2, VeronymousTokenError> {
-    match G2::deserialize(bytes, true) {
-        Ok(point) => Ok(point),
-        Err(err) => Err(DeserializationError(format!(
-            "Could not decode G2 point. {:?}",
-            err
-        ))),
-    }
+    let point = match G2::deserialize(bytes, true) {
+        Ok(point) => point,
+        Err(err) => {
+            return Err(DeserializationError(format!(
+                "Could not decode G2 point. {:?}",
+                err
+            )))
+        }
+    };
+
+    check_point(&point, "G2 point")?;
+
+    Ok(point)
 }
 
 pub fn read_fr(bytes: &mut Cursor<&[u8]>) -> Result<Fr, VeronymousTokenError> {
@@ -33,3 +59,302 @@ pub fn read_fr(bytes: &mut Cursor<&[u8]>) -> Result<Fr, VeronymousTokenError> {
         ))),
     }
 }
+
+fn check_point<C: CurveProjective>(point: &C, name: &str) -> Result<(), VeronymousTokenError>
+where
+    C::Affine: SubgroupCheck,
+{
+    if point.is_zero() {
+        return Err(IdentityPointError(format!("{} is the identity.", name)));
+    }
+
+    if !point.into_affine().in_subgroup() {
+        return Err(SubgroupError(format!(
+            "{} is not in the prime order subgroup.",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+// Writes a point or a field element. Uncompressed for the challenge bytes
+pub fn write_element<T: SerDes>(
+    element: &T,
+    compressed: bool,
+    bytes: &mut Vec<u8>,
+) -> Result<(), VeronymousTokenError> {
+    element
+        .serialize(bytes, compressed)
+        .map_err(|e| SerializationError(format!("Could not serialize element. {:?}", e)))
+}
+
+pub fn read_u8(bytes: &mut Cursor<&[u8]>) -> Result<u8, VeronymousTokenError> {
+    let mut value = [0u8; 1];
+    bytes
+        .read_exact(&mut value)
+        .map_err(|err| DeserializationError(format!("Could not decode u8. {:?}", err)))?;
+
+    Ok(value[0])
+}
+
+pub fn read_u64(bytes: &mut Cursor<&[u8]>) -> Result<u64, VeronymousTokenError> {
+    let mut value = [0u8; 8];
+    bytes
+        .read_exact(&mut value)
+        .map_err(|err| DeserializationError(format!("Could not decode u64. {:?}", err)))?;
+
+    Ok(u64::from_be_bytes(value))
+}
+
+// Attribute values encoding: |count (u8)|values (u64)|
+pub fn write_attribute_values(
+    attributes: &[u64],
+    bytes: &mut Vec<u8>,
+) -> Result<(), VeronymousTokenError> {
+    if attributes.len() > u8::MAX as usize {
+        return Err(SerializationError(format!(
+            "Cannot have more than {} attributes.",
+            u8::MAX
+        )));
+    }
+
+    bytes.push(attributes.len() as u8);
+    for value in attributes {
+        bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    Ok(())
+}
+
+pub fn read_attribute_values(bytes: &mut Cursor<&[u8]>) -> Result<Vec<u64>, VeronymousTokenError> {
+    let count = read_u8(bytes)? as usize;
+
+    let mut attributes = Vec::with_capacity(count);
+    for _ in 0..count {
+        attributes.push(read_u64(bytes)?);
+    }
+
+    Ok(attributes)
+}
+
+/*
+* Set membership proof encoding (one branch per set element):
+* |branch commitments|branch challenges|branch responses|
+*/
+pub fn write_set_membership_proof(
+    proof: &SetMembershipProof<G1>,
+    branches: usize,
+    bytes: &mut Vec<u8>,
+) -> Result<(), VeronymousTokenError> {
+    if proof.commitments.len() != branches
+        || proof.challenges.len() != branches
+        || proof.responses.len() != branches
+    {
+        return Err(SerializationError(format!(
+            "Set membership proof must have {} branches.",
+            branches
+        )));
+    }
+
+    for commitment in &proof.commitments {
+        commitment
+            .serialize(bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize G1 point. {:?}", e)))?;
+    }
+    for field in proof.challenges.iter().chain(&proof.responses) {
+        field
+            .serialize(bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize Fr. {:?}", e)))?;
+    }
+
+    Ok(())
+}
+
+pub fn read_set_membership_proof(
+    bytes: &mut Cursor<&[u8]>,
+    branches: usize,
+) -> Result<SetMembershipProof<G1>, VeronymousTokenError> {
+    let mut commitments = Vec::with_capacity(branches);
+    for _ in 0..branches {
+        commitments.push(read_g1_point(bytes)?);
+    }
+
+    let mut challenges = Vec::with_capacity(branches);
+    for _ in 0..branches {
+        challenges.push(read_fr(bytes)?);
+    }
+
+    let mut responses = Vec::with_capacity(branches);
+    for _ in 0..branches {
+        responses.push(read_fr(bytes)?);
+    }
+
+    Ok(SetMembershipProof {
+        commitments,
+        challenges,
+        responses,
+    })
+}
+
+/*
+* Range proof encoding:
+* |bits (u8)|bit commitments|bit set membership proofs|
+*/
+pub fn write_range_proof(
+    proof: &RangeProof<G1>,
+    bytes: &mut Vec<u8>,
+) -> Result<(), VeronymousTokenError> {
+    if proof.bits() == 0 || proof.bits() > MAX_RANGE_BITS || proof.bit_proofs.len() != proof.bits()
+    {
+        return Err(SerializationError(format!(
+            "Range proof must have between 1 and {} bits.",
+            MAX_RANGE_BITS
+        )));
+    }
+
+    bytes.push(proof.bits() as u8);
+
+    for bit_commitment in &proof.bit_commitments {
+        bit_commitment
+            .serialize(bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize G1 point. {:?}", e)))?;
+    }
+
+    for bit_proof in &proof.bit_proofs {
+        write_set_membership_proof(bit_proof, RANGE_PROOF_BRANCHES, bytes)?;
+    }
+
+    Ok(())
+}
+
+pub fn read_range_proof(bytes: &mut Cursor<&[u8]>) -> Result<RangeProof<G1>, VeronymousTokenError> {
+    let bits = read_u8(bytes)? as usize;
+
+    if bits == 0 || bits > MAX_RANGE_BITS {
+        return Err(DeserializationError(format!(
+            "Range proof must have between 1 and {} bits.",
+            MAX_RANGE_BITS
+        )));
+    }
+
+    let mut bit_commitments = Vec::with_capacity(bits);
+    for _ in 0..bits {
+        bit_commitments.push(read_g1_point(bytes)?);
+    }
+
+    let mut bit_proofs = Vec::with_capacity(bits);
+    for _ in 0..bits {
+        bit_proofs.push(read_set_membership_proof(bytes, RANGE_PROOF_BRANCHES)?);
+    }
+
+    Ok(RangeProof {
+        bit_commitments,
+        bit_proofs,
+    })
+}
+
+/*
+* Attribute predicate proof encoding:
+* |index (u8)|tag (u8)|value (u64) or count (u8) and values (u64)|commitment|
+* |randomness commitment|blinding response|set membership proof|
+*/
+pub fn write_attribute_predicate(
+    proof: &ProofAttributePredicate,
+    bytes: &mut Vec<u8>,
+) -> Result<(), VeronymousTokenError> {
+    if proof.index > u8::MAX as usize {
+        return Err(SerializationError(format!(
+            "Attribute index ({}) cannot be encoded.",
+            proof.index
+        )));
+    }
+
+    bytes.push(proof.index as u8);
+
+    match &proof.predicate {
+        AttributePredicate::Equal(value) => {
+            bytes.push(EQUAL_PREDICATE_TAG);
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        AttributePredicate::Member(values) => {
+            bytes.push(MEMBER_PREDICATE_TAG);
+            write_attribute_values(values, bytes)?;
+        }
+    }
+
+    for point in [&proof.commitment, &proof.randomness_commitment] {
+        point
+            .serialize(bytes, true)
+            .map_err(|e| SerializationError(format!("Could not serialize G1 point. {:?}", e)))?;
+    }
+    proof
+        .blinding_response
+        .serialize(bytes, true)
+        .map_err(|e| SerializationError(format!("Could not serialize Fr. {:?}", e)))?;
+
+    write_set_membership_proof(&proof.membership, proof.predicate.set().len(), bytes)
+}
+
+pub fn read_attribute_predicate(
+    bytes: &mut Cursor<&[u8]>,
+) -> Result<ProofAttributePredicate, VeronymousTokenError> {
+    let index = read_u8(bytes)? as usize;
+
+    let predicate = match read_u8(bytes)? {
+        EQUAL_PREDICATE_TAG => AttributePredicate::Equal(read_u64(bytes)?),
+        MEMBER_PREDICATE_TAG => AttributePredicate::Member(read_attribute_values(bytes)?),
+        tag => {
+            return Err(DeserializationError(format!(
+                "Unknown predicate tag ({}).",
+                tag
+            )))
+        }
+    };
+
+    let commitment = read_g1_point(bytes)?;
+    let randomness_commitment = read_g1_point(bytes)?;
+    let blinding_response = read_fr(bytes)?;
+    let membership = read_set_membership_proof(bytes, predicate.set().len())?;
+
+    Ok(ProofAttributePredicate {
+        index,
+        predicate,
+        commitment,
+        randomness_commitment,
+        blinding_response,
+        membership,
+    })
+}
+
+// Non revocation proof encoding: |blinded witness|D|R|R_D|r response|d response|
+pub fn write_non_revocation_proof(
+    proof: &ProofNonRevocation,
+    bytes: &mut Vec<u8>,
+) -> Result<(), VeronymousTokenError> {
+    for point in [
+        &proof.blinded_witness,
+        &proof.d_commitment,
+        &proof.randomness_commitment,
+        &proof.d_randomness_commitment,
+    ] {
+        write_element(point, true, bytes)?;
+    }
+    write_element(&proof.r_response, true, bytes)?;
+    write_element(&proof.d_response, true, bytes)?;
+
+    Ok(())
+}
+
+pub fn read_non_revocation_proof(
+    bytes: &mut Cursor<&[u8]>,
+) -> Result<ProofNonRevocation, VeronymousTokenError> {
+    Ok(ProofNonRevocation {
+        blinded_witness: read_g1_point(bytes)?,
+        d_commitment: read_g1_point(bytes)?,
+        randomness_commitment: read_g1_point(bytes)?,
+        d_randomness_commitment: read_g1_point(bytes)?,
+        r_response: read_fr(bytes)?,
+        d_response: read_fr(bytes)?,
+    })
+}
@@ -0,0 +1,306 @@
+/*
+* Property based tests of the token protocol.
+* Issuance and verification compute pairings, the properties are ignored by default.
+* Run them with: cargo test --test token_properties -- --ignored
+*/
+
+use crypto_common::rand_non_zero_fr;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::subsequence;
+use ps_signatures::keys::{PsPublicKey, PsSigningKey};
+use ps_signatures::serde::Serializable as PsSerializable;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use veronymous_token::issuer::TokenIssuer;
+use veronymous_token::root::RootVeronymousToken;
+use veronymous_token::root_exchange::{
+    complete_root_token, create_root_token_request, issue_root_token, RootTokenRequest,
+    RootTokenResponse,
+};
+use veronymous_token::serde::Serializable;
+use veronymous_token::token::VeronymousToken;
+
+const NOW: u64 = 1643629600;
+
+const TOKEN_LIFETIME: u64 = 30 * 24 * 60 * 60;
+
+struct Issued {
+    issuer: TokenIssuer,
+    token_request: RootTokenRequest,
+    token_response: RootTokenResponse,
+    root_token: RootVeronymousToken,
+}
+
+fn issue(attributes: &[u64], rng: &mut StdRng) -> Issued {
+    let issuer = TokenIssuer::generate_with_attributes(attributes.len(), rng);
+
+    let token_id = rand_non_zero_fr(rng);
+    let blinding = rand_non_zero_fr(rng);
+
+    let token_request =
+        create_root_token_request(&token_id, &blinding, &issuer.public_key, &issuer.params)
+            .unwrap();
+
+    let token_response = issue_root_token(
+        &token_request,
+        NOW + TOKEN_LIFETIME,
+        attributes,
+        &issuer.signing_key,
+        &issuer.public_key,
+        &issuer.params,
+        rng,
+    )
+    .unwrap();
+
+    let root_token = complete_root_token(
+        &token_response,
+        &token_id,
+        &blinding,
+        &issuer.public_key,
+        &issuer.params,
+    )
+    .unwrap();
+
+    Issued {
+        issuer,
+        token_request,
+        token_response,
+        root_token,
+    }
+}
+
+fn derive(
+    issued: &Issued,
+    domain: &[u8],
+    timestamp: u64,
+    connection_index: u64,
+    connection_limit: u64,
+    disclosed: &[usize],
+    rng: &mut StdRng,
+) -> VeronymousToken {
+    issued
+        .root_token
+        .derive_builder(
+            domain,
+            timestamp,
+            &issued.issuer.public_key,
+            &issued.issuer.params,
+        )
+        .connection(connection_index, connection_limit)
+        .disclose(disclosed)
+        .derive(rng)
+        .unwrap()
+}
+
+fn verifies(
+    token: &VeronymousToken,
+    domain: &[u8],
+    timestamp: u64,
+    connection_limit: u64,
+    issuer: &TokenIssuer,
+) -> bool {
+    token
+        .verify(
+            domain,
+            timestamp,
+            connection_limit,
+            None,
+            None,
+            &issuer.public_key,
+            &issuer.params,
+        )
+        .unwrap_or(false)
+}
+
+// Attribute values with the indices to disclose
+fn attributes_strategy() -> impl Strategy<Value = (Vec<u64>, Vec<usize>)> {
+    vec(any::<u64>(), 0..3).prop_flat_map(|attributes| {
+        let indices: Vec<usize> = (0..attributes.len()).collect();
+        let count = indices.len();
+
+        (Just(attributes), subsequence(indices, 0..=count))
+    })
+}
+
+// Connection limit with an index below it
+fn connection_strategy() -> impl Strategy<Value = (u64, u64)> {
+    (1u64..16).prop_flat_map(|limit| (Just(limit), 0..limit))
+}
+
+proptest! {
+    #[test]
+    #[ignore]
+    fn honest_tokens_verify(
+        seed in any::<u64>(),
+        (attributes, disclosed) in attributes_strategy(),
+        (connection_limit, connection_index) in connection_strategy(),
+        elapsed in 0..=TOKEN_LIFETIME,
+        domain in vec(any::<u8>(), 1..32),
+    ) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let issued = issue(&attributes, &mut rng);
+
+        let token = derive(
+            &issued,
+            &domain,
+            NOW + elapsed,
+            connection_index,
+            connection_limit,
+            &disclosed,
+            &mut rng,
+        );
+
+        prop_assert!(verifies(&token, &domain, NOW + elapsed, connection_limit, &issued.issuer));
+    }
+
+    #[test]
+    #[ignore]
+    fn serial_numbers_are_deterministic(
+        seed in any::<u64>(),
+        (connection_limit, connection_index) in connection_strategy(),
+        domain in vec(any::<u8>(), 1..32),
+    ) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let issued = issue(&[], &mut rng);
+
+        let token_1 = derive(&issued, &domain, NOW, connection_index, connection_limit, &[], &mut rng);
+        let token_2 = derive(&issued, &domain, NOW, connection_index, connection_limit, &[], &mut rng);
+
+        prop_assert_eq!(token_1.serial_number().unwrap(), token_2.serial_number().unwrap());
+
+        // The proofs themselves are randomized
+        prop_assert_ne!(token_1.root, token_2.root);
+    }
+
+    #[test]
+    #[ignore]
+    fn serial_numbers_are_unlinkable(
+        seed in any::<u64>(),
+        (connection_limit, connection_index) in connection_strategy(),
+        domain_1 in vec(any::<u8>(), 1..32),
+        domain_2 in vec(any::<u8>(), 1..32),
+        epoch_offset in 1..TOKEN_LIFETIME,
+    ) {
+        prop_assume!(domain_1 != domain_2);
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let issued = issue(&[], &mut rng);
+
+        let token = derive(&issued, &domain_1, NOW, connection_index, connection_limit, &[], &mut rng);
+        let serial_number = token.serial_number().unwrap();
+
+        // Other domain
+        let other_domain =
+            derive(&issued, &domain_2, NOW, connection_index, connection_limit, &[], &mut rng);
+        prop_assert_ne!(serial_number, other_domain.serial_number().unwrap());
+
+        // Other epoch
+        let other_epoch = derive(
+            &issued,
+            &domain_1,
+            NOW + epoch_offset,
+            connection_index,
+            connection_limit,
+            &[],
+            &mut rng,
+        );
+        prop_assert_ne!(serial_number, other_epoch.serial_number().unwrap());
+
+        // Other root token
+        let other_issued = issue(&[], &mut rng);
+        let other_root = derive(
+            &other_issued,
+            &domain_1,
+            NOW,
+            connection_index,
+            connection_limit,
+            &[],
+            &mut rng,
+        );
+        prop_assert_ne!(serial_number, other_root.serial_number().unwrap());
+    }
+
+    #[test]
+    #[ignore]
+    fn modified_tokens_do_not_verify(
+        seed in any::<u64>(),
+        (attributes, disclosed) in attributes_strategy(),
+        position in any::<prop::sample::Index>(),
+        bit in 0u8..8,
+    ) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let issued = issue(&attributes, &mut rng);
+
+        let domain = b"test_domain";
+        let token = derive(&issued, domain, NOW, 0, 2, &disclosed, &mut rng);
+
+        // Flip a bit of any field
+        let mut bytes = token.serialize().unwrap();
+        let position = position.index(bytes.len());
+        bytes[position] ^= 1 << bit;
+
+        if let Ok(modified_token) = VeronymousToken::deserialize(&bytes) {
+            prop_assert!(!verifies(&modified_token, domain, NOW, 2, &issued.issuer));
+        }
+
+        // Verified in another context
+        prop_assert!(!verifies(&token, b"other_domain", NOW, 2, &issued.issuer));
+        prop_assert!(!verifies(&token, domain, NOW + TOKEN_LIFETIME + 1, 2, &issued.issuer));
+
+        let other_issuer = TokenIssuer::generate_with_attributes(attributes.len(), &mut rng);
+        prop_assert!(!verifies(&token, domain, NOW, 2, &other_issuer));
+    }
+
+    #[test]
+    #[ignore]
+    fn serializable_round_trips(
+        seed in any::<u64>(),
+        (attributes, disclosed) in attributes_strategy(),
+    ) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let issued = issue(&attributes, &mut rng);
+
+        let token = derive(&issued, b"test_domain", NOW, 0, 1, &disclosed, &mut rng);
+
+        prop_assert_eq!(
+            &RootTokenRequest::deserialize(&issued.token_request.serialize().unwrap()).unwrap(),
+            &issued.token_request
+        );
+        prop_assert_eq!(
+            &RootTokenResponse::deserialize(&issued.token_response.serialize().unwrap()).unwrap(),
+            &issued.token_response
+        );
+        prop_assert_eq!(
+            &RootVeronymousToken::deserialize(&issued.root_token.serialize().unwrap()).unwrap(),
+            &issued.root_token
+        );
+        prop_assert_eq!(
+            &VeronymousToken::deserialize(&token.serialize().unwrap()).unwrap(),
+            &token
+        );
+
+        let issuer = &issued.issuer;
+        prop_assert_eq!(
+            &<PsSigningKey as PsSerializable>::deserialize(
+                &PsSerializable::serialize(&issuer.signing_key).unwrap()
+            )
+            .unwrap(),
+            &issuer.signing_key
+        );
+        prop_assert_eq!(
+            &<PsPublicKey as PsSerializable>::deserialize(
+                &PsSerializable::serialize(&issuer.public_key).unwrap()
+            )
+            .unwrap(),
+            &issuer.public_key
+        );
+        prop_assert_eq!(
+            &<ps_signatures::keys::PsParams as PsSerializable>::deserialize(
+                &PsSerializable::serialize(&issuer.params).unwrap()
+            )
+            .unwrap(),
+            &issuer.params
+        );
+    }
+}